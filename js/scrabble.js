@@ -35,6 +35,7 @@ class Scrabble {
     this.token = select("meta[name='id-token']").attr("content");
     this.player = select("meta[name='player']").attr("content"); // FIXME: use real username
     this.game_id = select("meta[name='game-id']").attr("content");
+    this.seed = select("meta[name='seed']").attr("content");
 
     this.socket = socket
     this.cursor = -1;
@@ -51,10 +52,21 @@ class Scrabble {
     this.scores = {};
     this._players = [];
     this.playersOnline = [];
+    this.spectatorCount = 0;
     this.first_load = true;
 
     this._rack = new Rack(this, "#rack-container", []);
 
+    // rendered with the page, so the board shows before the socket connects
+    const initialState = document.getElementById("initial-state");
+    if (initialState) {
+      const { game, rack, remaining } = JSON.parse(initialState.textContent);
+      if (game) { this.handleGameState({ game }) }
+      if (rack) { this.handleRack({ rack }) }
+      if (remaining) { this.handleRemaining({ remaining }) }
+      this.first_load = true;
+    }
+
     this.joinGameAs(this.token, this.player);
 
     Notification.requestPermission();
@@ -76,20 +88,39 @@ class Scrabble {
   }
 
   joinGameAs(token, player) {
-    this.channel = this.socket.channel(`game:${this.game_id}-000000`, { player, token });
+    let params = { player, token };
+    if (this.seed) { params.seed = parseInt(this.seed, 10); }
+
+    this.channel = this.socket.channel(`game:${this.game_id}-000000`, params);
     window.channel = this.channel;
     this.channel.join()
       .receive("ok", resp => { console.log(`joined game:${this.game_id}`, resp) })
       .receive("error", resp => { console.error("unable to join", resp) })
 
-    this.channel.on("player-state", ({ game, rack, remaining, message }) => {
+    // lets the server tell a live socket from a dropped one
+    clearInterval(this.heartbeat);
+    this.heartbeat = setInterval(() => this.channel.push("heartbeat", {}), 30000);
+
+    const handlePlayerState = ({ game, rack, remaining, message, recent }) => {
 
       console.log({ game, rack, remaining });
 
       if (game) { this.handleGameState({ game }) }
       if (rack) { this.handleRack({ rack }) }
       if (remaining) { this.handleRemaining({ remaining }) }
+      // info messages sent while this socket was away
+      if (recent && recent.length) {
+        this.flash("info", { message: recent.map(({ message }) => message).join("<br>") })
+      }
       if (message) { this.flash("info", { message }) }
+    };
+
+    this.channel.on("player-state", handlePlayerState);
+
+    // sent instead of player-state when rejoining a game in progress
+    this.channel.on("resync", payload => {
+      console.log("latest moves", payload.moves);
+      handlePlayerState(payload);
     });
 
     this.channel.on("new_proposed", payload => {
@@ -99,18 +130,31 @@ class Scrabble {
 
     this.channel.on("error", payload => {
       this.flash("error", payload);
+      // the rack this client played from was out of date
+      if (payload.resync) { handlePlayerState(payload.resync) }
     });
 
     this.channel.on("info", payload => {
       this.flash("info", payload);
     });
 
+    this.channel.on("game-over", ({ winner }) => {
+      if (winner) {
+        this.flash("info", { message: `game over! ${winner.player} wins with ${winner.score} points` });
+      }
+    });
+
+    this.channel.on("rematch", ({ url }) => {
+      this.flash("info", { message: `<a href="${url}">the rematch is ready</a>` });
+    });
+
     this.channel.on("log", payload => {
       console.log(payload)
     });
 
     this.channel.on("presence", payload => {
       this.playersOnline = payload.online;
+      this.spectatorCount = payload.spectator_count || 0;
       this.drawScores();
     });
 
@@ -181,8 +225,14 @@ class Scrabble {
     this.players = game.players;
 
     this.gameOver = game.game_over;
+    this.moveCount = game.move_count;
+    this.turnNumber = game.state === "Started" ? game.move_count + 1 : null;
     this.passAllowed = game.pass_allowed;
     this.swapAllowed = game.swap_allowed;
+    this.forfeitAllowed = game.state === "Started" && !game.spectating;
+    this.redrawAllowed = game.redraw_allowed;
+    this.challengeAllowed = game.challenge_allowed;
+    this.rematchAllowed = game.state === "Over" && !game.spectating;
 
     if (this.gameOver) {
       this.current_player = null;
@@ -195,6 +245,10 @@ class Scrabble {
     this.drawSubmitButton();
     this.drawSwapButton();
     this.drawPassButton();
+    this.drawForfeitButton();
+    this.drawRedrawButton();
+    this.drawChallengeButton();
+    this.drawRematchButton();
     this.first_load = false;
   }
 
@@ -401,23 +455,49 @@ class Scrabble {
       .receive("error", ({ message }) => this.flash("error", { message }));
   }
 
+  // moves are sent with the move count they were made against, so the server can refuse a
+  // repeated or out of date move
+  pushMove(key, payload) {
+    return this.push(key, Object.assign({ move_count: this.moveCount }, payload));
+  }
+
   submitProposed() {
-    this.push("play", this.proposed)
+    this.pushMove("play", this.proposed)
       .receive("ok", (payload) => this.handleRack(payload));
   }
 
   sendProposed() {
     this.push("proposed", this.proposed)
-      .receive("ok", ({ message }) => this.flash("info", { message }))
+      .receive("ok", ({ words, total, bingo }) => {
+        let scored = words.map(({ word, score }) => `${word} ${score}`);
+        if (bingo) { scored.push("bingo!"); }
+        this.flash("info", { message: `${scored.join(", ")} (${total})` });
+      })
   }
 
   sendSwapped() {
-    this.push("swap", this.proposed)
+    this.pushMove("swap", this.proposed)
       .receive("ok", (payload) => this.handleRack(payload))
   }
 
   sendPassed() {
-    this.push("pass", {});
+    this.pushMove("pass", {});
+  }
+
+  sendForfeit() {
+    this.push("forfeit", {});
+  }
+
+  sendRedraw() {
+    this.push("redraw", {});
+  }
+
+  sendChallenge() {
+    this.push("challenge", {});
+  }
+
+  sendRematch() {
+    this.push("rematch", {});
   }
 
   clickSetCursor(i) {
@@ -567,6 +647,16 @@ class Scrabble {
     let players = this.players;
 
     let table = select('#score-container table');
+    let captionParts = [];
+    if (this.turnNumber) { captionParts.push(`Turn ${this.turnNumber}`); }
+    if (this.spectatorCount) { captionParts.push(`${this.spectatorCount} watching`); }
+
+    let caption = table.selectAll('caption')
+      .data(captionParts.length ? [captionParts.join(' · ')] : []);
+    caption.exit().remove();
+    caption.merge(caption.enter().insert('caption', ':first-child'))
+      .text(text => text);
+
     let headerSelection = table.select('thead').selectAll('tr').data([0]);
     let headEnter = headerSelection.enter()
       .append('tr').attr('id', 'score-header')
@@ -589,21 +679,28 @@ class Scrabble {
       cells = cells.merge(cell_entry);
 
       cells.html(function(p) {
-        let localScores = scores[p][d]?.scores || [];
+        let localScores = scores[p][d] || [];
 
-        let total = localScores.reduce((acc, score) => {
-          return acc + score[1]
+        let total = localScores.reduce((acc, entry) => {
+          return acc + (entry.score || 0)
         }, 0);
 
-        let scoreString = localScores.map(([word, points]) => {
-          if (word === "*") {
-            return `<span class='score-bingo'>BINGO: ${points}</span>`
+        let scoreString = localScores.map((entry) => {
+          switch (entry.kind) {
+            case "bingo":
+              return `<span class='score-bingo'>BINGO: ${entry.score}</span>`
+            case "remaining_tiles":
+              return `<span class='score-word'>(remaining tiles)</span>`
+            case "pass":
+              return `<span class='score-word'>(pass)</span>`
+            case "exchange":
+              return `<span class='score-word'>(exchanged ${entry.count})</span>`
           }
 
           if (localScores.length > 1) {
-            return `<span class='score-word'>${word}: ${points}</span>`
+            return `<span class='score-word'>${entry.word}: ${entry.score}</span>`
           } else {
-            return `<span class='score-word'>${word}</span>`
+            return `<span class='score-word'>${entry.word}</span>`
           }
         }).join("");
 
@@ -615,8 +712,8 @@ class Scrabble {
   totalScore(player) {
     let sum = 0;
     this.scores[player].forEach(turn => {
-      turn.scores.forEach(word => {
-        sum += word[1]
+      turn.forEach(entry => {
+        sum += entry.score || 0
       })
     })
 
@@ -674,6 +771,82 @@ class Scrabble {
     selection.exit().remove();
   }
 
+  drawForfeitButton() {
+    let data = [];
+    if (this.forfeitAllowed) {
+      data.push(0);
+    }
+    let selection = select('#submit-button-container').selectAll('button#forfeit-button').data(data);
+    let component = this;
+    selection.enter()
+      .append('button')
+      .attr('id', 'forfeit-button')
+      .html("FORFEIT")
+      .on('click', () => {
+        if (confirm("You will lose this game. Proceed?")) {
+          component.sendForfeit();
+        }
+      });
+
+    selection.exit().remove();
+  }
+
+  drawRedrawButton() {
+    let data = [];
+    if (this.redrawAllowed) {
+      data.push(0);
+    }
+    let selection = select('#submit-button-container').selectAll('button#redraw-button').data(data);
+    let component = this;
+    selection.enter()
+      .append('button')
+      .attr('id', 'redraw-button')
+      .html("REDRAW RACK")
+      .on('click', () => {
+        if (confirm("Return your rack to the bag and draw a new one? This can only be done once.")) {
+          component.sendRedraw();
+        }
+      });
+
+    selection.exit().remove();
+  }
+
+  drawChallengeButton() {
+    let data = [];
+    if (this.challengeAllowed) {
+      data.push(0);
+    }
+    let selection = select('#submit-button-container').selectAll('button#challenge-button').data(data);
+    let component = this;
+    selection.enter()
+      .append('button')
+      .attr('id', 'challenge-button')
+      .html("CHALLENGE")
+      .on('click', () => {
+        if (confirm("Challenge the last play? If its words are all valid, it stands.")) {
+          component.sendChallenge();
+        }
+      });
+
+    selection.exit().remove();
+  }
+
+  drawRematchButton() {
+    let data = [];
+    if (this.rematchAllowed) {
+      data.push(0);
+    }
+    let selection = select('#submit-button-container').selectAll('button#rematch-button').data(data);
+    let component = this;
+    selection.enter()
+      .append('button')
+      .attr('id', 'rematch-button')
+      .html("REMATCH")
+      .on('click', () => { component.sendRematch(); });
+
+    selection.exit().remove();
+  }
+
   drawSwapButton() {
     let data = [];
     if (this.swapAllowed && this.current_player === this.player) {
@@ -1,43 +1,239 @@
-use std::collections::HashSet;
+use crate::scrabble::TileSet;
+use std::collections::{HashMap, HashSet};
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, BufReader},
     sync::OnceCell,
 };
+use tracing::warn;
 
-static WORDS: OnceCell<HashSet<String>> = OnceCell::const_new();
+static WORDS: OnceCell<Dictionary> = OnceCell::const_new();
 
-pub async fn dictionary() -> &'static HashSet<String> {
+// About 1500 common words (and every two-letter word), so a fresh checkout can validate plays
+// without a word list. Used in debug builds, or with DEV_DICTIONARY set, when neither
+// WORD_LIST_URL nor ./words is available.
+static DEV_WORDS: &str = include_str!("dev_words.txt");
+
+// Where the word list is read from
+#[derive(Debug)]
+enum Source {
+    Url(String),
+    File(File),
+    Dev,
+}
+
+// The word list, uppercased. Accents are kept, and matched per game by `contains`.
+#[derive(Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    // the listed words with accents, by their spelling with the accents dropped
+    accented: HashMap<String, Vec<String>>,
+}
+
+impl Dictionary {
+    fn insert(&mut self, word: &str) {
+        let word = uppercase(word);
+        let folded = fold(&word, |_| false);
+
+        if folded != word {
+            self.accented.entry(folded).or_default().push(word.clone());
+        }
+        self.words.insert(word);
+    }
+
+    // Whether `word` is listed, in any case. An accent on a listed word is dropped unless the
+    // game's tile set has a tile for the accented letter: CAFÉ in the list allows CAFE, but in a
+    // Spanish game (which has Ñ tiles) AÑO doesn't allow ANO.
+    pub fn contains(&self, word: &str, tile_set: Option<&TileSet>) -> bool {
+        let word = uppercase(word);
+        if self.words.contains(&word) {
+            return true;
+        }
+
+        let has_tile = |c: char| matches!(tile_set, Some(set) if set.distribution.contains_key(&c));
+        self.accented
+            .get(&fold(&word, |_| false))
+            .into_iter()
+            .flatten()
+            .any(|listed| fold(listed, has_tile) == word)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.words.iter()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Dictionary {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut dictionary = Dictionary::default();
+        for word in iter {
+            dictionary.insert(word);
+        }
+        dictionary
+    }
+}
+
+pub async fn dictionary() -> &'static Dictionary {
     WORDS
         .get_or_init(|| async {
-            let mut set = HashSet::new();
-            match std::env::var("WORD_LIST_URL") {
-                Ok(url) => {
-                    let body = reqwest::get(url).await.unwrap().text().await.unwrap();
-                    for line in body.lines() {
-                        set.insert(line.to_uppercase());
-                    }
-                }
-                Err(_) => {
-                    let file = File::open("./words").await.unwrap();
-                    let reader = BufReader::new(file);
-                    let mut lines = reader.lines();
-
-                    while let Ok(Some(line)) = lines.next_line().await {
-                        set.insert(line.to_uppercase());
-                    }
-                }
+            let source = match std::env::var("WORD_LIST_URL") {
+                Ok(url) => Ok(Source::Url(url)),
+                Err(_) => fallback_source(File::open("./words").await, dev_dictionary_allowed()),
+            };
+
+            match source {
+                Ok(source) => load(source).await,
+                Err(e) => panic!("no word list: set WORD_LIST_URL or add ./words ({})", e),
             }
-            set
         })
         .await
 }
 
-pub async fn illegal_words<'a>(words: Vec<String>) -> Vec<String> {
-    let dict = dictionary().await;
+// Without WORD_LIST_URL: ./words if it opened, else the development list where allowed
+fn fallback_source(file: std::io::Result<File>, dev_allowed: bool) -> std::io::Result<Source> {
+    match file {
+        Ok(file) => Ok(Source::File(file)),
+        Err(e) if dev_allowed => {
+            warn!(
+                "no word list ({}); using the reduced development dictionary, which will reject \
+                 many real words",
+                e
+            );
+            Ok(Source::Dev)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn load(source: Source) -> Dictionary {
+    let mut dictionary = Dictionary::default();
+
+    match source {
+        Source::Url(url) => {
+            let body = reqwest::get(url).await.unwrap().text().await.unwrap();
+            for line in body.lines() {
+                dictionary.insert(line);
+            }
+        }
+        Source::File(file) => {
+            let reader = BufReader::new(file);
+            let mut lines = reader.lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                dictionary.insert(&line);
+            }
+        }
+        Source::Dev => dictionary = dev_words(),
+    }
+
+    dictionary
+}
+
+fn dev_dictionary_allowed() -> bool {
+    cfg!(debug_assertions) || std::env::var("DEV_DICTIONARY").is_ok()
+}
 
+fn dev_words() -> Dictionary {
+    DEV_WORDS.lines().collect()
+}
+
+fn uppercase(word: &str) -> String {
+    word.trim().chars().flat_map(char::to_uppercase).collect()
+}
+
+// `word` with the accents dropped, except from letters for which `keep` holds
+fn fold(word: &str, keep: impl Fn(char) -> bool) -> String {
+    word.chars()
+        .map(|c| if keep(c) { c } else { fold_diacritic(c) })
+        .collect()
+}
+
+fn fold_diacritic(c: char) -> char {
+    match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'A',
+        'Ç' => 'C',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => 'O',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'Ý' | 'Ÿ' => 'Y',
+        c => c,
+    }
+}
+
+pub async fn illegal_words(words: Vec<String>, tile_set: Option<&TileSet>) -> Vec<String> {
+    illegal_words_in(dictionary().await, words, tile_set)
+}
+
+// The words (as given) missing from `dictionary`
+fn illegal_words_in(
+    dictionary: &Dictionary,
+    words: Vec<String>,
+    tile_set: Option<&TileSet>,
+) -> Vec<String> {
     words
         .into_iter()
-        .filter(|word| !dict.contains(&*word))
+        .filter(|word| !dictionary.contains(word, tile_set))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrabble::Language;
+
+    #[test]
+    fn test_dev_words() {
+        let words = dev_words();
+
+        for word in ["QI", "QUIT", "HOUSE", "LETTERS"] {
+            assert!(words.contains(word, None), "{} should be legal", word);
+        }
+        for gibberish in ["QXZ", "AEIOU", ""] {
+            assert!(
+                !words.contains(gibberish, None),
+                "{} should be illegal",
+                gibberish
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_without_a_source() {
+        let missing = || Err(std::io::ErrorKind::NotFound.into());
+
+        let source = fallback_source(missing(), true).unwrap();
+        assert!(matches!(source, Source::Dev));
+
+        let dict = load(source).await;
+        let words = vec!["QUIT".to_string(), "QXZ".to_string()];
+        assert_eq!(illegal_words_in(&dict, words, None), vec!["QXZ"]);
+
+        // outside development, a missing word list is an error
+        assert!(fallback_source(missing(), false).is_err());
+    }
+
+    #[test]
+    fn test_normalized_matching() {
+        let dict: Dictionary = ["CAFÉ", "house", " Tile", "año", "ÄRGER"]
+            .into_iter()
+            .collect();
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+
+        let english = [
+            "house", "HoUsE", "tile", "CAFE", "café", "ANO", "ARGER", "cafes",
+        ];
+        let illegal = illegal_words_in(&dict, words(&english), None);
+        assert_eq!(illegal, vec!["cafes"]);
+
+        // letters with tiles of their own keep their accents
+        let spanish = Language::Spanish.tile_set();
+        let illegal = illegal_words_in(&dict, words(&["AÑO", "ANO", "CAFE"]), Some(&spanish));
+        assert_eq!(illegal, vec!["ANO"]);
+
+        let german = Language::German.tile_set();
+        let illegal = illegal_words_in(&dict, words(&["ÄRGER", "ARGER"]), Some(&german));
+        assert_eq!(illegal, vec!["ARGER"]);
+    }
+}
@@ -0,0 +1,138 @@
+// A game against the bot in the terminal, without the database or websockets
+// (`scrabble --local`). Moves are read from stdin one per line, in the notation of
+// `Game::parse_move` (e.g. `8H QUIT`), or `pass`/`quit`; the board is printed after each move.
+// `--position <position>` starts from a `Game::to_position_string` encoding instead of a new
+// game, and quitting prints the position to pick up from.
+use crate::scrabble::{Error, Game, Player};
+use std::io::{self, BufRead, Write};
+
+static HUMAN: usize = 0;
+
+pub async fn run() -> io::Result<()> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--position");
+    let mut game = match args.nth(1) {
+        Some(position) => Game::from_position_string("game:local".parse().unwrap(), &position)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?,
+        None => {
+            let mut game = Game::new("game:local".parse().unwrap());
+            game.add_player(Player::from("You")).unwrap();
+            game.add_player(Player::from("Bot")).unwrap();
+            game.start().unwrap();
+            game
+        }
+    };
+
+    let stdin = io::stdin();
+    play(&mut game, HUMAN, stdin.lock(), io::stdout()).await
+}
+
+// Runs turns until the game is over or the input ends, with every seat but `human` played by
+// the bot.
+async fn play<R: BufRead, W: Write>(
+    game: &mut Game,
+    human: usize,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    let mut lines = input.lines();
+    write!(output, "{}", game.to_ascii(Some(human)))?;
+
+    while !game.is_over() {
+        let player = game.current_player().unwrap_or_default().to_string();
+
+        if game.assert_turn(human).is_ok() {
+            write!(output, "> ")?;
+            output.flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+
+            match line.trim() {
+                "quit" => {
+                    writeln!(output, "position: {}", game.to_position_string())?;
+                    break;
+                }
+                "" => continue,
+                line => {
+                    if let Err(e) = step(game, line).await {
+                        writeln!(output, "{}", e)?;
+                        continue;
+                    }
+                }
+            }
+        } else {
+            match game.play_best().await {
+                Ok(Some(_)) => writeln!(output, "{} played", player)?,
+                Ok(None) => writeln!(output, "{} had nothing to play", player)?,
+                Err(e) => {
+                    writeln!(output, "{} couldn't move: {}", player, e)?;
+                    break;
+                }
+            }
+        }
+
+        write!(output, "\n{}", game.to_ascii(Some(human)))?;
+    }
+
+    Ok(())
+}
+
+// Applies one line of the human player's input
+async fn step(game: &mut Game, line: &str) -> Result<(), Error> {
+    match line {
+        "pass" => game.pass(),
+        _ => {
+            let turn = game.parse_move(line)?;
+            game.play(turn).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_position() -> Game {
+        let board = ["15"; 15].join("/");
+        Game::from_position_string(
+            "game:local".parse().unwrap(),
+            &format!("{} 0 QUITAEL/VVWWKJX", board),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_move_advances_the_turn() {
+        let mut game = test_position();
+        step(&mut game, "8H QUIT").await.unwrap();
+
+        assert_eq!(game.current_player(), Some("player 2"));
+        assert!(!game.to_ascii(None).contains("player 1: 0\n"));
+        // every letter is now on the board, so there's nothing left to place
+        assert!(matches!(
+            step(&mut game, "8H QUIT").await,
+            Err(Error::EmptyTurn)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_loop_applies_moves_until_quit() {
+        let mut game = test_position();
+        let mut output = vec![];
+        play(&mut game, 0, "H7 TILE\nquit\n".as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // the bot moves (or swaps) in reply, and the turn comes back
+        assert!(output.contains("player 2 "));
+        assert_eq!(game.current_player(), Some("player 1"));
+        assert!(!game.to_ascii(None).contains("player 1: 0\n"));
+
+        // quitting leaves the position to start from next time
+        let position = format!("position: {}\n", game.to_position_string());
+        assert!(output.ends_with(&position));
+    }
+}
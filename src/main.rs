@@ -3,19 +3,26 @@ use axum_channels::channel::{self, Channel, MessageContext, NewChannel, Presence
 use axum_channels::message::{Message, MessageKind};
 use axum_channels::registry::Registry;
 use axum_channels::types::{ChannelId, Token};
-use scrabble::{Game, Player, Turn, TurnScore};
+use parking_lot::Mutex;
+use scrabble::{Game, Player, State, Turn, TurnScore};
+use serde::{de, Deserialize, Deserializer};
 use serde_json::json;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::{
-    collections::{HashMap, HashSet},
-    net::SocketAddr,
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
 };
+use tokio::sync::Notify;
 use tracing::{debug, error, warn};
 use users::User;
 
 use crate::{scrabble::PlayerIndex, session::Session};
 
 mod dictionary;
+mod local;
 mod scrabble;
 mod session;
 mod users;
@@ -28,6 +35,13 @@ mod web;
 #[tokio::main]
 async fn main() {
     let _ = dotenv::dotenv();
+
+    // a game against the bot in the terminal, without Postgres
+    if std::env::args().any(|arg| arg == "--local") {
+        local::run().await.unwrap();
+        return;
+    }
+
     console_subscriber::Builder::default().init();
 
     dictionary::dictionary().await;
@@ -41,15 +55,26 @@ async fn main() {
         .unwrap();
 
     let mut registry = Registry::default();
-    let game_channel = GameChannel::new(pool.clone(), "_template_".parse().unwrap());
+    let mut game_channel = GameChannel::new(pool.clone(), "_template_".parse().unwrap());
+    game_channel.max_spectators = std::env::var("MAX_SPECTATORS")
+        .ok()
+        .and_then(|max| max.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SPECTATORS);
     registry.register_template("game", game_channel);
 
     let (registry_sender, _registry_handle) = registry.start();
 
     let app = web::app(registry_sender, pool);
 
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let socket_addr = SocketAddr::new("0.0.0.0".parse().unwrap(), port.parse().unwrap());
+    let bind_addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string());
+    let port = std::env::var("PORT").unwrap_or_else(|_| DEFAULT_PORT.to_string());
+    let socket_addr = parse_socket_addr(&bind_addr, &port).unwrap_or_else(|e| {
+        error!(
+            "{}; falling back to {}:{}",
+            e, DEFAULT_BIND_ADDR, DEFAULT_PORT
+        );
+        parse_socket_addr(DEFAULT_BIND_ADDR, DEFAULT_PORT).unwrap()
+    });
 
     axum::Server::bind(&socket_addr)
         .serve(app.into_make_service())
@@ -57,299 +82,2306 @@ async fn main() {
         .unwrap();
 }
 
+static DEFAULT_BIND_ADDR: &str = "0.0.0.0";
+static DEFAULT_PORT: &str = "3000";
+static DEFAULT_MAX_SPECTATORS: usize = 50;
+// info messages kept for sockets that (re)join after they were sent
+static RECENT_MESSAGE_LIMIT: usize = 20;
+// how long a player must have been away before the others can hand their seat to the bot
+static BOT_REPLACEMENT_GRACE: Duration = Duration::from_secs(5 * 60);
+// Clients send a heartbeat this often; a socket that misses this many in a row is taken to be a
+// half-open connection and dropped, as though it had left. Each channel checks as often.
+static HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+static MISSED_HEARTBEAT_LIMIT: u32 = 3;
+
+lazy_static::lazy_static! {
+    // Games with a channel open in this process, by name
+    static ref LIVE_GAMES: Mutex<HashMap<String, LiveGame>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct LiveGame {
+    // an admin has asked for the game to be terminated
+    terminate: bool,
+    // wakes the channel's ticker, so a termination is applied straight away
+    wake: Arc<Notify>,
+}
+
+pub(crate) fn channel_is_live(name: &str) -> bool {
+    LIVE_GAMES.lock().contains_key(name)
+}
+
+// Returns whether there's a live channel to notify
+pub(crate) fn request_termination(name: &str) -> bool {
+    match LIVE_GAMES.lock().get_mut(name) {
+        Some(live) => {
+            live.terminate = true;
+            live.wake.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_termination(name: &str) -> bool {
+    LIVE_GAMES
+        .lock()
+        .get_mut(name)
+        .map(|live| std::mem::take(&mut live.terminate))
+        .unwrap_or_default()
+}
+
+fn parse_socket_addr(bind_addr: &str, port: &str) -> Result<SocketAddr, String> {
+    let ip: IpAddr = bind_addr
+        .parse()
+        .map_err(|_| format!("invalid BIND_ADDR {:?}", bind_addr))?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("invalid PORT {:?}", port))?;
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+// One per live game, owned by the registry, which hands it one message at a time. Every handler
+// takes `&mut self`, so moves are applied strictly one after another: the second of two
+// simultaneous plays is checked against the game as the first left it. Nothing else may hold
+// the game; don't share it (e.g. behind an `Arc`) with other tasks.
 #[derive(Debug)]
-struct GameChannel {
+struct GameChannel<S = Token> {
     pub(crate) game: Option<Game>,
-    pub(crate) socket_state: HashMap<Token, http::Extensions>,
+    pub(crate) socket_state: HashMap<S, http::Extensions>,
     pub(crate) pg_pool: PgPool,
     pub(crate) channel_id: ChannelId,
+    pub(crate) max_spectators: usize,
+    pub(crate) recent_messages: VecDeque<serde_json::Value>,
+    // the id of this game's rematch, once one has been created
+    pub(crate) rematch_id: Option<String>,
+    // when each seat's last socket left; seats not seen since the channel opened count from then
+    pub(crate) away_since: HashMap<usize, Instant>,
+    pub(crate) opened_at: Instant,
+    // players online as of the last presence update, less any whose sockets were dropped for
+    // missing heartbeats (the presence tracker still counts those until they rejoin)
+    pub(crate) online: HashSet<String>,
+    pub(crate) evicted: HashSet<String>,
+    // wakes the channel for its time-based work, once a game is loaded
+    pub(crate) ticker: Option<tokio::task::JoinHandle<()>>,
+    // wakes it when a pending play's challenge window closes
+    pub(crate) deadline_timer: Option<tokio::task::JoinHandle<()>>,
 }
 
-impl GameChannel {
+// marks a socket in `socket_state` as a spectator (not seated in the game)
+struct Spectator;
+
+// marks a socket that has just joined, and should get the recent messages with its next state
+struct ReplayRecent;
+
+// the seat a spectator follows, whose rack they see if its player reveals it
+struct Following(usize);
+
+// when a socket last sent anything (a heartbeat at least)
+struct LastSeen(Instant);
+
+impl<S: Copy + Eq + Hash> GameChannel<S> {
     pub fn new(pg_pool: PgPool, channel_id: ChannelId) -> Self {
         GameChannel {
             game: None,
             socket_state: HashMap::new(),
             pg_pool,
             channel_id,
+            max_spectators: DEFAULT_MAX_SPECTATORS,
+            recent_messages: VecDeque::new(),
+            rematch_id: None,
+            away_since: HashMap::new(),
+            opened_at: Instant::now(),
+            online: HashSet::new(),
+            evicted: HashSet::new(),
+            ticker: None,
+            deadline_timer: None,
         }
     }
 
-    fn propose(&self, payload: serde_json::Value) -> Result<TurnScore, scrabble::Error> {
-        let turn = payload.try_into().map_err(|_| scrabble::Error::TurnParse)?;
-        Ok(self.game.as_ref().unwrap().propose(&turn))
+    fn name(&self) -> Option<String> {
+        self.channel_id.value().map(|name| name.to_string())
     }
 
-    async fn play(
-        &mut self,
-        event: &str,
-        payload: serde_json::Value,
-        player_index: usize,
-        player: Player,
-    ) -> Result<Option<String>, scrabble::Error> {
-        let turn: Turn = payload.try_into()?;
-        let game = self.game.as_mut().unwrap();
+    // Applies an admin's termination, if one was asked for, and closes the channel: its clients
+    // leave on `phx_close`, and it stops being live. Anything sent before they've left is
+    // rejected, as for any finished game.
+    async fn apply_termination(&mut self, context: &impl EventContext<Socket = S>) {
+        if !matches!(self.name(), Some(name) if take_termination(&name)) {
+            return;
+        }
 
-        if game.player_index != player_index {
-            return Err(scrabble::Error::NotYourTurn);
+        if let Err(e) = self.game.as_mut().unwrap().terminate() {
+            warn!("game could not be terminated; e={:?}", e);
+            return;
         }
 
-        let turn_len = turn.len();
-        let result = match event {
-            "play" => game.play(turn).await.map(|_| None),
-            "swap" => game
-                .swap(turn)
-                .map(|_| Some(format!("{} swapped {} tiles", player, turn_len))),
-            "pass" => game.pass().map(|_| Some(format!("{} passed", player))),
-            _ => {
-                error!("unknown event {:?}", event);
-                return Err(scrabble::Error::Unknown);
-            }
-        };
+        let _ = self.save_state().await;
+        let message = self.info("this game was ended by an admin".into());
+        context.broadcast("info".into(), message);
+        self.broadcast_game_over(context);
+        context.broadcast_intercept("player-state".into(), Default::default());
+
+        context.broadcast("phx_close".into(), json!({}));
+        self.close();
+    }
 
-        // save state even if an error is returned
-        self.save_state().await?;
+    fn broadcast_game_over(&self, context: &impl EventContext<Socket = S>) {
+        if let Some(standings) = self.game.as_ref().unwrap().final_standings() {
+            let winner = standings.first().map(|standing| json!(standing));
 
-        // FIXME: optional message (e.g. 'player swapped 5 tiles');
-        result
+            context.broadcast(
+                "game-over".into(),
+                json!({ "winner": winner, "standings": standings }),
+            );
+        }
     }
 
-    async fn save_state(&mut self) -> Result<(), scrabble::Error> {
-        match self.game.as_mut().unwrap().persist(&self.pg_pool).await {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                error!("error saving game state; e={:?}", e);
+    // With the assist option, the player whose turn it now is gets a hint when nothing on their
+    // rack can be played (sent only to them; see `handle_out`). Finding out can take a while, so
+    // it's left to its own task, and the hint follows whatever was sent about the last move.
+    fn broadcast_hint(&self, context: &impl EventContext) {
+        let game = self.game.as_ref().unwrap();
+        if !game.options().assist || game.state() != &State::Started {
+            return;
+        }
 
-                Err(e)
+        let has_legal_move = game.has_legal_move(game.player_index);
+        let payload = json!({
+            "player_index": game.player_index,
+            "move_count": game.move_count(),
+            "message": "Nothing on your rack can be played; try exchanging tiles or passing",
+        });
+        let context = context.clone();
+
+        tokio::spawn(async move {
+            if !has_legal_move.await {
+                context.broadcast_intercept("hint".into(), payload);
             }
-        }
+        });
     }
-}
 
-// FIXME: need a nicer way to declare messages
-#[async_trait]
-impl Channel for GameChannel {
-    async fn handle_message(&mut self, context: &MessageContext) -> Option<Message> {
-        match &context.inner.kind {
-            MessageKind::Event => match context.inner.event.as_ref() {
-                "start" => {
-                    let _ = self.game.as_mut().unwrap().start();
-                    let _ = self.save_state().await;
-
-                    Some(
-                        context
-                            .build_broadcast_intercept("player-state".into(), Default::default()),
-                    )
-                }
+    // the payload of an info broadcast, kept for sockets that join later
+    fn info(&mut self, message: String) -> serde_json::Value {
+        let payload = json!({ "message": message });
 
-                "play" | "swap" | "pass" => {
-                    let index = self
-                        .socket_state
-                        .get(&context.token)
-                        .unwrap()
-                        .get::<PlayerIndex>()
-                        .unwrap()
-                        .0;
-
-                    let player = self
-                        .socket_state
-                        .get(&context.token)
-                        .unwrap()
-                        .get::<Player>()
-                        .unwrap()
-                        .clone();
-
-                    match self
-                        .play(
-                            context.inner.event.as_ref(),
-                            context.inner.payload.clone(),
-                            index,
-                            player,
-                        )
-                        .await
-                    {
-                        Ok(msg) => {
-                            context.broadcast_intercept("player-state".into(), Default::default());
+        self.recent_messages.push_back(payload.clone());
+        while self.recent_messages.len() > RECENT_MESSAGE_LIMIT {
+            self.recent_messages.pop_front();
+        }
 
-                            msg.map(|message| {
-                                context
-                                    .build_broadcast("info".into(), json!({ "message": message }))
-                            })
-                        }
-                        Err(e) => {
-                            error!("{:?}", e);
-                            let msg = format!("{:?}", e);
-
-                            match e {
-                                scrabble::Error::TriesExhausted => {
-                                    let reply = context.build_broadcast_intercept(
-                                        "player-state".into(),
-                                        Default::default(),
-                                    );
-
-                                    let state = self.socket_state.entry(context.token).or_default();
-                                    let player = state.get::<Player>();
-
-                                    let _ = context.broadcast(
-                                        "info".into(),
-                                        json!({
-                                            "message":
-                                                format!(
-                                                    "{:?} lost a turn due to illegal maneuvers!",
-                                                    player
-                                                )
-                                        }),
-                                    );
-
-                                    Some(reply)
-                                }
-                                _ => Some(context.build_push(
-                                    context.msg_ref.clone(),
-                                    "error".into(),
-                                    serde_json::json!({
-                                        "message": msg,
-                                    }),
-                                )),
-                            }
-                        }
-                    }
-                }
+        payload
+    }
 
-                "proposed" => match self.propose(context.inner.payload.clone()) {
-                    Ok(scores) => Some(context.build_push(
-                        context.msg_ref.clone(),
-                        "info".into(),
-                        serde_json::json!({ "message": format!("{:?}", scores) }),
-                    )),
-
-                    Err(e) => Some(context.build_push(
-                        context.msg_ref.clone(),
-                        "error".into(),
-                        serde_json::json!({ "message": format!("{:?}", e) }),
-                    )),
-                },
-
-                other => {
-                    warn!(
-                        "unhandled message [{}]; payload={:?}",
-                        other, context.inner.payload
-                    );
-                    None
-                }
-            },
-            _ => None,
+    // Seats the player (or finds their existing seat), with an info payload announcing them when
+    // the seat is new.
+    fn take_seat(
+        &mut self,
+        player: &Player,
+    ) -> Result<(usize, Option<serde_json::Value>), scrabble::Error> {
+        let game = self.game.as_mut().unwrap();
+        let seats_before = game.players().len();
+        let player_index = game.add_player(player.clone())?;
+
+        if game.players().len() > seats_before {
+            Ok((
+                player_index,
+                Some(self.info(format!("{} joined", player.0))),
+            ))
+        } else {
+            Ok((player_index, None))
         }
     }
 
-    async fn handle_out(&mut self, context: &MessageContext) -> Option<Message> {
-        match &context.inner.kind {
-            MessageKind::BroadcastIntercept => {
-                let index = self
-                    .socket_state
-                    .get(&context.token)
-                    .and_then(|entry| entry.get::<PlayerIndex>());
+    // A move using tiles the player doesn't have most likely came from a client holding a rack
+    // from before a reload, so the error carries the state it needs to refresh.
+    fn stale_rack_error(&self, e: &scrabble::Error, player_index: usize) -> serde_json::Value {
+        let mut payload = e.payload();
+        payload["resync"] = self.game.as_ref().unwrap().resync_state(player_index);
+        payload
+    }
 
-                match context.inner.event.as_ref() {
-                    "player-state" => {
-                        let payload = self.game.as_ref().unwrap().player_state(index);
-                        let reply = context.build_push(
-                            context.msg_ref.clone(),
-                            context.inner.event.clone(),
-                            payload,
-                        );
+    // Creates the rematch of a finished game the first time it's asked for; the payload says where
+    // to find it.
+    async fn rematch(&mut self) -> Result<serde_json::Value, scrabble::Error> {
+        let game_id = match &self.rematch_id {
+            Some(game_id) => game_id.clone(),
+            None => {
+                let game_id = web::random_game_id();
+                // random ids are always valid
+                let channel_id = format!("game:{}", web::game_name(&game_id))
+                    .parse()
+                    .unwrap();
+                self.game
+                    .as_ref()
+                    .unwrap()
+                    .rematch(channel_id)?
+                    .persist(&self.pg_pool)
+                    .await?;
 
-                        Some(reply)
-                    }
-                    _ => None,
-                }
+                self.rematch_id = Some(game_id.clone());
+                game_id
             }
-            _ => None,
+        };
+
+        Ok(json!({ "game_id": game_id, "url": format!("/play/{}", game_id) }))
+    }
+
+    // A seat can be handed to the bot once its player has been away for the grace period
+    fn check_replaceable(&self, player_index: usize) -> Result<(), scrabble::Error> {
+        let connected = self
+            .socket_state
+            .values()
+            .any(|state| matches!(state.get::<PlayerIndex>(), Some(PlayerIndex(i)) if *i == player_index));
+        if connected {
+            return Err(scrabble::Error::CannotReplace(
+                "the player is still connected".into(),
+            ));
         }
+
+        let away_since = self
+            .away_since
+            .get(&player_index)
+            .unwrap_or(&self.opened_at);
+        if away_since.elapsed() < BOT_REPLACEMENT_GRACE {
+            return Err(scrabble::Error::CannotReplace(format!(
+                "the player has been away for less than {} minutes",
+                BOT_REPLACEMENT_GRACE.as_secs() / 60
+            )));
+        }
+
+        Ok(())
     }
 
-    async fn handle_join(
+    // The user a join token speaks for, unless it's been revoked (by logging out or changing
+    // password) since the page was loaded
+    async fn join_user(&self, session: &Session) -> Result<User, channel::Error> {
+        let user = User::find(session.user_id.unwrap_or_default(), &self.pg_pool)
+            .await
+            .map_err(|e| channel::Error::Other(format!("user could not be loaded: {}", e)))?;
+
+        if !session.is_current_for(&user) {
+            return Err(channel::Error::Other("token was revoked".into()));
+        }
+
+        Ok(user)
+    }
+
+    // Seats a joining socket's player, or has them watch
+    async fn join_as(
         &mut self,
-        context: &MessageContext,
+        context: &impl EventContext<Socket = S>,
+        player: Player,
     ) -> Result<Option<Message>, channel::Error> {
-        if self.game.is_none() {
-            let game = Game::fetch(context.channel_id().clone(), &self.pg_pool).await;
-            debug!("setting up game {:?}...", context.channel_id());
-            self.game = Some(game);
-        }
+        self.evicted.remove(player.as_str());
+        self.socket_state
+            .entry(context.socket())
+            .or_default()
+            .insert(LastSeen(Instant::now()));
 
-        debug!("{:?}", context);
-        let token = context
-            .inner
-            .payload
-            .get("token")
-            .and_then(|t| t.as_str())
-            .ok_or_else(|| channel::Error::Other("token not found".into()))
-            .map(|token| Session::read_token(token.to_string()))?;
+        // once started, only players already seated can take a seat
+        let rejoining = self.game.as_ref().unwrap().state() == &State::Started;
 
-        let session = token.ok_or_else(|| channel::Error::Other("token was not valid".into()))?;
+        match self.take_seat(&player) {
+            Ok((player_index, _)) if rejoining => {
+                self.away_since.remove(&player_index);
+                let state = self.socket_state.entry(context.socket()).or_default();
 
-        let user = User::find(session.user_id.unwrap(), &self.pg_pool)
-            .await // damn it
-            .unwrap(); // FIXME: unwrap
+                state.insert(PlayerIndex(player_index));
+                state.insert(player);
+                state.insert(ReplayRecent);
 
-        let player = Player(user.username);
+                // nothing changed for the other sockets, so only the rejoining one needs the state
+                let mut payload = self.game.as_ref().unwrap().resync_state(player_index);
+                replay_recent(&self.recent_messages, Some(state), &mut payload);
+
+                return Ok(Some(context.build_push(
+                    context.msg_ref(),
+                    "resync".into(),
+                    payload,
+                )));
+            }
 
-        match self.game.as_mut().unwrap().add_player(player.clone()) {
-            Ok(player_index) => {
+            Ok((player_index, joined)) => {
+                self.away_since.remove(&player_index);
                 let _ = self.save_state().await;
-                let state = self.socket_state.entry(context.token).or_default();
+
+                if let Some(message) = joined {
+                    context.broadcast("info".into(), message);
+                }
+
+                let state = self.socket_state.entry(context.socket()).or_default();
 
                 state.insert(PlayerIndex(player_index));
                 state.insert(player);
             }
 
             Err(e) => {
-                error!("{:?}", e);
+                debug!("joining as spectator; e={:?}", e);
+                self.admit_spectator(&player)?;
+                let state = self.socket_state.entry(context.socket()).or_default();
+                state.insert(Spectator);
+                state.insert(player);
+
+                // the seat to follow can be given when joining, as well as with `follow`
+                if let Some(index) = context.payload().get("follow").and_then(|i| i.as_u64()) {
+                    state.insert(Following(index as usize));
+                }
             }
         }
 
+        self.socket_state
+            .entry(context.socket())
+            .or_default()
+            .insert(ReplayRecent);
+
         Ok(Some(context.build_broadcast_intercept(
             "player-state".into(),
             Default::default(),
         )))
     }
 
-    // FIXME: MessageContext
-    async fn handle_presence(
-        &mut self,
-        channel_id: &ChannelId,
-        presence: &Presence,
-    ) -> axum_channels::channel::Result<Option<Message>> {
-        let mut online = HashSet::new();
+    // Makes the moves of any seats handed to the bot, announcing each
+    async fn play_bots(&mut self, context: &impl EventContext<Socket = S>) {
+        let moves = match self.game.as_mut().unwrap().play_bots().await {
+            Ok(moves) => moves,
+            Err(e) => {
+                error!("bot could not move; e={:?}", e);
+                return;
+            }
+        };
 
-        for user in presence.data.values() {
-            online.insert(user.get("player").unwrap().as_str().unwrap());
+        if moves.is_empty() {
+            return;
         }
 
-        let message = Message {
-            channel_id: channel_id.clone(),
-            event: "presence".into(),
-            payload: serde_json::json!({"online":online.iter().collect::<Vec<_>>()}),
-            kind: MessageKind::Broadcast,
-            msg_ref: None,
-            join_ref: None,
-            channel_sender: None,
+        for (index, turn) in moves {
+            let player = self.game.as_ref().unwrap().players()[index].clone();
+            let message = match turn {
+                Some(_) => format!("{} (bot) played", player),
+                None => format!("{} (bot) had nothing to play", player),
+            };
+            context.broadcast("info".into(), self.info(message));
+        }
+
+        let _ = self.save_state().await;
+        let game = self.game.as_ref().unwrap();
+        if game.is_over() {
+            scrabble::persistence::retain_finished(game);
+        }
+    }
+
+    // Cleans up after a socket that left (or was dropped)
+    fn forget_socket(&mut self, state: http::Extensions) {
+        if let Some(PlayerIndex(index)) = state.get::<PlayerIndex>() {
+            self.away_since.insert(*index, Instant::now());
+        }
+    }
+
+    // Drops the sockets that have gone quiet, as though they had left
+    fn check_heartbeats(&mut self, context: &impl EventContext) {
+        let evicted = evict_stale(&mut self.socket_state, Instant::now());
+        if evicted.is_empty() {
+            return;
+        }
+
+        for state in evicted {
+            if let Some(player) = state.get::<Player>() {
+                debug!("dropping {} after missed heartbeats", player);
+                self.evicted.insert(player.0.clone());
+            }
+            self.forget_socket(state);
+        }
+
+        // a player may still have another socket open
+        let connected: HashSet<&str> = self
+            .socket_state
+            .values()
+            .filter_map(|state| state.get::<Player>())
+            .map(Player::as_str)
+            .collect();
+        self.evicted
+            .retain(|player| !connected.contains(player.as_str()));
+
+        context.broadcast("presence".into(), self.presence_summary());
+    }
+
+    // Wakes the channel once the pending play's challenge window has closed, replacing any
+    // wake-up set for an earlier play
+    fn schedule_finalize(&mut self, context: &impl EventContext) {
+        if let Some(timer) = self.deadline_timer.take() {
+            timer.abort();
+        }
+
+        let deadline = match self.game.as_ref().and_then(Game::challenge_deadline) {
+            Some(deadline) => deadline,
+            None => return,
         };
+        let wait = Duration::from_secs(deadline.saturating_sub(scrabble::unix_now()) + 1);
+        let context = context.clone();
 
-        Ok(Some(message))
+        self.deadline_timer = Some(tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+            context.broadcast_intercept("tick".into(), json!({}));
+        }));
     }
 
-    async fn handle_leave(
+    // Makes a play whose challenge window had closed by `now` (unix seconds) final, and tells
+    // everyone, along with the result if the play went out. A challenge arriving first finds the
+    // window closed itself, and is refused as too late.
+    async fn finalize_challenged_play(
         &mut self,
-        context: &MessageContext,
-    ) -> axum_channels::channel::Result<Option<Message>> {
-        self.socket_state.remove(&context.token);
-        Ok(None)
+        context: &impl EventContext<Socket = S>,
+        now: u64,
+    ) {
+        let finalized = match self.game.as_mut() {
+            Some(game) => game.finalize_expired(now),
+            None => false,
+        };
+        if !finalized {
+            return;
+        }
+
+        let _ = self.save_state().await;
+        let message = self.info("the challenge window has closed; the last play is final".into());
+        context.broadcast("info".into(), message);
+        self.broadcast_game_over(context);
+        context.broadcast_intercept("player-state".into(), Default::default());
+    }
+
+    // Applies what comes due with time rather than with a message; run on each of the ticker's
+    // wake-ups (see `start_ticker`), and when a challenge window closes
+    async fn tick(&mut self, context: &impl EventContext<Socket = S>) {
+        self.apply_termination(context).await;
+        self.check_heartbeats(context);
+        self.finalize_challenged_play(context, scrabble::unix_now())
+            .await;
+    }
+
+    fn online_players(&self) -> Vec<&String> {
+        self.online.difference(&self.evicted).collect()
+    }
+
+    // Takes in the presence tracker's entries (one per socket, each with the player name it
+    // joined as). Entries without a name are skipped.
+    fn record_presence<'a>(&mut self, entries: impl IntoIterator<Item = &'a serde_json::Value>) {
+        self.online = entries
+            .into_iter()
+            .filter_map(|entry| entry.get("player")?.as_str())
+            .filter(|player| !player.is_empty())
+            .map(|player| player.to_string())
+            .collect();
+    }
+
+    // Who's here: everyone online, each seat with whether its player is, and how many are watching
+    fn presence_summary(&self) -> serde_json::Value {
+        let online = self.online_players();
+        let players: Vec<serde_json::Value> = self
+            .game
+            .iter()
+            .flat_map(|game| game.players().iter().enumerate())
+            .map(|(player_index, player)| {
+                json!({
+                    "player_index": player_index,
+                    "name": player.as_str(),
+                    "online": online.iter().any(|name| name.as_str() == player.as_str()),
+                })
+            })
+            .collect();
+
+        json!({
+            "online": online,
+            "players": players,
+            "spectator_count": spectators(&self.socket_state).len(),
+        })
+    }
+
+    // Another socket for a user already watching doesn't count against the limit
+    fn admit_spectator(&self, player: &Player) -> Result<(), channel::Error> {
+        let spectators = spectators(&self.socket_state);
+        if !spectators.contains(player.as_str()) && spectators.len() >= self.max_spectators {
+            return Err(channel::Error::Other(format!(
+                "this game already has the maximum of {} spectators",
+                self.max_spectators
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn propose(&self, turn: &Turn, player_index: usize) -> Result<TurnScore, scrabble::Error> {
+        self.game.as_ref().unwrap().propose(turn, player_index)
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        fields(game = %self.name().unwrap_or_default(), player = %player, event = event.name())
+    )]
+    async fn play(&mut self, event: GameEvent, player_index: usize, player: Player) -> Played {
+        let game = self.game.as_mut().unwrap();
+        if let Err(e) = game.assert_turn(player_index) {
+            return Played {
+                result: Err(e),
+                saved: Ok(()),
+                bag_emptied: false,
+            };
+        }
+
+        let bag_was_empty = game.bag_empty();
+        let result = match event {
+            GameEvent::Play(turn) => game.play(turn).await.map(|_| {
+                game.challenge_deadline().map(|_| {
+                    format!(
+                        "{} played; the play can be challenged for {} seconds",
+                        player,
+                        game.options().challenge_window
+                    )
+                })
+            }),
+            GameEvent::Swap(turn) => {
+                let turn_len = turn.len();
+                game.swap(turn)
+                    .map(|_| Some(format!("{} swapped {} tiles", player, turn_len)))
+            }
+            GameEvent::Pass {} => game.pass().map(|_| Some(format!("{} passed", player))),
+            other => {
+                error!("not a move: {:?}", other);
+                Err(scrabble::Error::Unknown)
+            }
+        };
+
+        // save state even if an error is returned (a failed move may still count as a try)
+        let saved = self.save_state().await;
+
+        let game = self.game.as_ref().unwrap();
+        if game.is_over() {
+            scrabble::persistence::retain_finished(game);
+        }
+
+        Played {
+            result,
+            saved,
+            bag_emptied: !bag_was_empty && game.bag_empty() && !game.is_over(),
+        }
+    }
+
+    async fn save_state(&mut self) -> Result<(), scrabble::Error> {
+        match self.game.as_mut().unwrap().persist(&self.pg_pool).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("error saving game state; e={:?}", e);
+
+                Err(e)
+            }
+        }
     }
 }
 
-impl NewChannel for GameChannel {
-    fn new_channel(&self, channel_id: ChannelId) -> Box<dyn Channel> {
-        Box::new(GameChannel::new(self.pg_pool.clone(), channel_id))
+// What the handlers need of the message being handled: the socket it came from, the event, and
+// the means to reply or broadcast. The registry hands channels a `MessageContext`; tests drive
+// `handle_event` with a context that records what's sent. A copy can be kept by a task that
+// broadcasts once it's done.
+trait EventContext: Clone + Send + Sync + 'static {
+    type Socket;
+
+    fn socket(&self) -> Self::Socket;
+    fn kind(&self) -> &MessageKind;
+    fn event(&self) -> &str;
+    fn payload(&self) -> &serde_json::Value;
+    fn msg_ref(&self) -> Option<String>;
+
+    fn build_push(
+        &self,
+        msg_ref: Option<String>,
+        event: String,
+        payload: serde_json::Value,
+    ) -> Message;
+    fn build_broadcast(&self, event: String, payload: serde_json::Value) -> Message;
+    fn build_broadcast_intercept(&self, event: String, payload: serde_json::Value) -> Message;
+    fn broadcast(&self, event: String, payload: serde_json::Value);
+    fn broadcast_intercept(&self, event: String, payload: serde_json::Value);
+}
+
+impl EventContext for MessageContext {
+    type Socket = Token;
+
+    fn socket(&self) -> Token {
+        self.token
+    }
+
+    fn kind(&self) -> &MessageKind {
+        &self.inner.kind
+    }
+
+    fn event(&self) -> &str {
+        &self.inner.event
+    }
+
+    fn payload(&self) -> &serde_json::Value {
+        &self.inner.payload
+    }
+
+    fn msg_ref(&self) -> Option<String> {
+        self.msg_ref.clone()
+    }
+
+    fn build_push(
+        &self,
+        msg_ref: Option<String>,
+        event: String,
+        payload: serde_json::Value,
+    ) -> Message {
+        MessageContext::build_push(self, msg_ref, event, payload)
+    }
+
+    fn build_broadcast(&self, event: String, payload: serde_json::Value) -> Message {
+        MessageContext::build_broadcast(self, event, payload)
+    }
+
+    fn build_broadcast_intercept(&self, event: String, payload: serde_json::Value) -> Message {
+        MessageContext::build_broadcast_intercept(self, event, payload)
+    }
+
+    fn broadcast(&self, event: String, payload: serde_json::Value) {
+        MessageContext::broadcast(self, event, payload)
+    }
+
+    fn broadcast_intercept(&self, event: String, payload: serde_json::Value) {
+        MessageContext::broadcast_intercept(self, event, payload)
+    }
+}
+
+fn error_push(context: &impl EventContext, e: &scrabble::Error) -> Message {
+    context.build_push(context.msg_ref(), "error".into(), e.payload())
+}
+
+// The seat (index and player) of a socket, if it has joined as a player rather than a spectator
+fn seat(state: Option<&http::Extensions>) -> Option<(usize, Player)> {
+    let state = state?;
+    let PlayerIndex(index) = state.get::<PlayerIndex>()?;
+    let player = state.get::<Player>()?;
+
+    Some((*index, player.clone()))
+}
+
+// A new game's seed, from the join payload. Whoever picks the seed knows the bag order, so it's
+// only taken with the debug hooks; otherwise only an admin can seed a game (`/admin/games`).
+#[cfg(any(test, feature = "debug-hooks"))]
+fn join_seed(payload: &serde_json::Value) -> Option<u64> {
+    payload.get("seed").and_then(|s| s.as_u64())
+}
+
+#[cfg(not(any(test, feature = "debug-hooks")))]
+fn join_seed(_payload: &serde_json::Value) -> Option<u64> {
+    None
+}
+
+// The session a join payload's token was minted from (see `web::show_game`). The socket's own
+// cookie was checked in `web::ws_handler`, but `handle_connect` can't hand it to the channel,
+// so the token is what a join goes on.
+fn join_session(token: Option<&serde_json::Value>) -> Result<Session, channel::Error> {
+    let token = token
+        .ok_or_else(|| channel::Error::Other("token not found".into()))?
+        .as_str()
+        .and_then(|token| Session::read_token(token.to_string()))
+        .filter(|session| session.user_id.is_some());
+
+    token.ok_or_else(|| channel::Error::Other("token was not valid".into()))
+}
+
+// The users watching without a seat: sockets with a player name but no `PlayerIndex`, each user
+// counted once however many sockets they have open
+fn spectators<K>(socket_state: &HashMap<K, http::Extensions>) -> HashSet<&str> {
+    socket_state
+        .values()
+        .filter(|state| state.get::<PlayerIndex>().is_none())
+        .filter_map(|state| state.get::<Player>())
+        .map(Player::as_str)
+        .collect()
+}
+
+// After a (re)join, a socket's next state comes with the info messages it may have missed
+fn replay_recent(
+    recent_messages: &VecDeque<serde_json::Value>,
+    state: Option<&mut http::Extensions>,
+    payload: &mut serde_json::Value,
+) {
+    if state
+        .and_then(|state| state.remove::<ReplayRecent>())
+        .is_some()
+    {
+        payload["recent"] = json!(recent_messages);
+    }
+}
+
+// The outcome of a move, and separately whether the game could be saved after it. A failed save
+// doesn't undo the move, so it mustn't be reported in place of the move's result.
+struct Played {
+    result: Result<Option<String>, scrabble::Error>,
+    saved: Result<(), scrabble::Error>,
+    // the move drew the last tile from the bag, starting the endgame
+    bag_emptied: bool,
+}
+
+// Removes the sockets that have missed too many heartbeats, returning their state. A socket that
+// hasn't sent anything yet is left alone (it joined before the check was in place, or just now).
+fn evict_stale<K: Clone + Eq + std::hash::Hash>(
+    socket_state: &mut HashMap<K, http::Extensions>,
+    now: Instant,
+) -> Vec<http::Extensions> {
+    let timeout = HEARTBEAT_INTERVAL * MISSED_HEARTBEAT_LIMIT;
+    let stale: Vec<K> = socket_state
+        .iter()
+        .filter(|(_, state)| {
+            matches!(state.get::<LastSeen>(), Some(LastSeen(seen)) if now.duration_since(*seen) > timeout)
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    stale
+        .iter()
+        .filter_map(|key| socket_state.remove(key))
+        .collect()
+}
+
+// Inbound channel events, parsed from the event name and its payload
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event", content = "payload", rename_all = "snake_case")]
+enum GameEvent {
+    Start {},
+    Play(#[serde(deserialize_with = "turn_payload")] Turn),
+    Swap(#[serde(deserialize_with = "turn_payload")] Turn),
+    Pass {},
+    Proposed(#[serde(deserialize_with = "turn_payload")] Turn),
+    Forfeit {},
+    Redraw {},
+    Rematch {},
+    BroadcastInvite {},
+    Heartbeat {},
+    #[serde(rename = "replace-with-bot")]
+    ReplaceWithBot {
+        player_index: usize,
+    },
+    RevealRack {
+        reveal: bool,
+    },
+    // a spectator choosing the seat to follow (or none)
+    Follow {
+        player_index: Option<usize>,
+    },
+    Challenge {},
+}
+
+impl GameEvent {
+    fn parse(event: &str, payload: &serde_json::Value) -> Result<Self, String> {
+        serde_json::from_value(json!({ "event": event, "payload": payload }))
+            .map_err(|e| e.to_string())
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            GameEvent::Start {} => "start",
+            GameEvent::Play(..) => "play",
+            GameEvent::Swap(..) => "swap",
+            GameEvent::Pass {} => "pass",
+            GameEvent::Proposed(..) => "propose",
+            GameEvent::Forfeit {} => "forfeit",
+            GameEvent::Redraw {} => "redraw",
+            GameEvent::Rematch {} => "rematch",
+            GameEvent::BroadcastInvite {} => "invite",
+            GameEvent::Heartbeat {} => "heartbeat",
+            GameEvent::ReplaceWithBot { .. } => "replace-with-bot",
+            GameEvent::RevealRack { .. } => "reveal_rack",
+            GameEvent::Follow { .. } => "follow",
+            GameEvent::Challenge {} => "challenge",
+        }
+    }
+
+    // Which events make sense in each game state; checked before any game logic runs, so a
+    // misbehaving client can't e.g. restart a game in progress.
+    fn allowed_in(&self, state: &State) -> Result<(), scrabble::Error> {
+        if matches!(self, GameEvent::Heartbeat {}) {
+            return Ok(());
+        }
+
+        let allowed = match state {
+            State::Pre => matches!(
+                self,
+                GameEvent::Start {}
+                    | GameEvent::BroadcastInvite {}
+                    | GameEvent::RevealRack { .. }
+                    | GameEvent::Follow { .. }
+            ),
+            State::Started => matches!(
+                self,
+                GameEvent::Play(..)
+                    | GameEvent::Swap(..)
+                    | GameEvent::Pass {}
+                    | GameEvent::Proposed(..)
+                    | GameEvent::Forfeit {}
+                    | GameEvent::Redraw {}
+                    | GameEvent::ReplaceWithBot { .. }
+                    | GameEvent::RevealRack { .. }
+                    | GameEvent::Follow { .. }
+                    | GameEvent::Challenge {}
+            ),
+            State::Over => matches!(self, GameEvent::Rematch {} | GameEvent::Follow { .. }),
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(scrabble::Error::EventNotAllowed(self.name(), state.clone()))
+        }
+    }
+}
+
+fn turn_payload<'de, D>(deserializer: D) -> Result<Turn, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut value = serde_json::Value::deserialize(deserializer)?;
+    // sent alongside the tiles of a move; see `Game::check_move_count`
+    if let Some(map) = value.as_object_mut() {
+        map.remove("move_count");
+    }
+
+    Turn::try_from(value).map_err(|e| de::Error::custom(format!("invalid turn: {}", e)))
+}
+
+impl<S: Copy + Eq + Hash> GameChannel<S> {
+    // Every inbound event is handled in a span naming the game, the event and (for a seated
+    // socket) the player, so log lines can be traced back to them.
+    #[tracing::instrument(
+        name = "event",
+        skip_all,
+        fields(
+            game = %self.name().unwrap_or_default(),
+            event = %context.event(),
+            player = tracing::field::Empty,
+        )
+    )]
+    async fn handle_event(&mut self, context: &impl EventContext<Socket = S>) -> Option<Message> {
+        if let Some((_, player)) = seat(self.socket_state.get(&context.socket())) {
+            tracing::Span::current().record("player", player.0.as_str());
+        }
+
+        self.apply_termination(context).await;
+
+        // anything from a socket shows it's alive
+        self.socket_state
+            .entry(context.socket())
+            .or_default()
+            .insert(LastSeen(Instant::now()));
+
+        match context.kind() {
+            MessageKind::Event => {
+                let state = self.game.as_ref().unwrap().state();
+                let event = GameEvent::parse(context.event(), context.payload())
+                    .map_err(|message| json!({ "code": "bad_message", "message": message }))
+                    .and_then(|event| {
+                        event.allowed_in(state).map_err(|e| e.payload())?;
+                        Ok(event)
+                    });
+
+                match event {
+                    Err(error) => {
+                        warn!(
+                            "bad message [{}]; payload={:?}; e={}",
+                            context.event(),
+                            context.payload(),
+                            error
+                        );
+
+                        Some(context.build_push(context.msg_ref(), "error".into(), error))
+                    }
+
+                    Ok(GameEvent::Start {}) => {
+                        if let Err(e) = self.game.as_mut().unwrap().start() {
+                            return Some(error_push(context, &e));
+                        }
+                        let _ = self.save_state().await;
+                        self.broadcast_hint(context);
+
+                        Some(
+                            context.build_broadcast_intercept(
+                                "player-state".into(),
+                                Default::default(),
+                            ),
+                        )
+                    }
+
+                    Ok(
+                        event @ (GameEvent::Play(..) | GameEvent::Swap(..) | GameEvent::Pass {}),
+                    ) => {
+                        let (index, player) = match seat(self.socket_state.get(&context.socket())) {
+                            Some(seat) => seat,
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        let expected = context.payload().get("move_count").and_then(|c| c.as_u64());
+                        let game = self.game.as_ref().unwrap();
+                        if let Err(e) = game.check_move_count(expected) {
+                            warn!(
+                                "ignoring out of date move from {}; move_count={:?}",
+                                player, expected
+                            );
+
+                            // lets the client see the game has moved on since, and resync
+                            let mut payload = e.payload();
+                            payload["move_count"] = json!(game.move_count());
+                            return Some(context.build_push(
+                                context.msg_ref(),
+                                "error".into(),
+                                payload,
+                            ));
+                        }
+
+                        let played = self.play(event, index, player).await;
+                        if played.saved.is_err() {
+                            let message = self.info(
+                                "the game could not be saved; recent moves may be lost".into(),
+                            );
+                            context.broadcast("info".into(), message);
+                        }
+
+                        if played.bag_emptied {
+                            let message =
+                                self.info("the bag is empty; the endgame has begun".into());
+                            context.broadcast("info".into(), message);
+                        }
+
+                        match played.result {
+                            Ok(msg) => {
+                                // announced before any moves the bot makes in reply
+                                if let Some(message) = msg {
+                                    context.broadcast("info".into(), self.info(message));
+                                }
+                                self.play_bots(context).await;
+                                self.schedule_finalize(context);
+
+                                context
+                                    .broadcast_intercept("player-state".into(), Default::default());
+                                self.broadcast_game_over(context);
+                                self.broadcast_hint(context);
+
+                                None
+                            }
+                            Err(e) => {
+                                warn!(code = e.error_code(), "move rejected: {}", e);
+
+                                match e {
+                                    scrabble::Error::TriesExhausted => {
+                                        let reply = context.build_broadcast_intercept(
+                                            "player-state".into(),
+                                            Default::default(),
+                                        );
+
+                                        let state =
+                                            self.socket_state.entry(context.socket()).or_default();
+                                        let message = format!(
+                                            "{:?} lost a turn due to illegal maneuvers!",
+                                            state.get::<Player>()
+                                        );
+
+                                        context.broadcast("info".into(), self.info(message));
+
+                                        // the turn may have passed to the bot
+                                        self.play_bots(context).await;
+                                        self.schedule_finalize(context);
+                                        self.broadcast_game_over(context);
+                                        self.broadcast_hint(context);
+
+                                        Some(reply)
+                                    }
+                                    // the last play went out, and this move made it final
+                                    scrabble::Error::GameOver => {
+                                        self.broadcast_game_over(context);
+                                        context.broadcast_intercept(
+                                            "player-state".into(),
+                                            Default::default(),
+                                        );
+
+                                        Some(error_push(context, &e))
+                                    }
+                                    scrabble::Error::NoTileToSpend(_) => Some(context.build_push(
+                                        context.msg_ref(),
+                                        "error".into(),
+                                        self.stale_rack_error(&e, index),
+                                    )),
+                                    _ => Some(error_push(context, &e)),
+                                }
+                            }
+                        }
+                    }
+
+                    Ok(GameEvent::Proposed(turn)) => {
+                        let proposed = match seat(self.socket_state.get(&context.socket())) {
+                            Some((index, _)) => self.propose(&turn, index),
+                            None => Err(scrabble::Error::NotSeated),
+                        };
+
+                        match proposed {
+                            Ok(scores) => Some(context.build_push(
+                                context.msg_ref(),
+                                "info".into(),
+                                scores.preview(),
+                            )),
+
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    // another player disputing the last play, within its challenge window
+                    Ok(GameEvent::Challenge {}) => {
+                        let (index, player) = match seat(self.socket_state.get(&context.socket())) {
+                            Some(seat) => seat,
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        let game = self.game.as_mut().unwrap();
+                        match game.challenge(index, scrabble::unix_now()).await {
+                            Ok(words) => {
+                                let _ = self.save_state().await;
+                                let message = if words.is_empty() {
+                                    format!("{} challenged the last play, which stands", player)
+                                } else {
+                                    format!(
+                                        "{} challenged the last play, which is withdrawn: {}",
+                                        player,
+                                        scrabble::Error::IllegalWords(words)
+                                    )
+                                };
+                                context.broadcast("info".into(), self.info(message));
+                                // the turn may be the bot's, once a withdrawn play is taken back
+                                self.play_bots(context).await;
+                                self.schedule_finalize(context);
+                                self.broadcast_game_over(context);
+
+                                Some(context.build_broadcast_intercept(
+                                    "player-state".into(),
+                                    Default::default(),
+                                ))
+                            }
+                            // the play is now final for everyone
+                            Err(e @ scrabble::Error::ChallengeTooLate) => {
+                                let _ = self.save_state().await;
+                                self.broadcast_game_over(context);
+                                context
+                                    .broadcast_intercept("player-state".into(), Default::default());
+
+                                Some(error_push(context, &e))
+                            }
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    // a player can only forfeit their own seat
+                    Ok(GameEvent::Forfeit {}) => {
+                        let result = match seat(self.socket_state.get(&context.socket())) {
+                            Some((index, _)) => self.game.as_mut().unwrap().forfeit(index),
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        match result {
+                            Ok(()) => {
+                                let _ = self.save_state().await;
+                                self.broadcast_game_over(context);
+
+                                Some(context.build_broadcast_intercept(
+                                    "player-state".into(),
+                                    Default::default(),
+                                ))
+                            }
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    Ok(GameEvent::Redraw {}) => {
+                        let (index, player) = match seat(self.socket_state.get(&context.socket())) {
+                            Some(seat) => seat,
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        match self.game.as_mut().unwrap().redraw_opening_rack(index) {
+                            Ok(()) => {
+                                let _ = self.save_state().await;
+                                let message =
+                                    self.info(format!("{} redrew their opening rack", player));
+                                context.broadcast("info".into(), message);
+
+                                Some(context.build_broadcast_intercept(
+                                    "player-state".into(),
+                                    Default::default(),
+                                ))
+                            }
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    // any player can ask for a rematch; everyone is told where to find it
+                    Ok(GameEvent::Rematch {}) => {
+                        let player = match seat(self.socket_state.get(&context.socket())) {
+                            Some((_, player)) => player,
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        let created = self.rematch_id.is_none();
+                        match self.rematch().await {
+                            Ok(rematch) => {
+                                if created {
+                                    let message =
+                                        self.info(format!("{} started a rematch", player));
+                                    context.broadcast("info".into(), message);
+                                }
+
+                                Some(context.build_broadcast("rematch".into(), rematch))
+                            }
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    // another player's seat, once they've been away for a while
+                    Ok(GameEvent::ReplaceWithBot { player_index }) => {
+                        let player = match seat(self.socket_state.get(&context.socket())) {
+                            Some((_, player)) => player,
+                            None => {
+                                return Some(error_push(context, &scrabble::Error::NotSeated));
+                            }
+                        };
+
+                        let replaced = self.check_replaceable(player_index).and_then(|_| {
+                            self.game.as_mut().unwrap().replace_with_bot(player_index)
+                        });
+                        if let Err(e) = replaced {
+                            return Some(error_push(context, &e));
+                        }
+
+                        let _ = self.save_state().await;
+                        let replaced = self.game.as_ref().unwrap().players()[player_index].clone();
+                        let message =
+                            self.info(format!("{} handed {}'s seat to the bot", player, replaced));
+                        context.broadcast("info".into(), message);
+
+                        self.play_bots(context).await;
+                        self.broadcast_game_over(context);
+                        self.broadcast_hint(context);
+
+                        Some(
+                            context.build_broadcast_intercept(
+                                "player-state".into(),
+                                Default::default(),
+                            ),
+                        )
+                    }
+
+                    // a player lets spectators following their seat see their rack, or stops
+                    Ok(GameEvent::RevealRack { reveal }) => {
+                        let result = match seat(self.socket_state.get(&context.socket())) {
+                            Some((index, _)) => {
+                                self.game.as_mut().unwrap().set_reveal_rack(index, reveal)
+                            }
+                            None => Err(scrabble::Error::NotSeated),
+                        };
+
+                        match result {
+                            Ok(()) => {
+                                let _ = self.save_state().await;
+
+                                Some(context.build_broadcast_intercept(
+                                    "player-state".into(),
+                                    Default::default(),
+                                ))
+                            }
+                            Err(e) => Some(error_push(context, &e)),
+                        }
+                    }
+
+                    Ok(GameEvent::Follow { player_index }) => {
+                        let state = self.socket_state.entry(context.socket()).or_default();
+                        if state.get::<Spectator>().is_none() {
+                            return Some(error_push(context, &scrabble::Error::NotSpectating));
+                        }
+
+                        match player_index {
+                            Some(index) => state.insert(Following(index)),
+                            None => state.remove::<Following>(),
+                        };
+
+                        Some(context.build_push(
+                            context.msg_ref(),
+                            "player-state".into(),
+                            self.game.as_ref().unwrap().spectator_state(player_index),
+                        ))
+                    }
+
+                    // nothing to do beyond noting the socket is alive
+                    Ok(GameEvent::Heartbeat {}) => None,
+
+                    // FIXME: invites aren't implemented yet
+                    Ok(GameEvent::BroadcastInvite {}) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Channel for GameChannel {
+    async fn handle_message(&mut self, context: &MessageContext) -> Option<Message> {
+        self.handle_event(context).await
+    }
+
+    async fn handle_out(&mut self, context: &MessageContext) -> Option<Message> {
+        match &context.inner.kind {
+            // The ticker's wake-up, handed to the channel once for each socket; the work is only
+            // done on the first (the rest find nothing due)
+            MessageKind::BroadcastIntercept if context.inner.event == "tick" => {
+                self.tick(context).await;
+                None
+            }
+            MessageKind::BroadcastIntercept => {
+                let index = self
+                    .socket_state
+                    .get(&context.token)
+                    .and_then(|entry| entry.get::<PlayerIndex>())
+                    .map(|PlayerIndex(index)| *index);
+
+                match context.inner.event.as_ref() {
+                    "player-state" => {
+                        let game = self.game.as_ref().unwrap();
+                        let mut payload = match index {
+                            Some(_) => game.state_for(index),
+                            None => game.spectator_state(
+                                self.socket_state
+                                    .get(&context.token)
+                                    .and_then(|state| state.get::<Following>())
+                                    .map(|Following(index)| *index),
+                            ),
+                        };
+                        replay_recent(
+                            &self.recent_messages,
+                            self.socket_state.get_mut(&context.token),
+                            &mut payload,
+                        );
+
+                        let reply = context.build_push(
+                            context.msg_ref.clone(),
+                            context.inner.event.clone(),
+                            payload,
+                        );
+
+                        Some(reply)
+                    }
+                    // only for the player it's meant for, if no move has been made since
+                    "hint" => {
+                        let payload = &context.inner.payload;
+                        let move_count = self.game.as_ref().unwrap().move_count();
+                        let current = payload["move_count"] == json!(move_count);
+
+                        (current && index.is_some() && payload["player_index"] == json!(index))
+                            .then(|| {
+                                context.build_push(
+                                    context.msg_ref.clone(),
+                                    "info".into(),
+                                    json!({ "message": payload["message"] }),
+                                )
+                            })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    async fn handle_join(
+        &mut self,
+        context: &MessageContext,
+    ) -> Result<Option<Message>, channel::Error> {
+        if self.game.is_none() {
+            match context.channel_id().value() {
+                Some(id) if scrabble::valid_game_id(id) => (),
+                _ => return Err(channel::Error::Other("invalid game id".into())),
+            }
+
+            // only used if the game doesn't exist yet
+            let seed = join_seed(&context.inner.payload);
+            let game = Game::fetch(context.channel_id().clone(), seed, &self.pg_pool)
+                .await
+                .map_err(|e| channel::Error::Other(format!("game could not be loaded: {}", e)))?;
+            debug!("setting up game {:?}...", context.channel_id());
+            self.game = Some(game);
+
+            let live = LiveGame::default();
+            self.start_ticker(context, live.wake.clone());
+            if let Some(name) = self.name() {
+                LIVE_GAMES.lock().insert(name, live);
+            }
+            // a play may have been left pending when the channel last closed
+            self.schedule_finalize(context);
+        }
+
+        debug!("{:?}", context);
+        let session = join_session(context.inner.payload.get("token"))?;
+        let user = self.join_user(&session).await?;
+
+        self.join_as(context, Player(user.username)).await
+    }
+
+    // FIXME: MessageContext
+    async fn handle_presence(
+        &mut self,
+        channel_id: &ChannelId,
+        presence: &Presence,
+    ) -> axum_channels::channel::Result<Option<Message>> {
+        self.record_presence(presence.data.values());
+
+        let message = Message {
+            channel_id: channel_id.clone(),
+            event: "presence".into(),
+            payload: self.presence_summary(),
+            kind: MessageKind::Broadcast,
+            msg_ref: None,
+            join_ref: None,
+            channel_sender: None,
+        };
+
+        Ok(Some(message))
+    }
+
+    async fn handle_leave(
+        &mut self,
+        context: &MessageContext,
+    ) -> axum_channels::channel::Result<Option<Message>> {
+        if let Some(state) = self.socket_state.remove(&context.token) {
+            self.forget_socket(state);
+        }
+
+        Ok(None)
+    }
+}
+
+impl GameChannel {
+    // Outside of the messages the registry hands it, a channel only runs when a broadcast is
+    // intercepted, so the ticker wakes it with one every heartbeat interval, or sooner on `wake`
+    fn start_ticker(&mut self, context: &MessageContext, wake: Arc<Notify>) {
+        let context = context.clone();
+
+        self.ticker = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = wake.notified() => {}
+                }
+                context.broadcast_intercept("tick".into(), json!({}));
+            }
+        }));
+    }
+}
+
+impl<S> GameChannel<S> {
+    // Stops the channel's timers, and takes it out of the live games
+    fn close(&mut self) {
+        for timer in self.ticker.iter().chain(&self.deadline_timer) {
+            timer.abort();
+        }
+
+        // the template channel never loads a game
+        if self.game.is_some() {
+            if let Some(name) = self.channel_id.value() {
+                LIVE_GAMES.lock().remove(name);
+            }
+        }
+    }
+}
+
+impl<S> Drop for GameChannel<S> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl NewChannel for GameChannel {
+    fn new_channel(&self, channel_id: ChannelId) -> Box<dyn Channel> {
+        let mut channel = GameChannel::new(self.pg_pool.clone(), channel_id);
+        channel.max_spectators = self.max_spectators;
+
+        Box::new(channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nothing listens on the pool's port, so every save fails (quickly)
+    fn test_channel() -> GameChannel<usize> {
+        let pool = PgPoolOptions::new()
+            .connect_timeout(Duration::from_millis(100))
+            .connect_lazy("postgres://localhost:1/scrabble_rs_test")
+            .unwrap();
+
+        GameChannel::new(pool, "game:test".parse().unwrap())
+    }
+
+    // A two player game in progress, Frankie to move, with Frankie and Ada seated on sockets 0
+    // and 1
+    fn test_game_channel() -> GameChannel<usize> {
+        let mut channel = test_channel();
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        channel.game = Some(game);
+
+        for (socket, player) in ["Frankie", "Ada"].into_iter().enumerate() {
+            let state = channel.socket_state.entry(socket).or_default();
+            state.insert(PlayerIndex(socket));
+            state.insert(Player::from(player));
+        }
+
+        channel
+    }
+
+    // An event from one socket, as the registry would hand it to the channel, recording what the
+    // channel broadcasts while handling it
+    #[derive(Clone)]
+    struct TestContext {
+        socket: usize,
+        event: String,
+        payload: serde_json::Value,
+        broadcasts: std::sync::Arc<Mutex<Vec<Message>>>,
+    }
+
+    impl TestContext {
+        fn new(socket: usize, event: &str, payload: serde_json::Value) -> Self {
+            TestContext {
+                socket,
+                event: event.into(),
+                payload,
+                broadcasts: Default::default(),
+            }
+        }
+
+        fn message(&self, kind: MessageKind, event: String, payload: serde_json::Value) -> Message {
+            Message {
+                channel_id: "game:test".parse().unwrap(),
+                event,
+                payload,
+                kind,
+                msg_ref: None,
+                join_ref: None,
+                channel_sender: None,
+            }
+        }
+
+        // the payloads broadcast (or intercepted) as `event`
+        fn broadcasts(&self, event: &str) -> Vec<serde_json::Value> {
+            self.broadcasts
+                .lock()
+                .iter()
+                .filter(|message| message.event == event)
+                .map(|message| message.payload.clone())
+                .collect()
+        }
+    }
+
+    impl EventContext for TestContext {
+        type Socket = usize;
+
+        fn socket(&self) -> usize {
+            self.socket
+        }
+
+        fn kind(&self) -> &MessageKind {
+            &MessageKind::Event
+        }
+
+        fn event(&self) -> &str {
+            &self.event
+        }
+
+        fn payload(&self) -> &serde_json::Value {
+            &self.payload
+        }
+
+        fn msg_ref(&self) -> Option<String> {
+            None
+        }
+
+        // only the event and payload of a push are looked at
+        fn build_push(
+            &self,
+            _msg_ref: Option<String>,
+            event: String,
+            payload: serde_json::Value,
+        ) -> Message {
+            self.message(MessageKind::Event, event, payload)
+        }
+
+        fn build_broadcast(&self, event: String, payload: serde_json::Value) -> Message {
+            self.message(MessageKind::Broadcast, event, payload)
+        }
+
+        fn build_broadcast_intercept(&self, event: String, payload: serde_json::Value) -> Message {
+            self.message(MessageKind::BroadcastIntercept, event, payload)
+        }
+
+        fn broadcast(&self, event: String, payload: serde_json::Value) {
+            let message = self.build_broadcast(event, payload);
+            self.broadcasts.lock().push(message);
+        }
+
+        fn broadcast_intercept(&self, event: String, payload: serde_json::Value) {
+            let message = self.build_broadcast_intercept(event, payload);
+            self.broadcasts.lock().push(message);
+        }
+    }
+
+    #[test]
+    fn test_seat() {
+        assert!(seat(None).is_none());
+
+        let mut state = http::Extensions::new();
+        state.insert(Spectator);
+        assert!(seat(Some(&state)).is_none());
+
+        let mut state = http::Extensions::new();
+        state.insert(PlayerIndex(1));
+        state.insert(Player::from("Ada"));
+        assert_eq!(seat(Some(&state)), Some((1, Player::from("Ada"))));
+    }
+
+    // a spectator's move is refused with an error push, as for any socket without a seat
+    #[test]
+    fn test_spectator_cannot_play() {
+        let mut state = http::Extensions::new();
+        state.insert(Spectator);
+        state.insert(Player::from("Grace"));
+
+        let refused = seat(Some(&state)).ok_or(scrabble::Error::NotSeated);
+        let payload = refused.unwrap_err().payload();
+        assert_eq!(payload["code"], "not_seated");
+        assert_eq!(payload["message"], "You are not seated in this game");
+
+        // a seated player's name alone doesn't make a seat
+        let mut state = http::Extensions::new();
+        state.insert(Player::from("Ada"));
+        assert!(seat(Some(&state)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unseated_play_is_refused() {
+        let mut channel = test_game_channel();
+        let state = channel.socket_state.entry(2).or_default();
+        state.insert(Spectator);
+        state.insert(Player::from("Grace"));
+
+        let context = TestContext::new(2, "play", json!({ "112": "A", "113": "T" }));
+        let reply = channel.handle_event(&context).await.unwrap();
+        assert_eq!(reply.event, "error");
+        assert_eq!(reply.payload["code"], "not_seated");
+
+        let game = channel.game.as_ref().unwrap();
+        assert_eq!(game.move_count(), 0);
+        assert_eq!(game.player_index, 0);
+        assert!(context.broadcasts.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejoining_socket_gets_recent_messages() {
+        let mut channel = test_channel();
+
+        for i in 0..(RECENT_MESSAGE_LIMIT + 5) {
+            channel.info(format!("message {}", i));
+        }
+        assert_eq!(channel.recent_messages.len(), RECENT_MESSAGE_LIMIT);
+        assert_eq!(
+            channel.recent_messages[0],
+            json!({ "message": "message 5" })
+        );
+
+        let mut state = http::Extensions::new();
+        state.insert(ReplayRecent);
+
+        let mut payload = json!({});
+        replay_recent(&channel.recent_messages, Some(&mut state), &mut payload);
+        assert_eq!(
+            payload["recent"].as_array().unwrap().len(),
+            RECENT_MESSAGE_LIMIT
+        );
+
+        // only the first state after joining
+        let mut payload = json!({});
+        replay_recent(&channel.recent_messages, Some(&mut state), &mut payload);
+        assert_eq!(payload, json!({}));
+    }
+
+    #[test]
+    fn test_events_allowed_by_state() {
+        let turn = Turn::default();
+        let events = [
+            GameEvent::Start {},
+            GameEvent::Play(turn.clone()),
+            GameEvent::Swap(turn.clone()),
+            GameEvent::Pass {},
+            GameEvent::Proposed(turn),
+            GameEvent::Forfeit {},
+            GameEvent::Redraw {},
+            GameEvent::Rematch {},
+            GameEvent::BroadcastInvite {},
+            GameEvent::ReplaceWithBot { player_index: 1 },
+            GameEvent::RevealRack { reveal: true },
+            GameEvent::Follow { player_index: None },
+            GameEvent::Challenge {},
+        ];
+
+        // allowed in Pre, Started, Over
+        let expected = [
+            ("start", [true, false, false]),
+            ("play", [false, true, false]),
+            ("swap", [false, true, false]),
+            ("pass", [false, true, false]),
+            ("propose", [false, true, false]),
+            ("forfeit", [false, true, false]),
+            ("redraw", [false, true, false]),
+            ("rematch", [false, false, true]),
+            ("invite", [true, false, false]),
+            ("replace-with-bot", [false, true, false]),
+            ("reveal_rack", [true, true, false]),
+            ("follow", [true, true, true]),
+            ("challenge", [false, true, false]),
+        ];
+
+        for (event, (name, allowed)) in events.iter().zip(expected) {
+            assert_eq!(event.name(), name);
+
+            for (state, allowed) in [State::Pre, State::Started, State::Over]
+                .iter()
+                .zip(allowed)
+            {
+                let result = event.allowed_in(state);
+                assert_eq!(result.is_ok(), allowed, "{} {:?}", name, state);
+            }
+        }
+
+        assert_eq!(
+            GameEvent::Start {}
+                .allowed_in(&State::Started)
+                .unwrap_err()
+                .to_string(),
+            "Can't start while the game is in progress"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_termination_requests() {
+        let name = "terminated-000000";
+        assert!(!request_termination(name));
+
+        let mut channel = test_channel();
+        channel.channel_id = format!("game:{}", name).parse().unwrap();
+        channel.game = Some(Game::new(channel.channel_id.clone()));
+        LIVE_GAMES
+            .lock()
+            .insert(name.to_string(), LiveGame::default());
+
+        assert!(channel_is_live(name));
+        assert!(!take_termination(name));
+        assert!(request_termination(name));
+        assert!(take_termination(name));
+        // only applied once
+        assert!(!take_termination(name));
+
+        drop(channel);
+        assert!(!channel_is_live(name));
+    }
+
+    #[tokio::test]
+    async fn test_termination_is_applied_on_tick() {
+        let name = "terminated-000001";
+        let mut channel = test_game_channel();
+        channel.channel_id = format!("game:{}", name).parse().unwrap();
+        let live = LiveGame::default();
+        let wake = live.wake.clone();
+        LIVE_GAMES.lock().insert(name.to_string(), live);
+
+        // the ticker is woken straight away, rather than at its next interval
+        assert!(request_termination(name));
+        tokio::time::timeout(Duration::from_secs(1), wake.notified())
+            .await
+            .unwrap();
+
+        let tick = TestContext::new(0, "tick", json!({}));
+        channel.tick(&tick).await;
+        assert!(channel.game.as_ref().unwrap().is_over());
+        assert_eq!(
+            tick.broadcasts("info")[0]["message"],
+            "this game was ended by an admin"
+        );
+        assert_eq!(tick.broadcasts("game-over").len(), 1);
+
+        // the channel closes: its clients are told to leave, and it's no longer live
+        assert_eq!(tick.broadcasts("phx_close").len(), 1);
+        assert!(!channel_is_live(name));
+        assert!(!request_termination(name));
+
+        // and no later event brings it back
+        let pass = TestContext::new(0, "pass", json!({ "move_count": 0 }));
+        channel.handle_event(&pass).await;
+        assert!(channel.game.as_ref().unwrap().is_over());
+        assert_eq!(channel.game.as_ref().unwrap().move_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_turn_play_is_rejected() {
+        let mut channel = test_channel();
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        let waiting = if game.current_player() == Some("Frankie") {
+            1
+        } else {
+            0
+        };
+        channel.game = Some(game);
+
+        let played = channel
+            .play(GameEvent::Pass {}, waiting, Player::from("Ada"))
+            .await;
+        assert!(matches!(played.result, Err(scrabble::Error::NotYourTurn)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_save_keeps_the_move_result() {
+        let mut channel = test_channel();
+
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.set_bag(vec![]);
+        channel.game = Some(game);
+
+        let played = channel
+            .play(GameEvent::Pass {}, 0, Player::from("Frankie"))
+            .await;
+        assert!(played.saved.is_err());
+        assert_eq!(played.result.unwrap(), Some("Frankie passed".to_string()));
+        assert_eq!(channel.game.as_ref().unwrap().player_index, 1);
+    }
+
+    // span names, with their fields
+    type RecordedSpans = Vec<(String, HashMap<String, String>)>;
+
+    // A subscriber that only keeps the name and fields of each span opened
+    #[derive(Clone, Default)]
+    struct SpanRecorder(std::sync::Arc<Mutex<RecordedSpans>>);
+
+    #[derive(Default)]
+    struct SpanFields(HashMap<String, String>);
+
+    impl tracing::field::Visit for SpanFields {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = SpanFields::default();
+            span.record(&mut fields);
+
+            let mut spans = self.0.lock();
+            spans.push((span.metadata().name().to_string(), fields.0));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, _: &tracing::Event<'_>) {}
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_play_is_traced_with_the_game() {
+        let mut channel = test_channel();
+        channel.channel_id = "game:traced".parse().unwrap();
+
+        let mut game = Game::new("game:traced".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.set_bag(vec![]);
+        channel.game = Some(game);
+
+        let recorder = SpanRecorder::default();
+        let _default = tracing::subscriber::set_default(recorder.clone());
+        channel
+            .play(GameEvent::Pass {}, 0, Player::from("Frankie"))
+            .await;
+
+        let spans = recorder.0.lock();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "play")
+            .expect("no play span");
+        assert_eq!(fields["game"], "traced");
+        assert_eq!(fields["player"], "Frankie");
+        assert_eq!(fields["event"], "\"pass\"");
+    }
+
+    #[tokio::test]
+    async fn test_play_from_stale_rack_prompts_refresh() {
+        let mut channel = test_channel();
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.set_rack(0, vec![]).unwrap();
+        channel.game = Some(game);
+
+        let turn = match GameEvent::parse("play", &json!({ "112": "A", "113": "T" })) {
+            Ok(GameEvent::Play(turn)) => turn,
+            other => panic!("expected a play, got {:?}", other),
+        };
+        let e = channel.game.as_mut().unwrap().play(turn).await.unwrap_err();
+        assert!(matches!(e, scrabble::Error::NoTileToSpend(_)));
+
+        let payload = channel.stale_rack_error(&e, 0);
+        assert_eq!(payload["code"], "no_tile_to_spend");
+        assert_eq!(payload["tile"], "A");
+        assert_eq!(payload["refresh"], true);
+        assert_eq!(payload["resync"]["rack"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_move_is_ignored() {
+        let mut channel = test_game_channel();
+
+        let pass = TestContext::new(0, "pass", json!({ "move_count": 0 }));
+        assert!(channel.handle_event(&pass).await.is_none());
+        assert_eq!(channel.game.as_ref().unwrap().move_count(), 1);
+
+        // redelivered, with a fresh ref but the same move count
+        let reply = channel.handle_event(&pass).await.unwrap();
+        assert_eq!(reply.event, "error");
+        assert_eq!(reply.payload["code"], "out_of_order_turn");
+        assert_eq!(reply.payload["move_count"], 1);
+
+        // made before hearing of the last move, or without a count at all
+        for payload in [json!({ "move_count": 0 }), json!({})] {
+            let context = TestContext::new(1, "pass", payload);
+            let reply = channel.handle_event(&context).await.unwrap();
+            assert_eq!(reply.payload["code"], "out_of_order_turn");
+        }
+
+        let game = channel.game.as_ref().unwrap();
+        assert_eq!(game.move_count(), 1);
+        assert_eq!(game.player_index, 1);
+    }
+
+    #[tokio::test]
+    async fn test_bot_moves_after_a_lost_turn() {
+        let mut channel = test_game_channel();
+        let game = channel.game.as_mut().unwrap();
+        game.replace_with_bot(1).unwrap();
+        let rack = "EEEAAII".chars().map(scrabble::Tile::Char).collect();
+        game.set_rack(0, rack).unwrap();
+
+        // not a word, three times over
+        let play = TestContext::new(
+            0,
+            "play",
+            json!({ "112": "E", "113": "E", "114": "E", "move_count": 0 }),
+        );
+        for _ in 0..3 {
+            channel.handle_event(&play).await;
+        }
+
+        // Frankie's lost turn isn't a move, the bot's is
+        let game = channel.game.as_ref().unwrap();
+        assert_eq!(game.player_index, 0);
+        assert_eq!(game.move_count(), 1);
+
+        let info: Vec<String> = play
+            .broadcasts("info")
+            .iter()
+            .map(|info| info["message"].as_str().unwrap().to_string())
+            .filter(|message| !message.contains("could not be saved"))
+            .collect();
+        assert!(info[0].ends_with("lost a turn due to illegal maneuvers!"));
+        assert!(info[1].starts_with("Ada (bot)"));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_window_closes_on_tick() {
+        let mut channel = test_game_channel();
+        let options = scrabble::GameOptions {
+            challenge_window: 30,
+            ..Default::default()
+        };
+        let mut game = Game::with_options("game:test".parse().unwrap(), None, options).unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        let rack = "EEEAAII".chars().map(scrabble::Tile::Char).collect();
+        game.set_rack(0, rack).unwrap();
+        channel.game = Some(game);
+
+        let play = TestContext::new(
+            0,
+            "play",
+            json!({ "112": "E", "113": "E", "114": "E", "move_count": 0 }),
+        );
+        channel.handle_event(&play).await;
+
+        // the channel is woken once the window has closed
+        let deadline = channel.game.as_ref().unwrap().challenge_deadline().unwrap();
+        assert!(channel.deadline_timer.is_some());
+
+        let tick = TestContext::new(0, "tick", json!({}));
+        channel.finalize_challenged_play(&tick, deadline).await;
+        assert!(tick.broadcasts("info").is_empty());
+
+        channel.finalize_challenged_play(&tick, deadline + 1).await;
+        assert!(channel
+            .game
+            .as_ref()
+            .unwrap()
+            .challenge_deadline()
+            .is_none());
+        let info = tick.broadcasts("info");
+        assert_eq!(
+            info[0]["message"],
+            "the challenge window has closed; the last play is final"
+        );
+        assert_eq!(tick.broadcasts("player-state").len(), 1);
+    }
+
+    // a spectator socket, as `handle_join` leaves it
+    fn watch(channel: &mut GameChannel<usize>, socket: usize, player: &str) {
+        let state = channel.socket_state.entry(socket).or_default();
+        state.insert(Spectator);
+        state.insert(Player::from(player));
+    }
+
+    #[tokio::test]
+    async fn test_join_as_token_user() {
+        let token = |user_id| {
+            let mut session = Session::new();
+            session.user_id = user_id;
+            json!(session.token())
+        };
+
+        let session = join_session(Some(&token(Some(8)))).unwrap();
+        assert_eq!(session.user_id, Some(8));
+
+        // a join must say who it's for
+        assert!(join_session(Some(&token(None))).is_err());
+        assert!(join_session(Some(&json!("forged"))).is_err());
+        assert!(join_session(None).is_err());
+
+        // the socket is seated as its user
+        let mut channel = test_channel();
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        channel.game = Some(game);
+
+        let join = TestContext::new(3, "phx_join", json!({}));
+        channel.join_as(&join, Player::from("Lin")).await.unwrap();
+        let (index, player) = seat(channel.socket_state.get(&3)).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(player.as_str(), "Lin");
+    }
+
+    #[tokio::test]
+    async fn test_spectator_limit() {
+        let mut channel = test_channel();
+        channel.max_spectators = 2;
+        channel.game = Some(Game::new("game:test".parse().unwrap()));
+
+        let game = channel.game.as_mut().unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+
+        for (socket, player) in ["Lin", "Sam"].into_iter().enumerate() {
+            assert!(channel.admit_spectator(&Player::from(player)).is_ok());
+            watch(&mut channel, socket, player);
+        }
+        assert!(channel.admit_spectator(&Player::from("Grace")).is_err());
+        // another tab for someone already watching
+        assert!(channel.admit_spectator(&Player::from("Lin")).is_ok());
+
+        // seating players isn't limited by the spectator cap
+        let game = channel.game.as_mut().unwrap();
+        assert_eq!(game.add_player(Player::from("Ada")).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_seat_is_announced() {
+        let mut channel = test_channel();
+        channel.game = Some(Game::new("game:test".parse().unwrap()));
+
+        let (index, joined) = channel.take_seat(&Player::from("Frankie")).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(joined, Some(json!({ "message": "Frankie joined" })));
+
+        let (index, joined) = channel.take_seat(&Player::from("Ada")).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(joined, Some(json!({ "message": "Ada joined" })));
+
+        // reconnecting to an existing seat
+        let (index, joined) = channel.take_seat(&Player::from("Frankie")).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(joined, None);
+        assert_eq!(channel.recent_messages.len(), 2);
+    }
+
+    #[test]
+    fn test_seat_replaceable_after_grace_period() {
+        let mut channel = test_channel();
+        assert!(matches!(
+            channel.check_replaceable(1),
+            Err(scrabble::Error::CannotReplace(_))
+        ));
+
+        let long_ago = Instant::now().checked_sub(BOT_REPLACEMENT_GRACE).unwrap();
+        channel.opened_at = long_ago;
+        assert!(channel.check_replaceable(1).is_ok());
+
+        // recently left
+        channel.away_since.insert(1, Instant::now());
+        assert!(channel.check_replaceable(1).is_err());
+        channel.away_since.insert(1, long_ago);
+        assert!(channel.check_replaceable(1).is_ok());
+    }
+
+    #[test]
+    fn test_presence_summary() {
+        let mut channel = test_channel();
+        let mut game = Game::new("game:test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.add_player(Player::from("Grace")).unwrap();
+        channel.game = Some(game);
+
+        for (socket, player) in ["Frankie", "Ada"].into_iter().enumerate() {
+            let state = channel.socket_state.entry(socket).or_default();
+            state.insert(PlayerIndex(socket));
+            state.insert(Player::from(player));
+        }
+        watch(&mut channel, 2, "Lin");
+        watch(&mut channel, 3, "Sam");
+        watch(&mut channel, 4, "Lin");
+        // joined, but not yet seated or watching
+        channel.socket_state.insert(5, http::Extensions::new());
+
+        let mut presence: HashMap<usize, serde_json::Value> =
+            ["Frankie", "Ada", "Lin", "Sam", "Lin"]
+                .iter()
+                .enumerate()
+                .map(|(socket, player)| (socket, json!({ "player": player, "token": "..." })))
+                .collect();
+        // nameless entries are skipped
+        presence.insert(5, json!({ "token": "..." }));
+        presence.insert(6, json!({ "player": "" }));
+        channel.record_presence(presence.values());
+
+        let summary = channel.presence_summary();
+        let mut online: Vec<&str> = summary["online"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect();
+        online.sort_unstable();
+        assert_eq!(online, ["Ada", "Frankie", "Lin", "Sam"]);
+        // Lin is counted once for both tabs
+        assert_eq!(summary["spectator_count"], 2);
+        assert_eq!(
+            summary["players"],
+            json!([
+                { "player_index": 0, "name": "Frankie", "online": true },
+                { "player_index": 1, "name": "Ada", "online": true },
+                { "player_index": 2, "name": "Grace", "online": false },
+            ])
+        );
+
+        // dropped for missed heartbeats
+        channel.evicted.insert("Ada".into());
+        assert_eq!(channel.presence_summary()["players"][1]["online"], false);
+    }
+
+    #[test]
+    fn test_stale_sockets_are_evicted() {
+        let now = Instant::now();
+        let timeout = HEARTBEAT_INTERVAL * MISSED_HEARTBEAT_LIMIT;
+        let mut socket_state: HashMap<usize, http::Extensions> = HashMap::new();
+
+        // missed every pong since the timeout
+        let mut stale = http::Extensions::new();
+        stale.insert(LastSeen(now - timeout - Duration::from_secs(1)));
+        stale.insert(PlayerIndex(1));
+        socket_state.insert(1, stale);
+
+        let mut fresh = http::Extensions::new();
+        fresh.insert(LastSeen(now - HEARTBEAT_INTERVAL));
+        socket_state.insert(2, fresh);
+
+        socket_state.insert(3, http::Extensions::new());
+
+        let evicted = evict_stale(&mut socket_state, now);
+        assert_eq!(evicted.len(), 1);
+        assert!(matches!(
+            evicted[0].get::<PlayerIndex>(),
+            Some(PlayerIndex(1))
+        ));
+
+        assert!(!socket_state.contains_key(&1));
+        assert!(socket_state.contains_key(&2));
+        assert!(socket_state.contains_key(&3));
+        assert!(evict_stale(&mut socket_state, now).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_quiet_sockets_are_dropped_on_tick() {
+        let mut channel = test_game_channel();
+        channel.online = ["Frankie", "Ada"].iter().map(|p| p.to_string()).collect();
+
+        let now = Instant::now();
+        let quiet = now - HEARTBEAT_INTERVAL * (MISSED_HEARTBEAT_LIMIT + 1);
+        channel
+            .socket_state
+            .get_mut(&0)
+            .unwrap()
+            .insert(LastSeen(now));
+        channel
+            .socket_state
+            .get_mut(&1)
+            .unwrap()
+            .insert(LastSeen(quiet));
+
+        // no message from anyone, only the ticker's wake-up
+        let tick = TestContext::new(0, "tick", json!({}));
+        channel.tick(&tick).await;
+
+        assert!(channel.socket_state.contains_key(&0));
+        assert!(!channel.socket_state.contains_key(&1));
+        assert!(channel.away_since.contains_key(&1));
+
+        let presence = tick.broadcasts("presence");
+        assert_eq!(presence.len(), 1);
+        assert_eq!(presence[0]["players"][0]["online"], true);
+        assert_eq!(presence[0]["players"][1]["online"], false);
+
+        // nothing more is due
+        channel.tick(&tick).await;
+        assert_eq!(tick.broadcasts("presence").len(), 1);
+    }
+
+    #[test]
+    fn test_parse_game_event() {
+        assert!(matches!(
+            GameEvent::parse("start", &json!({})),
+            Ok(GameEvent::Start {})
+        ));
+        assert!(matches!(
+            GameEvent::parse("pass", &json!({})),
+            Ok(GameEvent::Pass {})
+        ));
+        assert!(matches!(
+            GameEvent::parse("forfeit", &json!({})),
+            Ok(GameEvent::Forfeit {})
+        ));
+        assert!(matches!(
+            GameEvent::parse("broadcast_invite", &json!({})),
+            Ok(GameEvent::BroadcastInvite {})
+        ));
+        assert!(matches!(
+            GameEvent::parse("replace-with-bot", &json!({ "player_index": 1 })),
+            Ok(GameEvent::ReplaceWithBot { player_index: 1 })
+        ));
+        assert!(matches!(
+            GameEvent::parse("heartbeat", &json!({})),
+            Ok(GameEvent::Heartbeat {})
+        ));
+        assert!(matches!(
+            GameEvent::parse("reveal_rack", &json!({ "reveal": true })),
+            Ok(GameEvent::RevealRack { reveal: true })
+        ));
+        assert!(matches!(
+            GameEvent::parse("follow", &json!({ "player_index": 0 })),
+            Ok(GameEvent::Follow {
+                player_index: Some(0)
+            })
+        ));
+
+        // the move count a move is sent with isn't a tile
+        let payload = json!({ "112": "M", "113": "A", "114": ":R", "move_count": 4 });
+        for event in ["play", "swap", "proposed"] {
+            match GameEvent::parse(event, &payload).unwrap() {
+                GameEvent::Play(turn) | GameEvent::Swap(turn) | GameEvent::Proposed(turn) => {
+                    assert_eq!(turn.len(), 3)
+                }
+                other => panic!("{} parsed as {:?}", event, other),
+            }
+        }
+
+        assert!(GameEvent::parse("play", &json!({ "center": "M" })).is_err());
+        assert!(GameEvent::parse("play", &json!({ "112": 7 })).is_err());
+        assert!(GameEvent::parse("swap", &json!(["M"])).is_err());
+        assert!(GameEvent::parse("resign", &json!({}))
+            .unwrap_err()
+            .contains("unknown variant"));
+
+        let oversized: serde_json::Map<String, serde_json::Value> =
+            (0..1000).map(|i| (i.to_string(), json!("A"))).collect();
+        assert!(
+            GameEvent::parse("play", &serde_json::Value::Object(oversized))
+                .unwrap_err()
+                .contains("can't place 1000 tiles")
+        );
+    }
+
+    #[test]
+    fn test_parse_socket_addr() {
+        assert_eq!(
+            parse_socket_addr("127.0.0.1", "4000").unwrap(),
+            "127.0.0.1:4000".parse().unwrap()
+        );
+        assert_eq!(
+            parse_socket_addr("::1", "3000").unwrap(),
+            "[::1]:3000".parse().unwrap()
+        );
+        assert!(parse_socket_addr("localhost", "3000").is_err());
+        assert!(parse_socket_addr("0.0.0.0", "not-a-port").is_err());
+        assert!(parse_socket_addr("0.0.0.0", "70000").is_err());
     }
 }
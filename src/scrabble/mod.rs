@@ -1,16 +1,22 @@
+use crate::dictionary::Dictionary;
 use axum_channels::types::ChannelId;
+use rand::rngs::StdRng;
 use rand::thread_rng;
-use rand::{seq::SliceRandom, Rng};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::{query, PgExecutor, PgPool};
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, error, warn};
 
+mod moves;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Game {
+    #[serde(with = "stored_board")]
     board: Board,
     players: Vec<Player>,
     pub player_index: usize,
@@ -22,20 +28,474 @@ pub struct Game {
     board_type: String,
     pkid: Option<i64>,
     name: String,
+    // consecutive scoreless turns (passes and exchanges) since the last word was played
     #[serde(default)]
     pass_count: usize,
     #[serde(default)]
     illegal_try_count: usize,
     #[serde(default)]
     turn_log: Vec<Turn>,
+    // flattened, so games saved before the options were grouped still load
+    #[serde(flatten)]
+    options: GameOptions,
+    // when set, shuffles and the starting player are reproducible
+    #[serde(default)]
+    seed: Option<u64>,
+    // every move with the rack it was made from, for post-game analysis
+    #[serde(default)]
+    history: Vec<Move>,
+    // a custom distribution and letter values; the standard set when `None`
+    #[serde(default)]
+    tile_set: Option<TileSet>,
+    // the seat of a player who forfeited, ending the game
+    #[serde(default)]
+    forfeited: Option<usize>,
+    // the seats of players who have redrawn their opening rack
+    #[serde(default)]
+    redrawn: Vec<usize>,
+    // the seats handed over to the bot (e.g. after their player abandoned the game)
+    #[serde(default)]
+    bots: Vec<usize>,
+    // the seats whose players let spectators follow their rack
+    #[serde(default)]
+    revealed: Vec<usize>,
+    // plays, swaps and passes made so far
+    #[serde(default)]
+    move_count: usize,
+    // the last play, while it can still be challenged
+    #[serde(default)]
+    pending: Option<PendingPlay>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Standing {
+    player: Player,
+    score: isize,
+    forfeited: bool,
+}
+
+/// House rules, chosen when the game is created.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GameOptions {
+    #[serde(default = "default_bingo_bonus")]
+    pub bingo_bonus: isize,
+    // tiles that must be played in one turn to earn the bingo bonus
+    #[serde(default = "default_bingo_tile_count")]
+    pub bingo_tile_count: usize,
+    // when false, any connected play is accepted without checking the word list
+    #[serde(default = "default_dictionary_enabled")]
+    pub dictionary_enabled: bool,
+    #[serde(default = "default_max_players")]
+    pub max_players: usize,
+    // each player may redraw their opening rack once, before their first move
+    #[serde(default)]
+    pub allow_mulligan: bool,
+    // hints for casual play, e.g. that nothing on a player's rack can be played; off by default,
+    // as they give away information
+    #[serde(default)]
+    pub assist: bool,
+    // tiles each player holds; racks are topped up to this after every turn
+    #[serde(default = "default_rack_size")]
+    pub rack_size: usize,
+    // seconds after a play during which another player may challenge it; 0 for no challenges.
+    // Under challenge rules, words aren't checked as they're played (see `Game::challenge`).
+    #[serde(default)]
+    pub challenge_window: u64,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            bingo_bonus: default_bingo_bonus(),
+            bingo_tile_count: default_bingo_tile_count(),
+            dictionary_enabled: default_dictionary_enabled(),
+            max_players: default_max_players(),
+            allow_mulligan: false,
+            assist: false,
+            rack_size: default_rack_size(),
+            challenge_window: 0,
+        }
+    }
+}
+
+impl GameOptions {
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::InvalidOptions(message));
+
+        if !(1..=MAX_PLAYERS).contains(&self.max_players) {
+            return invalid(format!(
+                "max_players is {}; expected between 1 and {}",
+                self.max_players, MAX_PLAYERS
+            ));
+        }
+
+        // a rack can't hold more than a word across the board
+        if !(1..=BOARD_SIZE).contains(&self.rack_size) {
+            return invalid(format!(
+                "rack_size is {}; expected between 1 and {}",
+                self.rack_size, BOARD_SIZE
+            ));
+        }
+
+        let max_bingo_tile_count = self.rack_size.max(default_bingo_tile_count());
+        if !(1..=max_bingo_tile_count).contains(&self.bingo_tile_count) {
+            return invalid(format!(
+                "bingo_tile_count is {}; expected between 1 and {}",
+                self.bingo_tile_count, max_bingo_tile_count
+            ));
+        }
+
+        if self.bingo_bonus < 0 {
+            return invalid(format!("bingo_bonus is {}", self.bingo_bonus));
+        }
+
+        if self.challenge_window > MAX_CHALLENGE_WINDOW {
+            return invalid(format!(
+                "challenge_window is {}; expected at most {}",
+                self.challenge_window, MAX_CHALLENGE_WINDOW
+            ));
+        }
+
+        Ok(())
+    }
+
+    // tiles that earn the bingo bonus; playing out a full rack always does, however small
+    fn bingo_threshold(&self) -> usize {
+        self.bingo_tile_count.min(self.rack_size)
+    }
+
+    // under challenge rules it's up to the other players to catch a word that isn't one
+    fn checks_words_on_play(&self) -> bool {
+        self.dictionary_enabled && self.challenge_window == 0
+    }
+}
+
+/// A fully custom set of tiles: how many of each letter go in the bag, and what each is worth.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TileSet {
+    pub distribution: BTreeMap<char, usize>,
+    #[serde(default)]
+    pub blanks: usize,
+    pub scores: ScoreTable,
+}
+
+impl TileSet {
+    // enough for two full racks, and not so many that the bag is unplayable
+    const MIN_TILES: usize = 14;
+    const MAX_TILES: usize = 500;
+    const MAX_SCORE: isize = 100;
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::InvalidTileSet(message));
+
+        if self.distribution.is_empty() {
+            return invalid("the distribution is empty".into());
+        }
+
+        for (letter, count) in &self.distribution {
+            if !letter.is_alphabetic() || letter.is_lowercase() {
+                return invalid(format!("{:?} is not an uppercase letter", letter));
+            }
+
+            if *count == 0 {
+                return invalid(format!("{} has no tiles", letter));
+            }
+
+            match self.scores.get(letter) {
+                Some(score) if (0..=Self::MAX_SCORE).contains(score) => (),
+                Some(score) => return invalid(format!("{} is worth {} points", letter, score)),
+                None => return invalid(format!("{} has no score", letter)),
+            }
+        }
+
+        let total = self.distribution.values().sum::<usize>() + self.blanks;
+        if !(Self::MIN_TILES..=Self::MAX_TILES).contains(&total) {
+            return invalid(format!(
+                "{} tiles in total; expected between {} and {}",
+                total,
+                Self::MIN_TILES,
+                Self::MAX_TILES
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn tiles(&self) -> Vec<(Tile, usize)> {
+        self.distribution
+            .iter()
+            .map(|(letter, count)| (Tile::Char(*letter), *count))
+            .chain(Some((Tile::Blank(None), self.blanks)).filter(|(_, count)| *count > 0))
+            .collect()
+    }
+
+    fn value(&self, tile: &Tile) -> isize {
+        match tile {
+            Tile::Char(c) => self.scores.get(c).copied().unwrap_or(0),
+            Tile::Blank(_) => 0,
+        }
+    }
+}
+
+/// Letter values for scoring, keyed by letter.
+pub type ScoreTable = BTreeMap<char, isize>;
+
+/// The standard tile sets (distribution and letter values) of other languages' editions.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+impl Language {
+    pub fn tile_set(self) -> TileSet {
+        let letters = match self {
+            Language::English => {
+                return TileSet {
+                    distribution: standard_distribution()
+                        .into_iter()
+                        .filter_map(|(tile, count)| match tile {
+                            Tile::Char(c) => Some((c, count)),
+                            Tile::Blank(_) => None,
+                        })
+                        .collect(),
+                    blanks: 2,
+                    scores: ('A'..='Z').map(|c| (c, score_char(&c))).collect(),
+                }
+            }
+            Language::French => FRENCH_LETTERS,
+            Language::German => GERMAN_LETTERS,
+            Language::Spanish => SPANISH_LETTERS,
+        };
+
+        TileSet {
+            distribution: letters.iter().map(|(c, count, _)| (*c, *count)).collect(),
+            blanks: 2,
+            scores: letters.iter().map(|(c, _, score)| (*c, *score)).collect(),
+        }
+    }
+}
+
+// (letter, count, value)
+static FRENCH_LETTERS: &[(char, usize, isize)] = &[
+    ('A', 9, 1),
+    ('B', 2, 3),
+    ('C', 2, 3),
+    ('D', 3, 2),
+    ('E', 15, 1),
+    ('F', 2, 4),
+    ('G', 2, 2),
+    ('H', 2, 4),
+    ('I', 8, 1),
+    ('J', 1, 8),
+    ('K', 1, 10),
+    ('L', 5, 1),
+    ('M', 3, 2),
+    ('N', 6, 1),
+    ('O', 6, 1),
+    ('P', 2, 3),
+    ('Q', 1, 8),
+    ('R', 6, 1),
+    ('S', 6, 1),
+    ('T', 6, 1),
+    ('U', 6, 1),
+    ('V', 2, 4),
+    ('W', 1, 10),
+    ('X', 1, 10),
+    ('Y', 1, 10),
+    ('Z', 1, 10),
+];
+
+static GERMAN_LETTERS: &[(char, usize, isize)] = &[
+    ('A', 5, 1),
+    ('Ä', 1, 6),
+    ('B', 2, 3),
+    ('C', 2, 4),
+    ('D', 4, 1),
+    ('E', 15, 1),
+    ('F', 2, 4),
+    ('G', 3, 2),
+    ('H', 4, 2),
+    ('I', 6, 1),
+    ('J', 1, 6),
+    ('K', 2, 4),
+    ('L', 3, 2),
+    ('M', 4, 3),
+    ('N', 9, 1),
+    ('O', 3, 2),
+    ('Ö', 1, 8),
+    ('P', 1, 4),
+    ('Q', 1, 10),
+    ('R', 6, 1),
+    ('S', 7, 1),
+    ('T', 6, 1),
+    ('U', 6, 1),
+    ('Ü', 1, 6),
+    ('V', 1, 6),
+    ('W', 1, 3),
+    ('X', 1, 8),
+    ('Y', 1, 10),
+    ('Z', 1, 3),
+];
+
+// The digraph tiles (CH, LL, RR) can't be represented as a single letter, so they're left out.
+static SPANISH_LETTERS: &[(char, usize, isize)] = &[
+    ('A', 12, 1),
+    ('B', 2, 3),
+    ('C', 4, 3),
+    ('D', 5, 2),
+    ('E', 12, 1),
+    ('F', 1, 4),
+    ('G', 2, 2),
+    ('H', 2, 4),
+    ('I', 6, 1),
+    ('J', 1, 8),
+    ('L', 4, 1),
+    ('M', 2, 3),
+    ('N', 5, 1),
+    ('Ñ', 1, 8),
+    ('O', 9, 1),
+    ('P', 2, 3),
+    ('Q', 1, 5),
+    ('R', 5, 1),
+    ('S', 6, 1),
+    ('T', 4, 1),
+    ('U', 5, 1),
+    ('V', 1, 4),
+    ('X', 1, 8),
+    ('Y', 1, 4),
+    ('Z', 1, 10),
+];
+
+// value of a tile in the given set, or in the standard set
+fn tile_value(tile_set: Option<&TileSet>, tile: &Tile) -> isize {
+    match tile_set {
+        Some(tile_set) => tile_set.value(tile),
+        None => score_tile(tile),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Move {
+    player_index: usize,
+    rack: Rack,
+    // tiles placed on the board; empty for swaps and passes
+    turn: Turn,
+    // number of tiles exchanged, for swaps
+    #[serde(default)]
+    swapped: usize,
+}
+
+/// A typed entry of a turn's score for the UI payload; storage keeps the labelled `TurnScore`
+/// tuples.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScoreEntry {
+    Word {
+        word: String,
+        score: isize,
+        // how the word was scored; absent on scores saved before breakdowns were kept
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        breakdown: Option<ScoredWord>,
+    },
+    Bingo {
+        score: isize,
+    },
+    RemainingTiles {
+        score: isize,
+    },
+    Pass,
+    Exchange {
+        count: usize,
+    },
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct PlayerStats {
+    total: isize,
+    // highest scoring single word, and its score
+    best_word: Option<(String, isize)>,
+    bingos: usize,
+    tiles_played: usize,
+    // turns spent exchanging tiles
+    exchanges: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TurnAnalysis {
+    player: Player,
+    played: Turn,
+    score: TurnScore,
+    best: Option<Turn>,
+    best_score: Option<TurnScore>,
+    // points left on the table; 0 when the move played was the best available
+    missed: isize,
+}
+
+fn default_bingo_bonus() -> isize {
+    50
+}
+
+fn default_bingo_tile_count() -> usize {
+    7
+}
+
+fn default_rack_size() -> usize {
+    RACK_SIZE
+}
+
+fn default_dictionary_enabled() -> bool {
+    true
+}
+
+fn default_max_players() -> usize {
+    MAX_PLAYERS
 }
 
 pub struct PlayerIndex(pub usize);
 
+// A play within its challenge window. It's on the board and scored like any other, but can still
+// be withdrawn (see `Game::challenge`).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct PendingPlay {
+    player_index: usize,
+    // unix seconds; the play is final after this
+    deadline: u64,
+    // scoreless turns before the play, as they stand again if it's withdrawn
+    pass_count: usize,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 pub mod persistence {
-    use super::Game;
+    use super::{Error, Game};
+    use parking_lot::Mutex;
     use sqlx::types::Json;
     use sqlx::{query, FromRow, PgExecutor};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+    use tracing::warn;
+
+    lazy_static::lazy_static! {
+        // Finished games are kept in memory for FINISHED_GAME_RETENTION_SECS (default 300, 0 to
+        // disable) so a rematch or review right after the game ends skips the database. This is
+        // separate from the channel, so it holds regardless of when the channel goes away.
+        static ref FINISHED: Mutex<HashMap<String, (Instant, Game)>> = Mutex::new(HashMap::new());
+        static ref RETENTION: Duration = Duration::from_secs(
+            std::env::var("FINISHED_GAME_RETENTION_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(300)
+        );
+    }
 
     #[derive(Debug)]
     pub struct SavedGame {
@@ -44,31 +504,166 @@ pub mod persistence {
         pub data: Json<Game>,
     }
 
-    pub async fn fetch<'a, E>(name: &str, db: E) -> Result<Game, sqlx::Error>
+    pub async fn fetch<'a, E>(name: &str, db: E) -> Result<Game, Error>
     where
         E: PgExecutor<'a>,
     {
         let res = query!(r#"SELECT id, data from games where games.name = $1;"#, name)
             .fetch_one(db)
-            .await?;
+            .await
+            .map_err(Error::Sqlx)?;
+
+        match res.data {
+            Some(data) => load(res.id, data),
+            None => Err(Error::Sqlx(sqlx::Error::RowNotFound)),
+        }
+    }
+
+    // Like `fetch`, but prefers a recently finished game still held in memory
+    pub async fn fetch_recent<'a, E>(name: &str, db: E) -> Result<Game, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        let retained = FINISHED
+            .lock()
+            .get(name)
+            .filter(|(finished_at, _)| finished_at.elapsed() < *RETENTION)
+            .map(|(_, game)| game.clone());
+
+        match retained {
+            Some(game) => Ok(game),
+            None => fetch(name, db).await,
+        }
+    }
+
+    // Games waiting for players with a seat open, newest first
+    pub async fn list_joinable<'a, E>(limit: i64, db: E) -> Result<Vec<Game>, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        let rows = query!(
+            r#"SELECT id, data from games where games.data->'state' = '"Pre"' ORDER BY id DESC LIMIT $1;"#,
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(Error::Sqlx)?;
 
-        if res.data.is_some() {
-            let mut game: Game = serde_json::from_value(res.data.unwrap()).unwrap();
-            let id = res.id;
+        let mut games = vec![];
+        for row in rows {
+            let game = match row.data.and_then(|data| load_listed(row.id, data)) {
+                Some(game) => game,
+                None => continue,
+            };
 
-            if game.pkid.is_none() {
-                game.pkid = Some(id);
+            if game.is_joinable() {
+                games.push(game);
             }
+        }
 
-            Ok(game)
-        } else {
-            Err(sqlx::Error::RowNotFound)
+        Ok(games)
+    }
+
+    // A player's unfinished games (waiting to start or in progress), newest first
+    pub async fn list_for_player<'a, E>(player: &str, limit: i64, db: E) -> Result<Vec<Game>, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        // `@>` (rather than `?`) so the players index is used
+        let rows = query!(
+            r#"SELECT id, data from games where games.data->'players' @> $1 AND games.data->'state' <> '"Over"' ORDER BY id DESC LIMIT $2;"#,
+            serde_json::json!([player]),
+            limit
+        )
+        .fetch_all(db)
+        .await
+        .map_err(Error::Sqlx)?;
+
+        let mut games = vec![];
+        for row in rows {
+            if let Some(game) = row.data.and_then(|data| load_listed(row.id, data)) {
+                games.push(game);
+            }
+        }
+
+        Ok(games)
+    }
+
+    // For listings: a game that fails to load is left out (and logged) rather than failing the
+    // whole list
+    fn load_listed(id: i64, data: serde_json::Value) -> Option<Game> {
+        load(id, data)
+            .map_err(|e| warn!("skipping game {} that failed to load; e={:?}", id, e))
+            .ok()
+    }
+
+    // Should be called after the finished game has been persisted
+    pub fn retain_finished(game: &Game) {
+        if !game.is_over() || RETENTION.is_zero() {
+            return;
+        }
+
+        let mut finished = FINISHED.lock();
+        finished.retain(|_, (finished_at, _)| finished_at.elapsed() < *RETENTION);
+        finished.insert(game.name.clone(), (Instant::now(), game.clone()));
+    }
+
+    // Deserialization failures (corrupt blobs, incompatible schema changes) are returned rather
+    // than panicking; fields added to `Game` need `#[serde(default)]` so older blobs keep loading.
+    pub fn load(id: i64, data: serde_json::Value) -> Result<Game, Error> {
+        let mut game: Game =
+            serde_json::from_value(migrate_blob(data)).map_err(Error::Deserialize)?;
+
+        if game.pkid.is_none() {
+            game.pkid = Some(id);
+        }
+
+        for rack in &mut game.racks {
+            rack.1 = game.options.rack_size;
+        }
+
+        game.validate_invariants()?;
+
+        // a consistency check only; a mismatch is logged rather than failing the load
+        for mismatch in game.verify_scores() {
+            warn!("score mismatch in game {:?}: {}", game.name, mismatch);
+        }
+
+        Ok(game)
+    }
+
+    // Upgrades a game saved in an older shape. Fields added to `Game` since carry
+    // `#[serde(default)]`; this covers what a default can't, like score lists missing for some
+    // players, or a move count for a game already under way (taken from the move history, which
+    // is as far back as moves are known). A missing rack isn't made up: it fails to load.
+    pub fn migrate_blob(mut data: serde_json::Value) -> serde_json::Value {
+        if let Some(game) = data.as_object_mut() {
+            let players = game
+                .get("players")
+                .and_then(serde_json::Value::as_array)
+                .map_or(0, Vec::len);
+
+            if let Some(serde_json::Value::Array(scores)) = game.get_mut("scores") {
+                while scores.len() < players {
+                    scores.push(serde_json::json!([]));
+                }
+            }
+
+            if !game.contains_key("move_count") {
+                let moves = game
+                    .get("history")
+                    .and_then(serde_json::Value::as_array)
+                    .map_or(0, Vec::len);
+                game.insert("move_count".into(), moves.into());
+            }
         }
+
+        data
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
-enum State {
+pub enum State {
     Pre,
     Started,
     Over,
@@ -80,6 +675,16 @@ impl Default for State {
     }
 }
 
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Pre => write!(f, "before the game has started"),
+            State::Started => write!(f, "while the game is in progress"),
+            State::Over => write!(f, "after the game is over"),
+        }
+    }
+}
+
 impl std::fmt::Debug for Game {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Game")
@@ -106,7 +711,112 @@ impl std::fmt::Display for Player {
     }
 }
 
-pub type Rack = Vec<Tile>;
+// Serialized as a plain list of tiles; the capacity comes from the game's options on load
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Rack(
+    Vec<Tile>,
+    #[serde(skip, default = "default_rack_size")] usize,
+);
+
+impl Rack {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Rack(Vec::new(), capacity)
+    }
+
+    // number of tiles a full rack holds
+    pub fn capacity(&self) -> usize {
+        self.1
+    }
+
+    // Tops the rack up to capacity, for as long as the bag lasts
+    pub fn draw_from(&mut self, bag: &mut Bag) {
+        while self.len() < self.capacity() {
+            match bag.pop() {
+                Some(tile) => self.0.push(tile),
+                None => return,
+            }
+        }
+    }
+
+    // Takes one tile off the rack; a played blank (whatever its letter) spends any unlettered blank
+    pub fn remove_tile(&mut self, tile: &Tile) -> Result<(), Error> {
+        let index = self
+            .iter()
+            .position(|rack_tile| match tile {
+                Tile::Char(..) => tile == rack_tile,
+                Tile::Blank(_) => matches!(rack_tile, Tile::Blank(None)),
+            })
+            .ok_or(Error::NoTileToSpend(*tile))?;
+        self.0.remove(index);
+        Ok(())
+    }
+}
+
+impl Default for Rack {
+    fn default() -> Self {
+        Rack::with_capacity(default_rack_size())
+    }
+}
+
+impl std::ops::Deref for Rack {
+    type Target = Vec<Tile>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Rack {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<Tile>> for Rack {
+    fn from(tiles: Vec<Tile>) -> Self {
+        Rack(tiles, default_rack_size())
+    }
+}
+
+impl FromIterator<Tile> for Rack {
+    fn from_iter<I: IntoIterator<Item = Tile>>(iter: I) -> Self {
+        Rack(iter.into_iter().collect(), default_rack_size())
+    }
+}
+
+impl IntoIterator for Rack {
+    type Item = Tile;
+    type IntoIter = std::vec::IntoIter<Tile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Rack {
+    type Item = &'a Tile;
+    type IntoIter = std::slice::Iter<'a, Tile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Rack {
+    type Item = &'a mut Tile;
+    type IntoIter = std::slice::IterMut<'a, Tile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl PartialEq<Vec<Tile>> for Rack {
+    fn eq(&self, other: &Vec<Tile>) -> bool {
+        &self.0 == other
+    }
+}
 
 impl Game {
     pub async fn persist<'a, E>(&mut self, db: E) -> Result<i64, Error>
@@ -143,13 +853,15 @@ impl Game {
         Ok(result.id)
     }
 
+    // A game saved as over (an admin may have ended it while it was loaded elsewhere) can only
+    // be saved over as over, so an in-progress copy can't bring it back
     async fn update<'a, E>(&self, db: E) -> Result<i64, Error>
     where
         E: PgExecutor<'a>,
     {
         warn!("Updating {:?}", self.pkid);
-        let _result = query!(
-            "UPDATE games set data = $1 WHERE id = $2 returning id;",
+        let result = query!(
+            r#"UPDATE games set data = $1 WHERE id = $2 AND (data->>'state' <> 'Over' OR $1->>'state' = 'Over') returning id;"#,
             serde_json::json!(self),
             self.pkid.as_ref().unwrap()
         )
@@ -157,81 +869,716 @@ impl Game {
         .await
         .map_err(Error::Sqlx)?;
 
+        if result.is_empty() {
+            return Err(Error::GameOver);
+        }
+
         Ok(self.pkid.unwrap())
     }
 
     fn repopulate_bag(&mut self, turn: &Turn) {
         for (_, tile) in turn.tiles.iter() {
-            self.bag.push(*tile);
+            // a blank goes back without the letter it was given
+            match tile {
+                Tile::Blank(_) => self.bag.push(Tile::Blank(None)),
+                _ => self.bag.push(*tile),
+            }
+        }
+
+        let mut rng = self.rng();
+        self.bag.shuffle(&mut rng);
+    }
+
+    // Seeded games derive their rng from the seed and the number of turns taken, so the same
+    // sequence of moves always produces the same shuffles (including after a fetch).
+    fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(self.turn_log.len() as u64)),
+            None => StdRng::from_entropy(),
         }
+    }
+
+    // Previews the score of a turn for the player at `player_index`, applying the same checks as
+    // `play` except the dictionary, so a preview is only shown for a placement `play` would accept.
+    pub fn propose(&self, turn: &Turn, player_index: usize) -> Result<TurnScore, Error> {
+        self.ensure_playable()?;
+
+        self.validate_turn_for(turn, player_index)?;
 
-        self.bag.shuffle();
+        self.score_hypothetical(turn)
     }
 
-    pub fn propose(&self, turn: &Turn) -> TurnScore {
-        // validate things other than dictionary?
+    // Scores a turn as if placed on the current board, with none of the checks `play` makes (turn
+    // order, rack, connection, dictionary). Only a placement that can't be scored is refused: an
+    // empty or non-linear turn, or one off the board or over an existing tile.
+    pub fn score_hypothetical(&self, turn: &Turn) -> Result<TurnScore, Error> {
+        turn.validate()?;
+
+        for index in turn.indexes() {
+            match self.board.0.get(*index) {
+                None => return Err(Error::IndexOutOfBounds),
+                Some(Square::Tile(..)) => return Err(Error::SquareOccupied(*index)),
+                Some(_) => (),
+            }
+        }
+
         let overlay = Overlay {
             board: &self.board,
             turn,
         };
 
-        overlay.score()
+        Ok(self.score_overlay(&overlay))
     }
-}
 
-impl Game {
-    pub fn start(&mut self) -> Result<(), Error> {
-        self.init_racks();
-        self.init_player_index();
-        self.state = State::Started;
-        Ok(())
+    // word scores from the overlay, plus the bingo bonus according to this game's rules
+    fn score_overlay(&self, overlay: &Overlay) -> TurnScore {
+        let mut score = overlay.score_with(self.tile_set.as_ref());
+
+        if overlay.turn.is_bingo(self.options.bingo_threshold()) {
+            score
+                .scores
+                .push((String::from(BINGO), self.options.bingo_bonus));
+        }
+
+        score
     }
 
-    pub fn is_over(&self) -> bool {
-        matches!(self.state, State::Over)
+    /// Replays a finished game, comparing each move with the best play that was available from
+    /// the same rack. Games saved before move history was recorded have nothing to analyze.
+    pub async fn analyze(&self) -> Result<Vec<TurnAnalysis>, Error> {
+        let dictionary = crate::dictionary::dictionary().await;
+        let game = self.clone();
+
+        tokio::task::spawn_blocking(move || game.analyze_with(dictionary))
+            .await
+            .map_err(|_| Error::Unknown)?
     }
 
-    // This is perhaps not ideal, but is easier than defining a custom serializer
-    pub fn player_state(&self, player_index: Option<&PlayerIndex>) -> serde_json::Value {
-        json!({
-            "game": {
-                "board": self.board,
-                "board_type": self.board_type,
-                "player_index": self.player_index,
-                "players": self.players,
-                "scores": self.serializable_scores(),
-                "size": self.size,
-                "state": self.state,
-                "current_player": self.current_player(),
-                "swap_allowed": self.swap_allowed(),
-                "pass_allowed": self.pass_allowed(),
+    fn analyze_with(&self, dictionary: &Dictionary) -> Result<Vec<TurnAnalysis>, Error> {
+        if !self.is_over() {
+            return Err(Error::GameNotOver);
+        }
+
+        let mut board = Board::standard()?;
+        let mut analysis = vec![];
+
+        for entry in &self.history {
+            let score = self.score_overlay(&Overlay {
+                board: &board,
+                turn: &entry.turn,
+            });
+            let (best, best_score) = self
+                .best_turn(&board, &entry.rack, dictionary)
+                .map_or((None, None), |(turn, score)| (Some(turn), Some(score)));
+            let missed = best_score
+                .as_ref()
+                .map_or(0, |best| (best.total() - score.total()).max(0));
+
+            analysis.push(TurnAnalysis {
+                player: self.players[entry.player_index].clone(),
+                played: entry.turn.clone(),
+                score,
+                best,
+                best_score,
+                missed,
+            });
+
+            board.commit_turn(&entry.turn)?;
+        }
+
+        Ok(analysis)
+    }
+
+    // Premium squares only count for the turn that covers them, so stored scores can't be
+    // recomputed from the board alone; instead the move history is replayed and each player's
+    // play scores are compared with what was stored. End-of-game adjustments are taken as stored.
+    // Games saved before the history was recorded can't be checked.
+    pub fn verify_scores(&self) -> Vec<String> {
+        if self.history.is_empty() {
+            return vec![];
+        }
+
+        let mut board = match Board::standard() {
+            Ok(board) => board,
+            Err(e) => return vec![format!("board could not be built: {:?}", e)],
+        };
+        let mut expected = vec![0; self.players.len()];
+
+        for entry in &self.history {
+            if entry.turn.indexes().any(|index| *index >= INDEX_OVERFLOW) {
+                return vec![String::from("history has a move off the board")];
+            }
+
+            let score = self.score_overlay(&Overlay {
+                board: &board,
+                turn: &entry.turn,
+            });
+
+            if let Some(total) = expected.get_mut(entry.player_index) {
+                *total += score.total();
+            }
+
+            let _ = board.commit_turn(&entry.turn);
+        }
+
+        let mut mismatches = vec![];
+
+        for (index, player) in self.players.iter().enumerate() {
+            let stored: isize = self
+                .scores
+                .get(index)
+                .into_iter()
+                .flatten()
+                .filter(|score| !score.is_adjustment())
+                .map(TurnScore::total)
+                .sum();
+
+            if stored != expected[index] {
+                mismatches.push(format!(
+                    "{} has {} points from plays, expected {}",
+                    player, stored, expected[index]
+                ));
+            }
+        }
+
+        mismatches
+    }
+}
+
+impl Game {
+    pub fn start(&mut self) -> Result<(), Error> {
+        match self.state {
+            State::Pre => (),
+            State::Started => return Err(Error::AlreadyStarted),
+            State::Over => return Err(Error::GameOver),
+        }
+
+        #[cfg(debug_assertions)]
+        let conserved = self.tiles_conserved();
+
+        self.init_racks();
+        self.init_player_index();
+        self.state = State::Started;
+
+        #[cfg(debug_assertions)]
+        if conserved {
+            self.assert_tile_conservation();
+        }
+
+        Ok(())
+    }
+
+    pub fn is_over(&self) -> bool {
+        matches!(self.state, State::Over)
+    }
+
+    // The gate for everything that changes a game in progress (moves, forfeits, redraws)
+    fn ensure_playable(&self) -> Result<(), Error> {
+        match self.state {
+            State::Pre => Err(Error::NotStarted),
+            State::Started => Ok(()),
+            State::Over => Err(Error::GameOver),
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    // For events any seated player may trigger, regardless of whose turn it is
+    pub fn assert_participant(&self, player_index: usize) -> Result<(), Error> {
+        if player_index >= self.players.len() {
+            return Err(Error::IndexOutOfBounds);
+        }
+
+        Ok(())
+    }
+
+    // For events only the current player may trigger (play, swap, pass)
+    pub fn assert_turn(&self, player_index: usize) -> Result<(), Error> {
+        self.assert_participant(player_index)?;
+
+        if self.player_index != player_index {
+            return Err(Error::NotYourTurn);
+        }
+
+        Ok(())
+    }
+
+    // Ends the game in favor of the other players
+    pub fn forfeit(&mut self, player_index: usize) -> Result<(), Error> {
+        self.ensure_playable()?;
+
+        self.assert_participant(player_index)?;
+        self.forfeited = Some(player_index);
+        self.state = State::Over;
+        Ok(())
+    }
+
+    /// Hands a seat over to the bot, which makes its moves from then on, keeping its rack and
+    /// score. At least one seat is always left to a person.
+    pub fn replace_with_bot(&mut self, player_index: usize) -> Result<(), Error> {
+        self.ensure_playable()?;
+        self.assert_participant(player_index)?;
+
+        if self.is_bot(player_index) {
+            return Ok(());
+        }
+
+        if self.bots.len() + 1 >= self.players.len() {
+            return Err(Error::CannotReplace(
+                "at least one seat must be left to a person".into(),
+            ));
+        }
+
+        self.bots.push(player_index);
+        Ok(())
+    }
+
+    pub fn is_bot(&self, player_index: usize) -> bool {
+        self.bots.contains(&player_index)
+    }
+
+    /// Lets spectators following the seat see its rack (e.g. for teaching or commentary); hidden
+    /// unless the player opts in.
+    pub fn set_reveal_rack(&mut self, player_index: usize, reveal: bool) -> Result<(), Error> {
+        self.assert_participant(player_index)?;
+
+        self.revealed.retain(|index| *index != player_index);
+        if reveal {
+            self.revealed.push(player_index);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the player's opening rack to the bag and draws a new one (a mulligan), if the game
+    /// allows it and the player hasn't moved or redrawn yet.
+    pub fn redraw_opening_rack(&mut self, player_index: usize) -> Result<(), Error> {
+        self.ensure_playable()?;
+
+        self.assert_participant(player_index)?;
+        if !self.can_redraw(player_index) {
+            return Err(Error::CannotRedraw);
+        }
+
+        let rack = std::mem::take(&mut self.racks[player_index].0);
+        self.bag.0.extend(rack);
+        let mut rng = self.rng();
+        self.bag.shuffle(&mut rng);
+        self.fill_rack_at(player_index);
+        self.redrawn.push(player_index);
+
+        Ok(())
+    }
+
+    fn can_redraw(&self, player_index: usize) -> bool {
+        self.options.allow_mulligan
+            && self.state == State::Started
+            && !self.redrawn.contains(&player_index)
+            && !self.history.iter().any(|m| m.player_index == player_index)
+    }
+
+    // Ends the game without a forfeit (e.g. an admin ending a stuck game)
+    pub fn terminate(&mut self) -> Result<(), Error> {
+        if self.is_over() {
+            return Err(Error::GameOver);
+        }
+
+        self.state = State::Over;
+        Ok(())
+    }
+
+    /// A new game (not yet saved) between the same players with the same options, started with
+    /// a fresh bag and the seat after this game's first player moving first. The rematch of a
+    /// seeded game is seeded too, so it's just as reproducible.
+    pub fn rematch(&self, channel_id: ChannelId) -> Result<Game, Error> {
+        if !self.is_over() {
+            return Err(Error::GameNotOver);
+        }
+
+        // a step of an LCG, so the rematch doesn't replay this game's shuffles
+        let seed = self
+            .seed
+            .map(|seed| seed.wrapping_mul(6364136223846793005).wrapping_add(1));
+        let mut game = Self::with_seed(channel_id, seed);
+        game.options = self.options.clone();
+
+        if let Some(tile_set) = &self.tile_set {
+            game.bag = Bag::with_distribution(tile_set.tiles(), &mut game.rng());
+            game.tile_set = Some(tile_set.clone());
+        }
+
+        for player in &self.players {
+            game.add_player(player.clone())?;
+        }
+        game.start()?;
+
+        let first = self.history.first().map_or(0, |m| m.player_index);
+        game.player_index = (first + 1) % game.players.len();
+
+        Ok(game)
+    }
+
+    /// Players ranked by total score once the game is over (winner first); a player who forfeited
+    /// ranks last regardless of score.
+    pub fn final_standings(&self) -> Option<Vec<Standing>> {
+        if !self.is_over() {
+            return None;
+        }
+
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| Standing {
+                player: player.clone(),
+                score: self.player_stats(index).total,
+                forfeited: self.forfeited == Some(index),
+            })
+            .collect();
+
+        standings.sort_by_key(|standing| (standing.forfeited, std::cmp::Reverse(standing.score)));
+        Some(standings)
+    }
+
+    /// The board rendered as a standalone SVG document.
+    pub fn board_svg(&self) -> String {
+        self.board.to_svg(self.tile_set.as_ref())
+    }
+
+    /// Game state as seen by the seated player at `player_index`, or by a spectator (`None`), whose
+    /// payload never includes a rack.
+    pub fn state_for(&self, player_index: Option<usize>) -> serde_json::Value {
+        let player_index = player_index.map(PlayerIndex);
+        let mut state = self.player_state(player_index.as_ref());
+
+        if player_index.is_none() {
+            if let Some(state) = state.as_object_mut() {
+                state.remove("rack");
+            }
+        }
+
+        state
+    }
+
+    /// State for a spectator, who sees the rack of the seat they follow only if its player has
+    /// chosen to reveal it (see `set_reveal_rack`).
+    pub fn spectator_state(&self, following: Option<usize>) -> serde_json::Value {
+        let mut state = self.state_for(None);
+
+        if let Some(index) = following.filter(|index| self.revealed.contains(index)) {
+            state["rack"] = json!(self.racks.get(index));
+            state["following"] = json!(index);
+        }
+
+        state
+    }
+
+    /// State for a seated player rejoining a game in progress: their view of the board and rack,
+    /// plus the latest moves (without the racks they were played from).
+    pub fn resync_state(&self, player_index: usize) -> serde_json::Value {
+        let mut state = self.state_for(Some(player_index));
+
+        let skip = self.history.len().saturating_sub(RESYNC_MOVES);
+        let moves: Vec<_> = self.history[skip..]
+            .iter()
+            .map(|m| {
+                json!({
+                    "player": self.players.get(m.player_index),
+                    "tiles": m.turn.tiles,
+                })
+            })
+            .collect();
+
+        state["moves"] = json!(moves);
+        state
+    }
+
+    // This is perhaps not ideal, but is easier than defining a custom serializer
+    fn player_state(&self, player_index: Option<&PlayerIndex>) -> serde_json::Value {
+        let remaining = self.remaining_tiles(player_index);
+        let blanks_remaining = remaining
+            .iter()
+            .find(|(tile, _)| tile == BLANK)
+            .map_or(0, |(_, count)| *count);
+
+        json!({
+            "game": {
+                "board": self.board,
+                "grid": self.board.to_grid_json(self.tile_set.as_ref()),
+                "board_type": self.board_type,
+                "player_index": self.player_index,
+                "players": self.players,
+                "scores": self.serializable_scores(),
+                "stats": self.serializable_stats(),
+                "size": self.size,
+                "state": self.state,
+                "current_player": self.current_player(),
+                "swap_allowed": self.swap_allowed(),
+                "pass_allowed": self.pass_allowed(),
+                "bag_empty": self.bag_empty(),
+                "redraw_allowed": matches!(player_index, Some(PlayerIndex(i)) if self.can_redraw(*i)),
                 "last_turn_indices": self.last_turn_indices(),
                 "spectating": player_index.is_none(),
+                "bots": self.bots,
+                "revealed": self.revealed,
+                "move_count": self.move_count,
+                "challengeable": self.pending.as_ref().map(|pending| json!({
+                    "player_index": pending.player_index,
+                    "deadline": pending.deadline,
+                })),
+                "challenge_allowed": matches!(player_index, Some(PlayerIndex(i)) if self.can_challenge(*i)),
             },
             "rack": self.rack(player_index),
-            "remaining": self.remaining_tiles(player_index)
+            "remaining": remaining,
+            "blanks_remaining": blanks_remaining,
         })
     }
 
+    // Once the bag is empty (mid-game), the endgame has begun: the next player to use up their
+    // rack ends the game.
+    pub fn bag_empty(&self) -> bool {
+        self.bag.is_empty()
+    }
+
     fn swap_allowed(&self) -> bool {
         matches!(self.state, State::Started) && self.bag.len() >= 7
     }
 
     fn pass_allowed(&self) -> bool {
-        matches!(self.state, State::Started) && self.bag.len() < 7
+        matches!(self.state, State::Started)
     }
 
-    fn serializable_scores(&self) -> HashMap<&str, Vec<&TurnScore>> {
+    fn serializable_scores(&self) -> HashMap<&str, Vec<Vec<ScoreEntry>>> {
         let mut map = HashMap::new();
 
         for (index, player) in self.players.iter().enumerate() {
-            map.entry(player.as_str())
-                .or_insert_with(|| self.scores[index].iter().rev().collect());
+            map.entry(player.as_str()).or_insert_with(|| {
+                let mut turns = self.score_entries(index);
+                turns.reverse();
+                turns
+            });
         }
 
         map
     }
 
+    // A player's turns in order, including the passes and exchanges from the move history (which
+    // aren't scored). Plays from before the history was recorded come first, without them.
+    fn score_entries(&self, index: usize) -> Vec<Vec<ScoreEntry>> {
+        let scores = match self.scores.get(index) {
+            Some(scores) => scores,
+            None => return vec![],
+        };
+
+        let moves: Vec<&Move> = self
+            .history
+            .iter()
+            .filter(|m| m.player_index == index)
+            .collect();
+        let played = moves.iter().filter(|m| m.turn.len() > 0).count();
+        let scored = scores.iter().filter(|s| !s.is_adjustment()).count();
+
+        let mut scores = scores.iter();
+        let mut turns: Vec<Vec<ScoreEntry>> = scores
+            .by_ref()
+            .take(scored.saturating_sub(played))
+            .map(TurnScore::entries)
+            .collect();
+
+        for m in moves {
+            if m.turn.len() > 0 {
+                turns.extend(scores.next().map(TurnScore::entries));
+            } else if m.swapped > 0 {
+                turns.push(vec![ScoreEntry::Exchange { count: m.swapped }]);
+            } else {
+                turns.push(vec![ScoreEntry::Pass]);
+            }
+        }
+
+        // end-of-game adjustments
+        turns.extend(scores.map(TurnScore::entries));
+        turns
+    }
+
+    fn serializable_stats(&self) -> HashMap<&str, PlayerStats> {
+        self.players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| (player.as_str(), self.player_stats(index)))
+            .collect()
+    }
+
+    // Tiles played come from the move history, so they're only counted for moves made since it
+    // was recorded; everything else is derived from the player's scores.
+    pub fn player_stats(&self, index: usize) -> PlayerStats {
+        let mut stats = PlayerStats::default();
+
+        for score in self.scores.get(index).into_iter().flatten() {
+            stats.total += score.total();
+
+            for (word, points) in &score.scores {
+                if word == BINGO {
+                    stats.bingos += 1;
+                } else if word != REMAINING_TILES
+                    && !matches!(&stats.best_word, Some((_, best)) if best >= points)
+                {
+                    stats.best_word = Some((word.clone(), *points));
+                }
+            }
+        }
+
+        for entry in self
+            .history
+            .iter()
+            .filter(|entry| entry.player_index == index)
+        {
+            stats.tiles_played += entry.turn.len();
+            if entry.swapped > 0 {
+                stats.exchanges += 1;
+            }
+        }
+
+        stats
+    }
+
+    /// The scoresheet as CSV: a row per turn in the order they were taken, numbered in sequence,
+    /// then any end-of-game deductions and each player's total. A bingo is noted with its word and
+    /// counted in the turn's score.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("turn,player,words,score\n");
+        let mut sheets: Vec<_> = (0..self.players.len())
+            .map(|index| self.score_entries(index).into_iter())
+            .collect();
+        let mut rows: Vec<(usize, Vec<ScoreEntry>)> = vec![];
+
+        // plays from before the move history was kept can only be put in seat order
+        let unordered: Vec<usize> = sheets
+            .iter()
+            .enumerate()
+            .map(|(index, sheet)| {
+                let moves = self
+                    .history
+                    .iter()
+                    .filter(|m| m.player_index == index)
+                    .count();
+                let adjustments = self.scores.get(index).map_or(0, |scores| {
+                    scores.iter().filter(|score| score.is_adjustment()).count()
+                });
+                sheet.len().saturating_sub(moves + adjustments)
+            })
+            .collect();
+        let rounds = unordered.iter().copied().max().unwrap_or_default();
+
+        for round in 0..rounds {
+            for (index, sheet) in sheets.iter_mut().enumerate() {
+                if round < unordered[index] {
+                    rows.extend(sheet.next().map(|entries| (index, entries)));
+                }
+            }
+        }
+
+        for m in &self.history {
+            if let Some(sheet) = sheets.get_mut(m.player_index) {
+                rows.extend(sheet.next().map(|entries| (m.player_index, entries)));
+            }
+        }
+
+        // what's left are the end-of-game deductions
+        for (index, sheet) in sheets.into_iter().enumerate() {
+            rows.extend(sheet.map(|entries| (index, entries)));
+        }
+
+        let mut turns = 0;
+
+        for (index, entries) in rows {
+            let player = &self.players[index];
+            let mut words = vec![];
+            let mut score = 0;
+            let mut adjustment = true;
+
+            for entry in &entries {
+                adjustment &= matches!(entry, ScoreEntry::RemainingTiles { .. });
+
+                match entry {
+                    ScoreEntry::Word {
+                        word,
+                        score: points,
+                        ..
+                    } => {
+                        words.push(word.clone());
+                        score += points;
+                    }
+                    ScoreEntry::Bingo { score: points } => {
+                        words.push("(bingo)".to_string());
+                        score += points;
+                    }
+                    ScoreEntry::RemainingTiles { score: points } => {
+                        words.push(REMAINING_TILES.to_string());
+                        score += points;
+                    }
+                    ScoreEntry::Pass => words.push("(pass)".to_string()),
+                    ScoreEntry::Exchange { count } => words.push(format!("(exchanged {})", count)),
+                }
+            }
+
+            // deductions only come at the end, so don't count as turns
+            let turn = if adjustment {
+                String::new()
+            } else {
+                turns += 1;
+                turns.to_string()
+            };
+
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                turn,
+                csv_field(player.as_str()),
+                csv_field(&words.join(" ")),
+                score
+            ));
+        }
+
+        for (index, player) in self.players.iter().enumerate() {
+            csv.push_str(&format!(
+                ",{},(total),{}\n",
+                csv_field(player.as_str()),
+                self.player_stats(index).total
+            ));
+        }
+
+        csv
+    }
+
+    // moves made so far, for showing e.g. "Turn 7" (the move to come)
+    pub fn move_count(&self) -> usize {
+        self.move_count
+    }
+
+    // Clients send each move with the move count of the game as they last saw it. A move sent
+    // against an earlier count (redelivered over a flaky connection, or made before the client
+    // heard of the last move) must not be applied.
+    pub fn check_move_count(&self, expected: Option<u64>) -> Result<(), Error> {
+        match expected {
+            Some(expected) if expected == self.move_count as u64 => Ok(()),
+            _ => Err(Error::OutOfOrderTurn),
+        }
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // whether a new player could take a seat
+    pub fn is_joinable(&self) -> bool {
+        self.state == State::Pre && self.players.len() < self.options.max_players
+    }
+
     pub fn current_player(&self) -> Option<&str> {
         match self.state {
             State::Pre => None,
@@ -251,11 +1598,15 @@ impl Game {
             return Err(Error::AlreadyStarted);
         }
 
+        if self.players.len() >= self.options.max_players {
+            return Err(Error::GameFull);
+        }
+
         self.players.push(player);
         let index = self.players.len() - 1;
 
         self.scores.push(Default::default());
-        self.racks.push(Rack::default());
+        self.racks.push(Rack::with_capacity(self.options.rack_size));
         self.fill_rack_at(index);
         Ok(index)
     }
@@ -269,22 +1620,41 @@ impl Game {
         let start = self.racks.len();
 
         for index in start..self.players.len() {
-            self.racks.push(Rack::default());
+            self.racks.push(Rack::with_capacity(self.options.rack_size));
             self.fill_rack_at(index);
         }
     }
 
-    fn fill_rack_at(&mut self, index: usize) {
-        let rack = &mut self.racks[index];
+    /// Replaces the rack at `index` with `tiles` taken from the bag (the old rack is returned to
+    /// the bag first), so the total tile count is unchanged.
+    #[cfg(any(test, feature = "debug-hooks"))]
+    pub fn set_rack(&mut self, index: usize, tiles: Vec<Tile>) -> Result<(), Error> {
+        let rack = self.racks.get(index).ok_or(Error::IndexOutOfBounds)?;
+        let mut bag = self.bag.0.clone();
+        bag.extend(rack.iter().copied());
 
-        while rack.len() < 7 {
-            match self.bag.pop() {
-                None => {
-                    return;
-                }
-                Some(tile) => rack.push(tile),
-            }
+        for tile in &tiles {
+            let position = bag
+                .iter()
+                .position(|t| t == tile)
+                .ok_or(Error::NoTileToSpend(*tile))?;
+            bag.remove(position);
         }
+
+        self.bag.0 = bag;
+        self.racks[index].0 = tiles;
+        Ok(())
+    }
+
+    /// Replaces the bag outright with `tiles`, drawn in the given order. Tile counts are not
+    /// checked.
+    #[cfg(any(test, feature = "debug-hooks"))]
+    pub fn set_bag(&mut self, tiles: Vec<Tile>) {
+        self.bag = Bag::from_ordered(tiles);
+    }
+
+    fn fill_rack_at(&mut self, index: usize) {
+        self.racks[index].draw_from(&mut self.bag);
     }
 
     /// Tiles left in the bag or other racks
@@ -294,25 +1664,32 @@ impl Game {
             .map(|PlayerIndex(i)| *i)
             .unwrap_or_else(|| self.racks.len());
 
+        // Blanks off the board should never carry a letter, but if one did it's still a blank,
+        // not one of that letter
+        let key = |tile: &Tile| match tile {
+            Tile::Blank(_) => BLANK.to_string(),
+            tile => tile.to_string(),
+        };
+
         let mut remaining = HashMap::new();
         for (index, rack) in self.racks.iter().enumerate() {
             if index != player_index {
                 for tile in rack.iter() {
-                    *remaining.entry(tile.to_string()).or_insert(0usize) += 1;
+                    *remaining.entry(key(tile)).or_insert(0usize) += 1;
                 }
             }
         }
 
         for tile in self.bag.0.iter() {
-            *remaining.entry(tile.to_string()).or_insert(0usize) += 1;
+            *remaining.entry(key(tile)).or_insert(0usize) += 1;
         }
 
         let mut collection = remaining.into_iter().collect::<Vec<(String, usize)>>();
 
         collection.sort_by(|(a, _), (b, _)| {
-            if a.as_str() == "BLANK" {
+            if a.as_str() == BLANK {
                 Ordering::Greater
-            } else if b.as_str() == "BLANK" {
+            } else if b.as_str() == BLANK {
                 Ordering::Less
             } else {
                 a.cmp(b)
@@ -323,24 +1700,33 @@ impl Game {
     }
 
     fn init_player_index(&mut self) {
-        self.player_index = thread_rng().gen_range(0..self.players.len());
+        self.player_index = self.rng().gen_range(0..self.players.len());
     }
 
     pub async fn play(&mut self, turn: Turn) -> Result<(), Error> {
-        match self.state {
-            State::Pre => return Err(Error::NotStarted),
-            State::Over => return Err(Error::GameOver),
-            _ => (),
-        }
+        self.settle_if_out();
+        self.ensure_playable()?;
         // FIXME: make this an atomic operation? Need something like immutable data;
         // the validation should otherwise check everything
 
         debug!("turn={:?}", turn);
-        self.validate_turn(&turn)?;
+        if let Err(e) = self.validate_turn(&turn) {
+            debug!(game = %self.name, code = e.error_code(), "invalid turn");
+            return Err(e);
+        }
+
+        #[cfg(debug_assertions)]
+        let conserved = self.tiles_conserved();
 
         match self.score_turn(&turn).await {
             Err(Error::IllegalWords(x)) => {
                 self.illegal_try_count += 1;
+                debug!(
+                    game = %self.name,
+                    tries = self.illegal_try_count,
+                    "illegal words: {:?}",
+                    x
+                );
 
                 if self.illegal_try_count >= 3 {
                     self.next_player();
@@ -356,83 +1742,311 @@ impl Game {
 
             Ok(..) => {}
         }
+        let pending = PendingPlay {
+            player_index: self.player_index,
+            deadline: unix_now() + self.options.challenge_window,
+            pass_count: self.pass_count,
+        };
+
+        self.record_move(turn.clone(), 0);
         self.spend_tiles(&turn)?;
         self.board.commit_turn(&turn)?;
         self.turn_log.push(turn);
         self.fill_rack_at(self.player_index);
         self.next_player();
         self.pass_count = 0;
-        self.check_game_over();
-        Ok(())
-    }
 
-    fn check_game_over(&mut self) {
-        if self.bag.is_empty() && self.any_rack_empty() || self.check_consecutive_passes() {
-            self.state = State::Over;
+        // a play that could still be withdrawn only ends the game once it's final (see
+        // `settle_pending`)
+        if self.options.challenge_window > 0 {
+            self.pending = Some(pending);
+        } else {
+            self.check_game_over();
+        }
 
-            for (index, rack) in self.racks.iter().enumerate() {
-                let remaining = rack.iter().fold(0, |sum, tile| sum + score_tile(tile));
-                if remaining > 0 {
-                    self.scores[index].push(TurnScore {
-                        scores: vec![("(remaining tiles)".to_string(), -remaining)],
-                    })
-                }
-            }
+        #[cfg(debug_assertions)]
+        if conserved {
+            self.assert_tile_conservation();
         }
+
+        Ok(())
     }
 
-    fn any_rack_empty(&self) -> bool {
+    // called before the rack is spent, so the history keeps the rack the move was made from; the
+    // next move makes any play still pending final
+    fn record_move(&mut self, turn: Turn, swapped: usize) {
+        self.pending = None;
+        self.move_count += 1;
+        self.history.push(Move {
+            player_index: self.player_index,
+            rack: self.racks[self.player_index].clone(),
+            turn,
+            swapped,
+        });
+    }
+
+    // Makes any pending play final, which may end the game
+    fn settle_pending(&mut self) {
+        if self.pending.take().is_some() {
+            self.check_game_over();
+        }
+    }
+
+    // The next move would make a pending play that went out final, ending the game; so it's
+    // settled first, and the move refused
+    fn settle_if_out(&mut self) {
+        if self.pending_out() {
+            self.settle_pending();
+        }
+    }
+
+    // whether the pending play went out, and so ends the game unless it's withdrawn
+    fn pending_out(&self) -> bool {
+        self.pending.is_some() && self.bag.is_empty() && self.any_rack_empty()
+    }
+
+    fn check_game_over(&mut self) {
+        if self.bag.is_empty() && self.any_rack_empty() || self.check_consecutive_passes() {
+            self.state = State::Over;
+
+            for (index, rack) in self.racks.iter().enumerate() {
+                let remaining = rack.iter().fold(0, |sum, tile| {
+                    sum + tile_value(self.tile_set.as_ref(), tile)
+                });
+                if remaining > 0 {
+                    self.scores[index].push(TurnScore {
+                        scores: vec![(REMAINING_TILES.to_string(), -remaining)],
+                        ..Default::default()
+                    })
+                }
+            }
+        }
+    }
+
+    fn any_rack_empty(&self) -> bool {
         self.racks.iter().any(|r| r.is_empty())
     }
 
+    // two rounds of scoreless turns; each player's remaining tiles are then deducted as usual
     fn check_consecutive_passes(&self) -> bool {
         self.pass_count >= (self.players.len() * 2)
     }
 
     pub fn swap(&mut self, turn: Turn) -> Result<(), Error> {
-        if !self.swap_allowed() {
-            return Err(Error::SwapNotAllowed);
+        self.settle_if_out();
+        self.ensure_playable()?;
+
+        // checked against the bag before the swapped tiles are returned to it
+        if !self.swap_allowed() || turn.len() > self.bag.len() {
+            return Err(Error::CannotSwap(self.bag.len()));
+        }
+
+        // exchanging nothing would be a pass, which has its own rules
+        if turn.len() == 0 {
+            return Err(Error::EmptyTurn);
         }
 
         self.validate_swap(&turn)?;
+
+        #[cfg(debug_assertions)]
+        let conserved = self.tiles_conserved();
+
+        self.record_move(Default::default(), turn.len());
         self.spend_tiles(&turn)?;
         self.fill_rack_at(self.player_index);
         self.repopulate_bag(&turn);
         self.turn_log.push(Default::default());
         self.next_player();
+        self.pass_count += 1;
+        self.check_game_over();
+
+        #[cfg(debug_assertions)]
+        if conserved {
+            self.assert_tile_conservation();
+        }
 
         Ok(())
     }
 
-    #[allow(dead_code)]
+    // Exchanges the current player's whole rack, e.g. for a player (or the bot) with nothing
+    // worth playing.
+    pub fn exchange_all(&mut self) -> Result<(), Error> {
+        self.ensure_playable()?;
+
+        let rack = self.racks[self.player_index].clone();
+        self.swap(Turn {
+            tiles: rack.into_iter().enumerate().collect(),
+        })
+    }
+
+    // A player may always pass (only exchanging depends on the bag); enough consecutive passes
+    // and exchanges end the game, which is how it ends once the bag is empty and no one can play.
     pub fn pass(&mut self) -> Result<(), Error> {
-        if !self.pass_allowed() {
-            return Err(Error::CannotPass);
-        }
+        self.settle_if_out();
+        self.ensure_playable()?;
 
+        self.record_move(Default::default(), 0);
         self.next_player();
         self.pass_count += 1;
         self.turn_log.push(Default::default());
         self.check_game_over();
 
-        #[allow(unreachable_code)]
         Ok(())
     }
 
-    fn validate_turn(&mut self, turn: &Turn) -> Result<(), Error> {
+    /// Challenges the pending play on behalf of `challenger` (any other seated player) at `now`
+    /// (unix seconds). A play forming a word that isn't in the dictionary is withdrawn; otherwise
+    /// it stands. Either way it's settled, and the words that failed are returned.
+    pub async fn challenge(&mut self, challenger: usize, now: u64) -> Result<Vec<String>, Error> {
+        self.ensure_playable()?;
+        self.assert_participant(challenger)?;
+
+        let pending = match self.pending.take() {
+            Some(pending) if pending.player_index != challenger => pending,
+            other => {
+                self.pending = other;
+                return Err(Error::NothingToChallenge);
+            }
+        };
+
+        if now > pending.deadline {
+            self.check_game_over();
+            return Err(Error::ChallengeTooLate);
+        }
+
+        // the words are checked against the board as it was before the play
+        let turn = self.turn_log.last().cloned().unwrap_or_default();
+        let standard = Board::standard()?;
+        let mut board = self.board.clone();
+        for index in turn.indexes() {
+            board.0[*index] = standard.0[*index].clone();
+        }
+
+        let overlay = Overlay {
+            board: &board,
+            turn: &turn,
+        };
+        // without the dictionary, any play stands
+        let dictionary_enabled = self.options.dictionary_enabled;
+        match overlay
+            .validate_words(dictionary_enabled, self.tile_set.as_ref())
+            .await
+        {
+            Ok(()) => {
+                self.check_game_over();
+                Ok(vec![])
+            }
+            Err(Error::IllegalWords(words)) => {
+                self.withdraw(&pending, board, &turn);
+                Ok(words)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Takes back a successfully challenged play: its tiles return to the rack and those drawn
+    // after it to the bag, and it stands as a pass in the history.
+    fn withdraw(&mut self, pending: &PendingPlay, board: Board, turn: &Turn) {
+        let index = pending.player_index;
+        let before = match self.history.last() {
+            Some(entry) if entry.player_index == index => entry.rack.clone(),
+            _ => return,
+        };
+
+        let rack = &mut self.racks[index];
+        let kept = before.len().saturating_sub(turn.len()).min(rack.len());
+        let drawn = rack.split_off(kept);
+        // back on top of the bag, in the order they were drawn
+        self.bag.0.extend(drawn.into_iter().rev());
+        self.racks[index] = before;
+        self.board = board;
+        self.scores[index].pop();
+
+        if let Some(entry) = self.history.last_mut() {
+            entry.turn = Turn::default();
+        }
+        if let Some(logged) = self.turn_log.last_mut() {
+            *logged = Turn::default();
+        }
+
+        self.pass_count = pending.pass_count + 1;
+        self.check_game_over();
+    }
+
+    /// Makes the pending play final once its challenge window has passed at `now` (unix
+    /// seconds). Returns whether there was one to finalize.
+    pub fn finalize_expired(&mut self, now: u64) -> bool {
+        match &self.pending {
+            Some(pending) if now > pending.deadline => {
+                self.settle_pending();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // when the pending play becomes final, if there is one
+    pub fn challenge_deadline(&self) -> Option<u64> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    fn can_challenge(&self, player_index: usize) -> bool {
+        match &self.pending {
+            Some(pending) => {
+                self.state == State::Started
+                    && pending.player_index != player_index
+                    && unix_now() <= pending.deadline
+            }
+            None => false,
+        }
+    }
+
+    fn validate_turn(&self, turn: &Turn) -> Result<(), Error> {
+        self.validate_turn_for(turn, self.player_index)
+    }
+
+    fn validate_turn_for(&self, turn: &Turn, player_index: usize) -> Result<(), Error> {
+        // more than a full rack can't have come from the player's rack
+        if turn.len() > self.options.rack_size {
+            return Err(Error::TooManyTiles(turn.len(), self.options.rack_size));
+        }
+
         turn.validate()?;
 
+        let alphabet: HashSet<Tile> = self
+            .distribution()
+            .into_iter()
+            .map(|(tile, _)| tile)
+            .collect();
+        for (_, tile) in &turn.tiles {
+            match tile {
+                Tile::Char(c) | Tile::Blank(Some(c)) => {
+                    if !alphabet.contains(&Tile::Char(*c)) {
+                        return Err(Error::TileParse);
+                    }
+                }
+                // a blank has to be given a letter to be played (only exchanges can spend one
+                // without)
+                Tile::Blank(None) => return Err(Error::BlankTileInTurn),
+            }
+        }
+
         for index in turn.indexes() {
             if matches!(self.board.0.get(*index), Some(Square::Tile(..))) {
                 return Err(Error::SquareOccupied(*index));
             }
         }
 
+        self.validate_contiguous(turn)?;
         self.validate_connected(turn)?;
 
         // This is called here on a clone of the rack to ensure the tiles exist before deleting them from the actual rack.
         // FIXME: any way to do this once? This clone currently happens again in the commit.
-        Self::spend_tiles_inner(turn, self.racks[self.player_index].clone())?;
+        let rack = self
+            .racks
+            .get(player_index)
+            .ok_or(Error::IndexOutOfBounds)?;
+        Self::spend_tiles_inner(turn, rack.clone())?;
         Ok(())
     }
 
@@ -441,13 +2055,41 @@ impl Game {
         Ok(())
     }
 
-    fn validate_connected(&mut self, turn: &Turn) -> Result<(), Error> {
+    // The played tiles, together with the tiles already between them, must form a single
+    // unbroken run along the line of play; two runs on the same row are rejected even if each
+    // touches existing tiles.
+    fn validate_contiguous(&self, turn: &Turn) -> Result<(), Error> {
+        let (first, last) = match (turn.indexes().min(), turn.indexes().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Ok(()),
+        };
+
+        // `validate_linear` has already checked that the tiles share a row or a column
+        let step = if first / BOARD_SIZE == last / BOARD_SIZE {
+            1
+        } else {
+            BOARD_SIZE
+        };
+
+        for index in (first..=last).step_by(step) {
+            let placed = turn.get_tile(&index).is_some();
+            let existing = matches!(self.board.0.get(index), Some(Square::Tile(..)));
+
+            if !placed && !existing {
+                return Err(Error::TurnHasGaps);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_connected(&self, turn: &Turn) -> Result<(), Error> {
         if turn.indexes().any(|idx| *idx == BOARD_CENTER) {
             return Ok(());
         }
 
         for index in turn.indexes() {
-            for connected in Self::connected_indexes(*index) {
+            for connected in Board::connected_indexes(*index) {
                 if matches!(self.board.0.get(connected), Some(Square::Tile(..))) {
                     return Ok(());
                 }
@@ -457,41 +2099,15 @@ impl Game {
         Err(Error::NotConnected)
     }
 
-    fn connected_indexes(index: usize) -> impl Iterator<Item = usize> {
-        let original_row = index / BOARD_SIZE;
-
-        // prev in row; can't break out of original row
-        index
-            .checked_sub(1)
-            .filter(|x| x / BOARD_SIZE == original_row)
-            .into_iter()
-            .chain(
-                // next in row, can't break out of original row
-                index
-                    .checked_add(1)
-                    .filter(|x| x / BOARD_SIZE == original_row)
-                    .into_iter(),
-            )
-            .chain(
-                // col can't be less than 0
-                index.checked_sub(BOARD_SIZE).into_iter(),
-            )
-            .chain(
-                // can't be greater than board size
-                index
-                    .checked_add(BOARD_SIZE)
-                    .filter(|x| *x < INDEX_OVERFLOW)
-                    .into_iter(),
-            )
-    }
-
     async fn score_turn(&mut self, turn: &Turn) -> Result<(), Error> {
         let overlay = Overlay {
             board: &self.board,
             turn,
         };
-        overlay.validate_words().await?;
-        let score = overlay.score();
+        overlay
+            .validate_words(self.options.checks_words_on_play(), self.tile_set.as_ref())
+            .await?;
+        let score = self.score_overlay(&overlay);
         self.scores[self.player_index].push(score);
 
         Ok(())
@@ -514,15 +2130,7 @@ impl Game {
     fn spend_tiles_inner(turn: &Turn, mut rack: Rack) -> Result<Rack, Error> {
         for (_, tile) in &turn.tiles {
             debug!("searching tile={:?} rack={:?}", tile, rack);
-            let index = rack
-                .iter()
-                .position(|rack_tile| match tile {
-                    Tile::Char(..) => tile == rack_tile,
-                    Tile::Blank(Some(_)) => matches!(rack_tile, Tile::Blank(None)),
-                    Tile::Blank(None) => false,
-                })
-                .ok_or_else(|| Error::NoTileToSpend(*tile))?;
-            rack.remove(index);
+            rack.remove_tile(tile)?;
         }
         Ok(rack)
     }
@@ -534,1041 +2142,4135 @@ impl Game {
             .map(|turn| turn.indexes().copied().collect())
             .unwrap_or_default()
     }
-}
-
-impl From<&str> for Player {
-    fn from(name: &str) -> Self {
-        Player(name.to_owned())
-    }
-}
-
-impl Player {
-    pub fn as_str(&self) -> &str {
-        self.0.as_str()
-    }
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Board(Vec<Square>);
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Bag(Vec<Tile>);
+    /// Runs every invariant check and reports all of the problems found (rather than stopping at
+    /// the first); intended for admin diagnostics/repair of broken games.
+    pub fn validate_full(&self) -> Vec<String> {
+        let mut issues = self.invariant_issues();
 
-#[derive(Hash, Serialize, Deserialize, Eq, PartialEq, Copy, Clone)]
-pub enum Tile {
-    Char(char),
-    Blank(Option<char>),
-}
+        let mut seen = HashSet::new();
+        for player in self.players.iter() {
+            if !seen.insert(player) {
+                issues.push(format!("duplicate player {}", player));
+            }
+        }
 
-impl Game {
-    pub async fn fetch(channel_id: ChannelId, db: &PgPool) -> Self {
-        warn!("fetching {:?}", channel_id);
-        match persistence::fetch(channel_id.value().unwrap(), db).await {
-            Ok(game) => game,
-            e => {
-                error!("{:?}", e);
-                Game::new(channel_id)
+        for tile in self.bag.0.iter().chain(self.racks.iter().flatten()) {
+            if matches!(tile, Tile::Blank(Some(_))) {
+                issues.push(format!("assigned blank {:?} found off the board", tile));
             }
         }
+
+        issues.extend(self.tile_conservation_issues());
+        issues
     }
 
-    pub fn new(channel_id: ChannelId) -> Self {
-        Game {
-            board: Board::standard().expect("standard board could not be built"),
-            players: Default::default(),
-            player_index: 0,
-            bag: Bag::standard(),
-            racks: Default::default(),
-            scores: Default::default(),
-            state: Default::default(),
-            size: BOARD_SIZE,
-            board_type: BOARD_TYPE.to_string(),
-            pkid: None,
-            name: channel_id.value().unwrap().to_string(),
-            pass_count: 0,
-            illegal_try_count: 0,
-            turn_log: Default::default(),
+    /// Checks the shape of a loaded game (the per-player lists line up with the players, the board
+    /// is the right size), which the rest of the game indexes into without checking.
+    pub fn validate_invariants(&self) -> Result<(), Error> {
+        let issues = self.invariant_issues();
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::CorruptGame(issues.join("; ")))
         }
     }
-}
 
-pub static BOARD_SIZE: usize = 15;
-pub static BOARD_TYPE: &str = "standard";
-pub static BOARD_CENTER: usize = 112;
-static INDEX_OVERFLOW: usize = 15 * 15;
+    fn invariant_issues(&self) -> Vec<String> {
+        let mut issues = vec![];
+        let player_count = self.players.len();
 
-impl std::fmt::Debug for Tile {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Tile::Char(char) => write!(f, "[{}]", char),
-            Tile::Blank(None) => write!(f, "[ ]"),
-            Tile::Blank(Some(char)) => write!(f, "[({})]", char),
+        if self.racks.len() != player_count {
+            issues.push(format!(
+                "{} racks for {} players",
+                self.racks.len(),
+                player_count
+            ));
         }
-    }
-}
 
-impl Tile {
-    pub fn as_char(&self) -> Option<char> {
-        match *self {
-            Tile::Char(char) => Some(char),
-            Tile::Blank(Some(char)) => Some(char),
-            _ => None,
+        if self.scores.len() != player_count {
+            issues.push(format!(
+                "{} score lists for {} players",
+                self.scores.len(),
+                player_count
+            ));
         }
-    }
-}
 
-impl std::fmt::Display for Tile {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            Tile::Char(char) | Tile::Blank(Some(char)) => write!(f, "{}", char),
-            Tile::Blank(None) => write!(f, "BLANK"),
+        if self.state != State::Pre && self.player_index >= player_count {
+            issues.push(format!(
+                "player_index {} is out of range for {} players",
+                self.player_index, player_count
+            ));
+        }
+
+        if self.board.0.len() != self.size * self.size {
+            issues.push(format!(
+                "board has {} squares, expected {}",
+                self.board.0.len(),
+                self.size * self.size
+            ));
         }
+
+        issues.extend(self.unknown_tile_issues());
+
+        issues
     }
-}
 
-macro_rules! l {
-    () => {
-        Tile::Blank(None)
-    };
+    // Tiles with a letter this game's tile set doesn't have (e.g. from a hand-edited blob), which
+    // couldn't be scored or played
+    fn unknown_tile_issues(&self) -> Vec<String> {
+        let alphabet: HashSet<char> = self
+            .distribution()
+            .into_iter()
+            .filter_map(|(tile, _)| match tile {
+                Tile::Char(c) => Some(c),
+                Tile::Blank(_) => None,
+            })
+            .collect();
+        let unknown = |tile: &Tile| match tile {
+            Tile::Char(c) | Tile::Blank(Some(c)) => !alphabet.contains(c),
+            Tile::Blank(None) => false,
+        };
 
-    ($c:expr) => {
-        Tile::Char($c)
-    };
-}
+        let mut issues = vec![];
 
-macro_rules! lb {
-    () => {
-        Tile::Blank(None)
-    };
+        for (index, rack) in self.racks.iter().enumerate() {
+            for tile in rack.iter().filter(|tile| unknown(tile)) {
+                issues.push(format!("unknown tile '{}' in rack {}", tile, index));
+            }
+        }
 
-    ($c:expr) => {
-        Tile::Blank(Some($c))
-    };
-}
+        for tile in self.bag.0.iter().filter(|tile| unknown(tile)) {
+            issues.push(format!("unknown tile '{}' in the bag", tile));
+        }
 
-impl Bag {
-    pub fn pop(&mut self) -> Option<Tile> {
-        self.0.pop()
+        for (index, square) in self.board.0.iter().enumerate() {
+            match square {
+                Square::Tile(tile) if unknown(tile) => {
+                    issues.push(format!("unknown tile '{}' on square {}", tile, index))
+                }
+                Square::Tile(Tile::Blank(None)) => {
+                    issues.push(format!("blank without a letter on square {}", index))
+                }
+                _ => (),
+            }
+        }
+
+        issues
     }
 
-    pub fn standard() -> Self {
-        let counts = vec![
-            (l!('A'), 9),
-            (l!('B'), 2),
-            (l!('C'), 2),
-            (l!('D'), 4),
-            (l!('E'), 12),
-            (l!('F'), 2),
-            (l!('G'), 3),
-            (l!('H'), 2),
-            (l!('I'), 9),
-            (l!('J'), 1),
-            (l!('K'), 1),
-            (l!('L'), 4),
-            (l!('M'), 2),
-            (l!('N'), 6),
-            (l!('O'), 8),
-            (l!('P'), 2),
-            (l!('Q'), 1),
-            (l!('R'), 6),
-            (l!('S'), 4),
-            (l!('T'), 6),
-            (l!('U'), 4),
-            (l!('V'), 2),
-            (l!('W'), 2),
-            (l!('X'), 1),
-            (l!('Y'), 2),
-            (l!('Z'), 1),
-            (l!(), 2),
-        ];
+    // Each tile whose count across the bag, racks and board differs from the game's distribution
+    fn tile_conservation_issues(&self) -> Vec<String> {
+        let mut issues = vec![];
+        let expected: HashMap<Tile, usize> = self.distribution().into_iter().collect();
+        let actual = self.tile_counts();
 
-        let mut inner = vec![];
+        let mut tiles: Vec<&Tile> = expected
+            .keys()
+            .chain(actual.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        tiles.sort_by_key(|tile| tile.to_string());
 
-        for (letter, count) in counts {
-            for _ in 0..count {
-                inner.push(letter);
+        for tile in tiles {
+            let expected = expected.get(tile).copied().unwrap_or(0);
+            let actual = actual.get(tile).copied().unwrap_or(0);
+
+            if expected != actual {
+                issues.push(format!(
+                    "expected {} of tile {}, found {}",
+                    expected, tile, actual
+                ));
             }
         }
 
-        let mut bag = Bag(inner);
-        bag.shuffle();
-        bag
+        issues
     }
 
-    fn len(&self) -> usize {
-        self.0.len()
+    /// Panics unless the bag, racks and board together hold exactly the game's distribution, i.e.
+    /// no tile has been duplicated or lost.
+    pub fn assert_tile_conservation(&self) {
+        let issues = self.tile_conservation_issues();
+        assert!(
+            issues.is_empty(),
+            "tiles were not conserved: {}",
+            issues.join("; ")
+        );
     }
 
-    fn push(&mut self, tile: Tile) {
-        self.0.push(tile);
+    // Whether a debug build should check conservation after a move. Games whose tiles were set
+    // up by hand (tests, debug hooks) don't match the distribution to begin with.
+    #[cfg(debug_assertions)]
+    fn tiles_conserved(&self) -> bool {
+        self.tile_conservation_issues().is_empty()
     }
 
-    fn shuffle(&mut self) {
-        self.0.shuffle(&mut thread_rng());
-    }
+    /// Applies the safe automatic fixes for problems reported by `validate_full`, returning a
+    /// description of each change made. Board state is never modified.
+    pub fn repair(&mut self) -> Vec<String> {
+        let mut changes = vec![];
 
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        for tile in self.bag.0.iter_mut().chain(self.racks.iter_mut().flatten()) {
+            if let Tile::Blank(Some(c)) = tile {
+                changes.push(format!("reset assigned blank ({}) off the board", c));
+                *tile = Tile::Blank(None);
+            }
+        }
+
+        while self.scores.len() < self.players.len() {
+            changes.push(format!("added missing score list {}", self.scores.len()));
+            self.scores.push(Default::default());
+        }
+
+        while self.racks.len() < self.players.len() {
+            let index = self.racks.len();
+            changes.push(format!("added missing rack {}", index));
+            self.racks.push(Rack::with_capacity(self.options.rack_size));
+
+            if self.state != State::Over {
+                self.fill_rack_at(index);
+            }
+        }
+
+        if !self.players.is_empty() && self.player_index >= self.players.len() {
+            changes.push(format!(
+                "reset out-of-range player_index {}",
+                self.player_index
+            ));
+            self.player_index %= self.players.len();
+        }
+
+        changes
     }
-}
 
-#[derive(Debug)]
-pub enum Error {
-    BoardParse(String),
-    NoTileToSpend(Tile),
-    TurnIndexesNotUnique,
-    TurnNotLinear,
-    NotStarted,
-    AlreadyStarted,
-    GameOver,
-    #[allow(dead_code)]
-    BlankTileInTurn,
-    CannotPass,
-    #[allow(dead_code)]
-    IndexOutOfBounds,
-    TileParse,
-    #[allow(dead_code)]
-    TurnParse,
-    SquareOccupied(usize),
-    NotConnected,
-    Sqlx(sqlx::Error),
-    IllegalWords(Vec<String>),
-    Unknown,
-    SwapNotAllowed,
-    NotYourTurn,
-    TriesExhausted,
-}
+    // Counts of every tile in the game (bag, racks and board), with played blanks counted as blanks
+    fn tile_counts(&self) -> HashMap<Tile, usize> {
+        let mut counts = HashMap::new();
+        let board_tiles = self.board.0.iter().filter_map(|square| square.tile());
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        for tile in self
+            .bag
+            .0
+            .iter()
+            .chain(self.racks.iter().flatten())
+            .chain(board_tiles)
+        {
+            let tile = match tile {
+                Tile::Blank(_) => Tile::Blank(None),
+                tile => *tile,
+            };
+
+            *counts.entry(tile).or_insert(0usize) += 1;
+        }
+
+        counts
     }
-}
 
-impl Board {
-    pub fn standard() -> Result<Self, Error> {
-        let board_string = "
-            3w .  .  2l .  .  .  3w .  .  .  2l .  .  3w 
-            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
-            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
-            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
-            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .  
-            .  3l .  .  .  3l .  .  .  3l .  .  .  3l .   
-            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
-            3w .  .  2l .  .  .  2w .  .  .  2l .  .  3w  
-            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
-            .  3l .  .  .  3l .  .  .  3l .  .  .  3l . 
-            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .   
-            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
-            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
-            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
-            3w .  .  2l .  .  .  3w .  .  .  2l .  .  3w
-        ";
+    /// A plain-text view of the game for terminals and bug reports: the board with column letters
+    /// and row numbers, the viewer's rack (if they're seated), each player's score and whose
+    /// turn it is.
+    pub fn to_ascii(&self, viewer: Option<usize>) -> String {
+        let columns: Vec<String> = (0..BOARD_SIZE)
+            .map(|col| format!("{:<2}", (b'A' + col as u8) as char))
+            .collect();
+        let mut ascii = format!("   {}\n", columns.join(" ").trim_end());
+
+        for (row, squares) in self.board.0.chunks(BOARD_SIZE).enumerate() {
+            let squares: Vec<String> = squares.iter().map(format_square).collect();
+            ascii.push_str(&format!(
+                "{:>2} {}\n",
+                row + 1,
+                squares.join(" ").trim_end()
+            ));
+        }
+
+        if let Some(rack) = viewer.and_then(|index| self.racks.get(index)) {
+            let tiles: Vec<String> = rack.iter().map(|t| position_char(t).to_string()).collect();
+            ascii.push_str(&format!("\nRack: {}\n", tiles.join(" ")));
+        }
+
+        ascii.push('\n');
+        for (index, player) in self.players.iter().enumerate() {
+            let to_move = if self.current_player() == Some(player.0.as_str()) {
+                " (to move)"
+            } else {
+                ""
+            };
+
+            ascii.push_str(&format!(
+                "{}: {}{}\n",
+                player,
+                self.player_stats(index).total,
+                to_move
+            ));
+        }
 
-        Self::parse(board_string)
+        ascii
     }
 
-    // FIXME: this doesn't parse a blank used as a letter, but maybe it doesn't need to
-    // (that condition would only be used in a test; for persistence this should be serialized structurally.)
-    pub fn parse(board_string: &str) -> Result<Self, Error> {
+    /// Reads a move in the usual notation, against the squares labelled by `to_ascii`: the
+    /// starting square, row first for a word played across (`8H QUIT`) or column first for one
+    /// played down (`H8 QUIT`). Letters already on the board are played through, and lowercase
+    /// letters are played with blanks.
+    pub fn parse_move(&self, notation: &str) -> Result<Turn, Error> {
+        let (square, word) = notation
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or(Error::TurnParse)?;
+        let (row, col, across) = parse_square(square).ok_or(Error::TurnParse)?;
         let mut tiles = vec![];
 
-        for token in board_string.split_whitespace() {
-            match token {
-                "." => tiles.push(Square::blank()),
-                "3w" => tiles.push(Square::word_bonus(3)),
-                "2w" => tiles.push(Square::word_bonus(2)),
-                "3l" => tiles.push(Square::letter_bonus(3)),
-                "2l" => tiles.push(Square::letter_bonus(2)),
-                ref c => {
-                    let parsed = char::from_str(c).map_err(|_| Error::BoardParse(c.to_string()))?;
-                    let square = Square::Tile(Tile::Char(parsed));
-                    tiles.push(square);
-                }
+        for (offset, c) in word.trim().chars().enumerate() {
+            let (row, col) = if across {
+                (row, col + offset)
+            } else {
+                (row + offset, col)
+            };
+            if row >= BOARD_SIZE || col >= BOARD_SIZE {
+                return Err(Error::IndexOutOfBounds);
+            }
+
+            let index = row * BOARD_SIZE + col;
+            let letter = parse_letter(c)?;
+            match self.board.get_char(index) {
+                Some(existing) if existing == letter => continue,
+                Some(_) => return Err(Error::SquareOccupied(index)),
+                None => (),
             }
+
+            let tile = if c.is_lowercase() {
+                Tile::Blank(Some(letter))
+            } else {
+                Tile::Char(letter)
+            };
+            tiles.push((index, tile));
         }
 
-        Ok(Self(tiles))
+        Ok(Turn { tiles })
     }
 
-    pub fn words(&self) -> impl Iterator<Item = Word> + '_ {
-        let horizontal = Words::horizontal(self);
-        let vertical = Words::vertical(self);
-        horizontal.chain(vertical)
-    }
+    /// A compact, shareable encoding of a position, in the spirit of chess FEN: the board's rows
+    /// separated by `/`, with letters for tiles (lowercase for blanks) and numbers for runs of
+    /// empty squares, then the index of the player to move, then each player's rack separated by
+    /// `/` (with `?` for a blank), e.g. `15/.../7MAR5/.../15 1 AEIRST?/QUEENLY`.
+    pub fn to_position_string(&self) -> String {
+        let rows: Vec<String> = self
+            .board
+            .0
+            .chunks(BOARD_SIZE)
+            .map(|row| {
+                let mut encoded = String::new();
+                let mut empty = 0;
+
+                for square in row {
+                    match square.tile() {
+                        Some(tile) => {
+                            if empty > 0 {
+                                encoded.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            encoded.push(position_char(tile));
+                        }
+                        None => empty += 1,
+                    }
+                }
 
-    // FIXME: check dictionary and return Result instead
-    fn new_words(&self, turn: &Turn) -> Vec<Word> {
-        let overlay = Overlay { board: self, turn };
-        overlay.new_words()
-    }
+                if empty > 0 {
+                    encoded.push_str(&empty.to_string());
+                }
 
-    fn get_square(&self, index: &usize) -> Option<&Square> {
-        self.0.get(*index)
-    }
+                encoded
+            })
+            .collect();
 
-    fn word_bonus(&self, indexes: &[usize]) -> isize {
-        let mut bonus = 1;
-        for index in indexes {
-            if let Some(Square::WordBonus(multiplier)) = self.get_square(index) {
-                bonus *= *multiplier
-            }
-        }
+        let racks: Vec<String> = self
+            .racks
+            .iter()
+            .map(|rack| rack.iter().map(position_char).collect())
+            .collect();
 
-        bonus
+        format!(
+            "{} {} {}",
+            rows.join("/"),
+            self.player_index,
+            racks.join("/")
+        )
     }
 
-    fn commit_turn(&mut self, turn: &Turn) -> Result<(), Error> {
-        // FIXME: ensure turn is valid, get scores
-        for (index, tile) in &turn.tiles {
-            let entry = &mut self.0[*index];
-            let mut new_value = Square::Tile(*tile);
-            std::mem::swap(entry, &mut new_value);
+    // Sets up a started game (e.g. a puzzle) from `to_position_string`'s encoding. Players are
+    // named by seat, and the bag holds whatever the position hasn't used of the standard tiles.
+    pub fn from_position_string(channel_id: ChannelId, position: &str) -> Result<Self, Error> {
+        let invalid = |message: &str| Error::PositionParse(message.to_string());
+        let mut parts = position.trim().splitn(3, ' ');
+        let (board, player_index, racks) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(board), Some(player_index), Some(racks)) => (board, player_index, racks),
+            _ => return Err(invalid("expected a board, player index and racks")),
+        };
+
+        let mut game = Self::new(channel_id);
+        game.bag = Bag(vec![]);
+
+        let rows: Vec<&str> = board.split('/').collect();
+        if rows.len() != BOARD_SIZE {
+            return Err(invalid("wrong number of rows"));
         }
 
-        Ok(())
-    }
+        for (row, encoded) in rows.into_iter().enumerate() {
+            let mut col = 0;
+            let mut empty: usize = 0;
+
+            for c in encoded.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    // a run of empty squares never goes past the end of the row
+                    empty = empty
+                        .checked_mul(10)
+                        .and_then(|empty| empty.checked_add(digit as usize))
+                        .filter(|empty| col + empty <= BOARD_SIZE)
+                        .ok_or_else(|| invalid("row is too long"))?;
+                    continue;
+                }
 
-    fn as_board_string(&self) -> String {
-        let mut result = String::new();
-        for (index, square) in self.0.iter().enumerate() {
-            result.push_str(&format_square(square));
-            if index % BOARD_SIZE == BOARD_SIZE - 1 {
-                result.push('\n');
+                col += std::mem::take(&mut empty);
+                if col >= BOARD_SIZE {
+                    return Err(invalid("row is too long"));
+                }
+
+                let tile = match c {
+                    c if c.is_uppercase() => Tile::Char(c),
+                    c if c.is_lowercase() => Tile::Blank(c.to_uppercase().next()),
+                    _ => return Err(invalid("unexpected character on the board")),
+                };
+                game.board.0[row * BOARD_SIZE + col] = Square::Tile(tile);
+                col += 1;
+            }
+
+            if col + empty != BOARD_SIZE {
+                return Err(invalid("row is the wrong length"));
             }
         }
 
-        result
-    }
+        for (index, encoded) in racks.split('/').enumerate() {
+            let rack = encoded
+                .chars()
+                .map(|c| match c {
+                    '?' => Ok(Tile::Blank(None)),
+                    c if c.is_uppercase() => Ok(Tile::Char(c)),
+                    _ => Err(invalid("unexpected character in a rack")),
+                })
+                .collect::<Result<Rack, Error>>()?;
 
-    fn get_tile(&self, index: &usize) -> Option<&Tile> {
-        match self.0.get(*index).expect("index out of bounds") {
-            Square::Blank => None,
-            Square::Tile(tile) => Some(tile),
-            Square::LetterBonus(_) => None,
-            Square::WordBonus(_) => None,
+            game.players.push(Player(format!("player {}", index + 1)));
+            game.racks.push(rack);
+            game.scores.push(Default::default());
+        }
+
+        game.player_index = player_index
+            .parse()
+            .ok()
+            .filter(|index| *index < game.players.len())
+            .ok_or_else(|| invalid("player index is out of range"))?;
+
+        let used = game.tile_counts();
+        let mut remaining = vec![];
+        for (tile, count) in game.distribution() {
+            let used = used.get(&tile).copied().unwrap_or(0);
+            remaining.push((
+                tile,
+                count.checked_sub(used).ok_or_else(|| {
+                    Error::PositionParse(format!("more than {} of tile {}", count, tile))
+                })?,
+            ));
         }
+
+        game.bag = Bag::with_distribution(remaining, &mut game.rng());
+        game.state = State::Started;
+
+        Ok(game)
     }
 }
 
-fn format_square(square: &Square) -> String {
-    match square {
-        Square::Blank => ".  ".to_string(),
-        Square::Tile(tile) => match tile {
-            Tile::Char(char) => format!("{}  ", char),
-            Tile::Blank(Some(char)) => format!(":{} ", char),
-            Tile::Blank(None) => ":: ".to_string(),
-        },
-        Square::LetterBonus(m) => format!("{}l ", m),
-        Square::WordBonus(m) => format!("{}w ", m),
+fn position_char(tile: &Tile) -> char {
+    match tile {
+        Tile::Char(c) => *c,
+        Tile::Blank(Some(c)) => c.to_lowercase().next().unwrap_or(*c),
+        Tile::Blank(None) => '?',
     }
 }
 
-fn score_tile(tile: &Tile) -> isize {
-    match tile {
-        Tile::Char(c) => score_char(c),
-        Tile::Blank(_) => 0,
+// `8H` (row, then column: across) or `H8` (column, then row: down) to a row, column and
+// direction, counting from 0
+fn parse_square(square: &str) -> Option<(usize, usize, bool)> {
+    let across = square.starts_with(|c: char| c.is_ascii_digit());
+    let (row, col) = if across {
+        square.split_at(square.find(|c: char| !c.is_ascii_digit())?)
+    } else {
+        let mut chars = square.chars();
+        let col = chars.next()?;
+        (chars.as_str(), &square[..col.len_utf8()])
+    };
+
+    let col = match col.as_bytes() {
+        [c] => c.to_ascii_uppercase().checked_sub(b'A')? as usize,
+        _ => return None,
+    };
+    let row: usize = row.parse().ok()?;
+
+    if col >= BOARD_SIZE || !(1..=BOARD_SIZE).contains(&row) {
+        return None;
     }
+
+    Some((row - 1, col, across))
 }
 
-fn score_char(char: &char) -> isize {
-    match char {
-        'A' => 1,
-        'B' => 3,
-        'C' => 3,
-        'D' => 2,
-        'E' => 1,
-        'F' => 4,
-        'G' => 2,
-        'H' => 4,
-        'I' => 1,
-        'J' => 8,
-        'K' => 5,
-        'L' => 1,
-        'M' => 3,
-        'N' => 1,
-        'O' => 1,
-        'P' => 3,
-        'Q' => 10,
-        'R' => 1,
-        'S' => 1,
-        'T' => 1,
-        'U' => 1,
-        'V' => 4,
-        'W' => 4,
-        'X' => 8,
-        'Y' => 4,
-        'Z' => 10,
-        _ => 0,
+// Quotes a CSV field if it holds a separator, quote or newline
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
 }
 
-pub trait GetChar {
-    fn get_char(&self, index: usize) -> Option<char>;
+impl From<&str> for Player {
+    fn from(name: &str) -> Self {
+        Player(name.to_owned())
+    }
 }
 
-struct Overlay<'a> {
-    board: &'a Board,
-    turn: &'a Turn,
+impl Player {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
 }
 
-impl Overlay<'_> {
-    fn new_words(&self) -> Vec<Word> {
-        let original: Vec<Word> = self.board.words().collect();
-        let horizontal = Words::horizontal(self);
-        let vertical = Words::vertical(self);
-        let mut overlay_words: Vec<Word> = horizontal.chain(vertical).collect();
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Board(Vec<Square>);
 
-        for word in original {
-            overlay_words.retain(|w| *w != word);
-        }
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Bag(Vec<Tile>);
 
-        overlay_words
+#[derive(Hash, Serialize, Deserialize, Eq, PartialEq, Copy, Clone)]
+pub enum Tile {
+    Char(char),
+    Blank(Option<char>),
+}
+
+pub static MAX_GAME_ID_LENGTH: usize = 64;
+pub static MAX_PLAYERS: usize = 4;
+pub static RACK_SIZE: usize = 7;
+// seconds
+static MAX_CHALLENGE_WINDOW: u64 = 600;
+// the width and height of a square in `Board::to_svg`, in pixels
+static SVG_CELL_SIZE: usize = 40;
+
+// Game ids end up in urls, channel ids and the games table, so they're limited to a short run of
+// ascii letters, digits, `-` and `_`.
+pub fn valid_game_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_GAME_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Game {
+    // A missing row starts a new game; any other failure is returned so a corrupt game isn't
+    // silently replaced by a fresh one.
+    pub async fn fetch(
+        channel_id: ChannelId,
+        seed: Option<u64>,
+        db: &PgPool,
+    ) -> Result<Self, Error> {
+        warn!("fetching {:?}", channel_id);
+        match persistence::fetch_recent(channel_id.value().unwrap(), db).await {
+            Ok(game) => Ok(game),
+            Err(Error::Sqlx(sqlx::Error::RowNotFound)) => Ok(Game::with_seed(channel_id, seed)),
+            Err(e) => {
+                error!("could not load game {:?}; e={:?}", channel_id, e);
+                Err(e)
+            }
+        }
     }
 
-    // FIXME: blank gets 0
-    fn score_tile(&self, tile: &Tile, index: &usize) -> isize {
-        score_tile(tile) * self.letter_bonus(index)
+    pub fn new(channel_id: ChannelId) -> Self {
+        Self::with_seed(channel_id, None)
     }
 
-    fn get_tile(&self, index: &usize) -> Option<&Tile> {
-        self.turn
-            .get_tile(index)
-            .or_else(|| self.board.get_tile(index))
+    pub fn with_seed(channel_id: ChannelId, seed: Option<u64>) -> Self {
+        let mut game = Game {
+            board: Board::standard().expect("standard board could not be built"),
+            players: Default::default(),
+            player_index: 0,
+            bag: Bag(vec![]),
+            racks: Default::default(),
+            scores: Default::default(),
+            state: Default::default(),
+            size: BOARD_SIZE,
+            board_type: BOARD_TYPE.to_string(),
+            pkid: None,
+            name: channel_id.value().unwrap().to_string(),
+            pass_count: 0,
+            illegal_try_count: 0,
+            turn_log: Default::default(),
+            options: Default::default(),
+            seed,
+            history: Default::default(),
+            tile_set: None,
+            forfeited: None,
+            redrawn: Default::default(),
+            bots: Default::default(),
+            revealed: Default::default(),
+            move_count: 0,
+            pending: None,
+        };
+
+        game.bag = Bag::standard_with_rng(&mut game.rng());
+        game
     }
 
-    fn score_word(&self, word: &Word) -> isize {
-        let word_bonus = self.word_bonus(&word.indexes);
+    pub fn with_tile_set(
+        channel_id: ChannelId,
+        seed: Option<u64>,
+        tile_set: TileSet,
+        options: GameOptions,
+    ) -> Result<Self, Error> {
+        tile_set.validate()?;
+
+        let mut game = Self::with_options(channel_id, seed, options)?;
+        game.bag = Bag::with_distribution(tile_set.tiles(), &mut game.rng());
+        game.tile_set = Some(tile_set);
+        Ok(game)
+    }
 
-        let mut score = 0;
+    /// A game drawing from `bag` rather than a shuffled standard bag, e.g. `Bag::from_ordered`
+    /// for a scripted game.
+    #[cfg(any(test, feature = "debug-hooks"))]
+    pub fn with_bag(channel_id: ChannelId, seed: Option<u64>, bag: Bag) -> Self {
+        let mut game = Self::with_seed(channel_id, seed);
+        game.bag = bag;
+        game
+    }
 
-        for index in &word.indexes {
-            let tile = self
-                .get_tile(index)
-                .expect("tile unexpectedly missing from word");
-            score += self.score_tile(tile, index);
-        }
+    pub fn with_options(
+        channel_id: ChannelId,
+        seed: Option<u64>,
+        options: GameOptions,
+    ) -> Result<Self, Error> {
+        options.validate()?;
 
-        score * word_bonus
+        let mut game = Self::with_seed(channel_id, seed);
+        game.options = options;
+        Ok(game)
     }
 
-    fn word_bonus(&self, indexes: &[usize]) -> isize {
-        self.board.word_bonus(indexes)
+    pub fn options(&self) -> &GameOptions {
+        &self.options
     }
 
-    fn letter_bonus(&self, index: &usize) -> isize {
-        match self.board.get_square(index) {
-            Some(Square::LetterBonus(multiplier)) => *multiplier,
-            _ => 1,
+    fn distribution(&self) -> Vec<(Tile, usize)> {
+        match &self.tile_set {
+            Some(tile_set) => tile_set.tiles(),
+            None => standard_distribution(),
         }
     }
+}
 
-    pub fn score(&self) -> TurnScore {
-        let mut scores = vec![];
-        for word in self.new_words() {
-            scores.push((String::from(&word), self.score_word(&word)))
-        }
+pub static BOARD_SIZE: usize = 15;
+pub static BOARD_TYPE: &str = "standard";
+pub static BOARD_CENTER: usize = 112;
+static INDEX_OVERFLOW: usize = 15 * 15;
 
-        if self.turn.is_bingo() {
-            scores.push((String::from("*"), 50));
-        }
+// (kind, multiplier) => count, including the center double word
+static STANDARD_PREMIUM_SQUARES: [((char, isize), usize); 4] = [
+    (('w', 3), 8),
+    (('w', 2), 17),
+    (('l', 3), 12),
+    (('l', 2), 24),
+];
 
-        TurnScore { scores }
+impl std::fmt::Debug for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tile::Char(char) => write!(f, "[{}]", char),
+            Tile::Blank(None) => write!(f, "[ ]"),
+            Tile::Blank(Some(char)) => write!(f, "[({})]", char),
+        }
     }
+}
 
-    async fn validate_words(&self) -> Result<(), Error> {
-        let illegal_words = crate::dictionary::illegal_words(
-            self.new_words().into_iter().map(String::from).collect(),
-        )
-        .await;
-
-        if illegal_words.is_empty() {
-            Ok(())
-        } else {
-            Err(Error::IllegalWords(illegal_words))
+impl Tile {
+    pub fn as_char(&self) -> Option<char> {
+        match *self {
+            Tile::Char(char) => Some(char),
+            Tile::Blank(Some(char)) => Some(char),
+            _ => None,
         }
     }
 }
 
-impl GetChar for Board {
-    fn get_char(&self, index: usize) -> Option<char> {
-        self.0.get(index).and_then(|square| square.get_char())
+impl std::fmt::Display for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Tile::Char(char) | Tile::Blank(Some(char)) => write!(f, "{}", char),
+            Tile::Blank(None) => write!(f, "{}", BLANK),
+        }
     }
 }
 
-impl GetChar for Overlay<'_> {
-    fn get_char(&self, index: usize) -> Option<char> {
-        self.board
-            .get_char(index)
-            .or_else(|| self.turn.get_char(index))
-    }
+macro_rules! l {
+    () => {
+        Tile::Blank(None)
+    };
+
+    ($c:expr) => {
+        Tile::Char($c)
+    };
 }
 
-impl GetChar for Turn {
-    fn get_char(&self, index: usize) -> Option<char> {
-        self.tiles
-            .iter()
-            .filter(|(i, _)| *i == index)
-            .map(|(_, tile)| tile.as_char())
-            .next()
-            .unwrap_or(None)
-    }
+macro_rules! lb {
+    () => {
+        Tile::Blank(None)
+    };
+
+    ($c:expr) => {
+        Tile::Blank(Some($c))
+    };
 }
 
-impl From<Word> for String {
-    fn from(word: Word) -> Self {
-        word.string
-    }
+fn standard_distribution() -> Vec<(Tile, usize)> {
+    vec![
+        (l!('A'), 9),
+        (l!('B'), 2),
+        (l!('C'), 2),
+        (l!('D'), 4),
+        (l!('E'), 12),
+        (l!('F'), 2),
+        (l!('G'), 3),
+        (l!('H'), 2),
+        (l!('I'), 9),
+        (l!('J'), 1),
+        (l!('K'), 1),
+        (l!('L'), 4),
+        (l!('M'), 2),
+        (l!('N'), 6),
+        (l!('O'), 8),
+        (l!('P'), 2),
+        (l!('Q'), 1),
+        (l!('R'), 6),
+        (l!('S'), 4),
+        (l!('T'), 6),
+        (l!('U'), 4),
+        (l!('V'), 2),
+        (l!('W'), 2),
+        (l!('X'), 1),
+        (l!('Y'), 2),
+        (l!('Z'), 1),
+        (l!(), 2),
+    ]
 }
 
-impl From<&Word> for String {
-    fn from(word: &Word) -> Self {
-        word.string.clone()
+impl Bag {
+    pub fn pop(&mut self) -> Option<Tile> {
+        self.0.pop()
     }
-}
 
-#[derive(Debug)]
-enum Direction {
-    Horizontal,
-    Vertical,
-}
+    /// A bag drawn from in the order given (first tile first), without shuffling
+    #[cfg(any(test, feature = "debug-hooks"))]
+    pub fn from_ordered(mut tiles: Vec<Tile>) -> Self {
+        tiles.reverse();
+        Bag(tiles)
+    }
 
-pub struct Words<'a, S> {
-    cursor: usize,
-    index: usize,
-    direction: Direction,
-    source: &'a S,
-}
+    pub fn standard() -> Self {
+        Self::standard_with_rng(&mut thread_rng())
+    }
 
-impl<S: GetChar> Words<'_, S> {
-    fn horizontal(source: &S) -> Words<'_, S> {
-        Words {
-            cursor: 0,
-            index: 0,
-            direction: Direction::Horizontal,
-            source,
-        }
+    pub fn standard_with_rng<R: Rng>(rng: &mut R) -> Self {
+        Self::with_distribution(standard_distribution(), rng)
     }
 
-    fn vertical(source: &S) -> Words<'_, S> {
-        Words {
-            cursor: 0,
-            index: 0,
-            direction: Direction::Vertical,
-            source,
+    fn with_distribution<R: Rng>(distribution: Vec<(Tile, usize)>, rng: &mut R) -> Self {
+        let mut inner = vec![];
+
+        for (letter, count) in distribution {
+            for _ in 0..count {
+                inner.push(letter);
+            }
         }
+
+        let mut bag = Bag(inner);
+        bag.shuffle(rng);
+        bag
     }
 
-    fn advance(&mut self) {
-        self.cursor += 1;
-        self.index = transpose_index(self.cursor, &self.direction);
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn push(&mut self, tile: Tile) {
+        self.0.push(tile);
+    }
+
+    fn shuffle<R: Rng>(&mut self, rng: &mut R) {
+        self.0.shuffle(rng);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
-impl<S: GetChar> Iterator for Words<'_, S> {
-    type Item = Word;
+#[derive(Debug)]
+pub enum Error {
+    // an unknown token, at its position on the board (counting from 0)
+    BoardParse {
+        token: String,
+        row: usize,
+        col: usize,
+    },
+    BoardWrongSize {
+        expected: usize,
+        got: usize,
+    },
+    InvalidBoard(String),
+    NoTileToSpend(Tile),
+    TurnIndexesNotUnique,
+    TurnNotLinear,
+    // the tiles of a turn don't form one unbroken run
+    TurnHasGaps,
+    EmptyTurn,
+    NotStarted,
+    AlreadyStarted,
+    GameOver,
+    GameNotOver,
+    BlankTileInTurn,
+    CannotRedraw,
+    // why a seat can't be handed over to the bot
+    CannotReplace(String),
+    IndexOutOfBounds,
+    TileParse,
+    TurnParse,
+    // the number of tiles in a turn payload that can't possibly be legal
+    TurnTooLarge(usize),
+    // more tiles in a turn than a rack holds (the count, and the rack size)
+    TooManyTiles(usize, usize),
+    SquareOccupied(usize),
+    NotConnected,
+    Sqlx(sqlx::Error),
+    Deserialize(serde_json::Error),
+    IllegalWords(Vec<String>),
+    Unknown,
+    // bag count at the time of the attempt
+    CannotSwap(usize),
+    InvalidTileSet(String),
+    PositionParse(String),
+    NotYourTurn,
+    // a move made against an earlier move count (e.g. redelivered by the client)
+    OutOfOrderTurn,
+    TriesExhausted,
+    // every seat (`GameOptions::max_players`) is taken
+    GameFull,
+    InvalidOptions(String),
+    // an event (by name) that can't be handled in the game's current state
+    EventNotAllowed(&'static str, State),
+    // a spectator attempting a player's action
+    NotSeated,
+    // a player attempting a spectator's action
+    NotSpectating,
+    // a saved game that loads, but can't be played (see `Game::validate_invariants`)
+    CorruptGame(String),
+    // no play open to challenge (or only the challenger's own)
+    NothingToChallenge,
+    ChallengeTooLate,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut current = Word::new();
+impl Error {
+    /// Stable, machine-readable name of the error for clients (unlike the message, this won't
+    /// change wording).
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Error::BoardParse { .. } => "board_parse",
+            Error::BoardWrongSize { .. } => "board_wrong_size",
+            Error::InvalidBoard(_) => "invalid_board",
+            Error::NoTileToSpend(_) => "no_tile_to_spend",
+            Error::TurnIndexesNotUnique => "turn_indexes_not_unique",
+            Error::TurnNotLinear => "turn_not_linear",
+            Error::TurnHasGaps => "turn_has_gaps",
+            Error::EmptyTurn => "empty_turn",
+            Error::NotStarted => "not_started",
+            Error::AlreadyStarted => "already_started",
+            Error::GameOver => "game_over",
+            Error::GameNotOver => "game_not_over",
+            Error::BlankTileInTurn => "blank_tile_in_turn",
+            Error::CannotRedraw => "cannot_redraw",
+            Error::CannotReplace(_) => "cannot_replace",
+            Error::IndexOutOfBounds => "index_out_of_bounds",
+            Error::TileParse => "tile_parse",
+            Error::TurnParse => "turn_parse",
+            Error::TurnTooLarge(_) => "turn_too_large",
+            Error::TooManyTiles(..) => "too_many_tiles",
+            Error::SquareOccupied(_) => "square_occupied",
+            Error::NotConnected => "not_connected",
+            Error::Sqlx(_) => "database",
+            Error::Deserialize(_) => "deserialize",
+            Error::IllegalWords(_) => "illegal_words",
+            Error::Unknown => "unknown",
+            Error::CannotSwap(_) => "cannot_swap",
+            Error::InvalidTileSet(_) => "invalid_tile_set",
+            Error::PositionParse(_) => "position_parse",
+            Error::NotYourTurn => "not_your_turn",
+            Error::OutOfOrderTurn => "out_of_order_turn",
+            Error::TriesExhausted => "tries_exhausted",
+            Error::GameFull => "game_full",
+            Error::InvalidOptions(_) => "invalid_options",
+            Error::EventNotAllowed(..) => "event_not_allowed",
+            Error::NotSeated => "not_seated",
+            Error::NotSpectating => "not_spectating",
+            Error::CorruptGame(_) => "corrupt_game",
+            Error::NothingToChallenge => "nothing_to_challenge",
+            Error::ChallengeTooLate => "challenge_too_late",
+        }
+    }
 
-        // advance to next non-empty square
-        loop {
-            while self.source.get_char(self.index).is_none() {
-                self.advance();
+    /// The payload of an error pushed to a client: the code and message, plus details for errors
+    /// that have them.
+    pub fn payload(&self) -> serde_json::Value {
+        let mut payload = json!({
+            "code": self.error_code(),
+            "message": self.to_string(),
+        });
 
-                if self.cursor >= INDEX_OVERFLOW {
-                    return None;
-                }
+        match self {
+            Error::IllegalWords(words) => payload["words"] = json!(words),
+            Error::SquareOccupied(index) => payload["index"] = json!(index),
+            // the client's rack is stale; it should refresh before trying again
+            Error::NoTileToSpend(tile) => {
+                payload["tile"] = json!(tile.to_string());
+                payload["refresh"] = json!(true);
             }
+            _ => (),
+        }
 
-            while let Some(char) = self.source.get_char(self.index) {
-                current.push(self.index, char);
-                self.advance();
+        payload
+    }
+}
 
-                // end of row
-                if self.cursor % BOARD_SIZE == 0 {
-                    break;
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::EmptyTurn => write!(f, "No tiles were placed (pass instead?)"),
+            Error::NotYourTurn => write!(f, "It's not your turn"),
+            Error::NotStarted => write!(f, "This game hasn't started yet"),
+            Error::AlreadyStarted => write!(f, "This game has already started"),
+            Error::GameOver => write!(f, "This game is over"),
+            Error::NotSeated => write!(f, "You are not seated in this game"),
+            Error::NotSpectating => write!(f, "Only spectators can follow a player"),
+            Error::TileParse => write!(f, "Tiles must be letters of this game's alphabet"),
+            Error::TurnHasGaps => write!(f, "The tiles played must form a single word"),
+            Error::CannotRedraw => write!(
+                f,
+                "Your opening rack can only be redrawn once, before your first move"
+            ),
+            Error::CannotReplace(reason) => {
+                write!(f, "This seat can't be handed over to the bot: {}", reason)
+            }
+            Error::TooManyTiles(count, rack_size) => write!(
+                f,
+                "A turn can't place {} tiles (a rack holds {})",
+                count, rack_size
+            ),
+            Error::TurnTooLarge(count) => write!(
+                f,
+                "A turn can't place {} tiles (at most {})",
+                count, BOARD_SIZE
+            ),
+            Error::NoTileToSpend(tile) => {
+                write!(f, "Your rack has no {} tile; it has been refreshed", tile)
+            }
+            Error::BoardParse { token, row, col } => write!(
+                f,
+                "Unknown square {:?} at row {}, column {}",
+                token, row, col
+            ),
+            Error::BoardWrongSize { expected, got } => {
+                write!(f, "Board has {} squares; expected {}", got, expected)
+            }
+            Error::IllegalWords(words) => match words.as_slice() {
+                [] => write!(f, "Not in the dictionary"),
+                [word] => write!(f, "{} is not a valid word", word),
+                [rest @ .., last] => {
+                    write!(f, "{} and {} are not valid words", rest.join(", "), last)
                 }
+            },
+            Error::OutOfOrderTurn => {
+                write!(
+                    f,
+                    "This move was already received, or the game has moved on since"
+                )
             }
-
-            if current.len() > 1 {
-                return Some(current.clone());
-            } else {
-                current.clear();
+            Error::EventNotAllowed(event, state) => write!(f, "Can't {} {}", event, state),
+            Error::InvalidTileSet(message) => write!(f, "Invalid tile set: {}", message),
+            Error::InvalidOptions(message) => write!(f, "Invalid game options: {}", message),
+            Error::CorruptGame(message) => write!(f, "This game can't be loaded: {}", message),
+            Error::NothingToChallenge => write!(f, "There's no play to challenge"),
+            Error::ChallengeTooLate => {
+                write!(
+                    f,
+                    "Too late; the play was final once its challenge window closed"
+                )
             }
+            Error::CannotSwap(count) => write!(
+                f,
+                "Tiles can't be swapped with {} left in the bag (at least 7 are needed)",
+                count
+            ),
+            _ => write!(f, "{:?}", self),
         }
     }
 }
 
-// Word uniqueness is based on the indexes played, not the word itself (allow for duplicates)
-#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Word {
-    indexes: Vec<usize>,
-    string: String,
-}
+impl Board {
+    pub fn standard() -> Result<Self, Error> {
+        let board_string = "
+            3w .  .  2l .  .  .  3w .  .  .  2l .  .  3w 
+            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
+            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
+            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
+            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .  
+            .  3l .  .  .  3l .  .  .  3l .  .  .  3l .   
+            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
+            3w .  .  2l .  .  .  2w .  .  .  2l .  .  3w  
+            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
+            .  3l .  .  .  3l .  .  .  3l .  .  .  3l . 
+            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .   
+            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
+            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
+            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
+            3w .  .  2l .  .  .  3w .  .  .  2l .  .  3w
+        ";
 
-impl Word {
-    pub fn new() -> Self {
-        Default::default()
+        let board = Self::parse(board_string)?;
+        board.validate_premium_squares(&STANDARD_PREMIUM_SQUARES)?;
+        Ok(board)
     }
 
-    pub fn push(&mut self, index: usize, char: char) {
-        self.indexes.push(index);
-        self.string.push(char);
-    }
+    /// Number of premium squares of each kind, keyed by ('w' | 'l', multiplier)
+    pub fn premium_square_counts(&self) -> HashMap<(char, isize), usize> {
+        let mut counts = HashMap::new();
 
-    pub fn clear(&mut self) {
-        self.indexes.clear();
-        self.string.clear();
-    }
+        for square in &self.0 {
+            let key = match square {
+                Square::WordBonus(multiplier) => ('w', *multiplier),
+                Square::LetterBonus(multiplier) => ('l', *multiplier),
+                _ => continue,
+            };
 
-    pub fn len(&self) -> usize {
-        self.indexes.len()
-    }
+            *counts.entry(key).or_insert(0) += 1;
+        }
 
-    pub fn char_indicies(&self) -> impl Iterator<Item = (char, &usize)> {
-        self.string.chars().zip(self.indexes.iter())
+        counts
     }
-}
 
-fn transpose_index(index: usize, direction: &Direction) -> usize {
-    match direction {
-        Direction::Vertical => (index / BOARD_SIZE) + (index % BOARD_SIZE * BOARD_SIZE),
-        Direction::Horizontal => index,
+    /// Empty squares bordering at least one placed tile, where any new word has to touch down;
+    /// on an empty board, just the center square.
+    pub fn anchors(&self) -> Vec<usize> {
+        if self.0.iter().all(|square| square.tile().is_none()) {
+            return vec![BOARD_CENTER];
+        }
+
+        (0..INDEX_OVERFLOW)
+            .filter(|index| self.get_tile(index).is_none())
+            .filter(|index| {
+                Self::connected_indexes(*index).any(|connected| self.get_tile(&connected).is_some())
+            })
+            .collect()
     }
-}
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Turn {
-    tiles: Vec<(usize, Tile)>,
-    // map of indexes to letters
-    // player
-    // validations:
-    // - player has the letters played
-    // - new words are all valid
-    // - new words all touch the existing words
-    // - single direction of play
+    // the squares orthogonally adjacent to `index`
+    pub(crate) fn connected_indexes(index: usize) -> impl Iterator<Item = usize> {
+        let original_row = index / BOARD_SIZE;
 
-    // score!
-}
+        // prev in row; can't break out of original row
+        index
+            .checked_sub(1)
+            .filter(|x| x / BOARD_SIZE == original_row)
+            .into_iter()
+            .chain(
+                // next in row, can't break out of original row
+                index
+                    .checked_add(1)
+                    .filter(|x| x / BOARD_SIZE == original_row)
+                    .into_iter(),
+            )
+            .chain(
+                // col can't be less than 0
+                index.checked_sub(BOARD_SIZE).into_iter(),
+            )
+            .chain(
+                // can't be greater than board size
+                index
+                    .checked_add(BOARD_SIZE)
+                    .filter(|x| *x < INDEX_OVERFLOW)
+                    .into_iter(),
+            )
+    }
 
-#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
-pub struct TurnScore {
-    scores: Vec<(String, isize)>,
-}
+    fn validate_premium_squares(&self, expected: &[((char, isize), usize)]) -> Result<(), Error> {
+        let counts = self.premium_square_counts();
+        let expected: HashMap<(char, isize), usize> = expected.iter().copied().collect();
 
-impl Turn {
-    fn indexes(&self) -> impl Iterator<Item = &usize> {
-        self.tiles.iter().map(|(i, _)| i)
+        if counts == expected {
+            Ok(())
+        } else {
+            Err(Error::InvalidBoard(format!(
+                "premium squares {:?} do not match expected {:?}",
+                counts, expected
+            )))
+        }
     }
 
-    pub fn len(&self) -> usize {
-        self.tiles.len()
+    // A blank used as a letter is written `:m` (as `format_square` does) or `(m)`. For
+    // persistence the board is serialized structurally; this format is for tests and debugging.
+    pub fn parse(board_string: &str) -> Result<Self, Error> {
+        let mut tiles = vec![];
+
+        for (index, token) in board_string.split_whitespace().enumerate() {
+            match token {
+                "." => tiles.push(Square::blank()),
+                "::" => tiles.push(Square::Tile(Tile::Blank(None))),
+                c if parse_bonus(c, 'w').is_some() => {
+                    tiles.push(Square::word_bonus(parse_bonus(c, 'w').unwrap()))
+                }
+                c if parse_bonus(c, 'l').is_some() => {
+                    tiles.push(Square::letter_bonus(parse_bonus(c, 'l').unwrap()))
+                }
+                ref c if c.starts_with(':') || (c.starts_with('(') && c.ends_with(')')) => {
+                    let letter = c
+                        .trim_start_matches(':')
+                        .trim_matches(|c| c == '(' || c == ')');
+                    let parsed = char::from_str(letter).map_err(|_| Error::BoardParse {
+                        token: c.to_string(),
+                        row: index / BOARD_SIZE,
+                        col: index % BOARD_SIZE,
+                    })?;
+                    tiles.push(Square::Tile(Tile::Blank(Some(parsed.to_ascii_uppercase()))));
+                }
+                ref c => {
+                    let parsed = char::from_str(c).map_err(|_| Error::BoardParse {
+                        token: c.to_string(),
+                        row: index / BOARD_SIZE,
+                        col: index % BOARD_SIZE,
+                    })?;
+                    let square = Square::Tile(Tile::Char(parsed));
+                    tiles.push(square);
+                }
+            }
+        }
+
+        if tiles.len() != BOARD_SIZE * BOARD_SIZE {
+            return Err(Error::BoardWrongSize {
+                expected: BOARD_SIZE * BOARD_SIZE,
+                got: tiles.len(),
+            });
+        }
+
+        Ok(Self(tiles))
     }
 
-    fn is_bingo(&self) -> bool {
-        self.tiles.len() >= 7
+    // every word on the board; new words are found from the squares played instead
+    #[cfg(test)]
+    pub fn words(&self) -> impl Iterator<Item = Word> + '_ {
+        let horizontal = Words::horizontal(self);
+        let vertical = Words::vertical(self);
+        horizontal.chain(vertical)
     }
 
-    // FIXME: validate words in dictionary
-    fn validate(&self) -> Result<(), Error> {
-        self.validate_unique_indexes()?;
-        self.validate_linear()?;
+    // FIXME: check dictionary and return Result instead
+    fn new_words(&self, turn: &Turn) -> Vec<Word> {
+        let overlay = Overlay { board: self, turn };
+        overlay.new_words()
+    }
+
+    fn get_square(&self, index: &usize) -> Option<&Square> {
+        self.0.get(*index)
+    }
+
+    fn word_bonus(&self, indexes: &[usize]) -> isize {
+        let mut bonus = 1;
+        for index in indexes {
+            if let Some(Square::WordBonus(multiplier)) = self.get_square(index) {
+                bonus *= *multiplier
+            }
+        }
+
+        bonus
+    }
+
+    fn commit_turn(&mut self, turn: &Turn) -> Result<(), Error> {
+        // FIXME: ensure turn is valid, get scores
+        for (index, tile) in &turn.tiles {
+            let entry = &mut self.0[*index];
+            let mut new_value = Square::Tile(*tile);
+            std::mem::swap(entry, &mut new_value);
+        }
 
         Ok(())
     }
 
-    fn validate_unique_indexes(&self) -> Result<(), Error> {
-        // all indexes should be unique
-        if self.indexes().count() == self.indexes().collect::<HashSet<&usize>>().len() {
-            Ok(())
-        } else {
-            Err(Error::TurnIndexesNotUnique)
+    /// The board as rows of plain cells for the client, e.g. `{"type": "tile", "letter": "A",
+    /// "blank": false, "value": 1}` or `{"type": "bonus", "kind": "2w"}`. Storage is unaffected.
+    pub fn to_grid_json(&self, tile_set: Option<&TileSet>) -> serde_json::Value {
+        let cell = |square: &Square| match square {
+            Square::Blank => json!({ "type": "empty" }),
+            Square::Tile(tile) => json!({
+                "type": "tile",
+                "letter": tile.as_char(),
+                "blank": matches!(tile, Tile::Blank(_)),
+                "value": tile_value(tile_set, tile),
+            }),
+            Square::LetterBonus(_) | Square::WordBonus(_) => json!({
+                "type": "bonus",
+                "kind": square_token(square),
+            }),
+        };
+
+        self.0
+            .chunks(BOARD_SIZE)
+            .map(|row| row.iter().map(cell).collect::<serde_json::Value>())
+            .collect()
+    }
+
+    /// The board as an SVG image: a cell per square, colored by bonus, with the letter and value
+    /// of each placed tile. Blanks are drawn in another color, without a value.
+    pub fn to_svg(&self, tile_set: Option<&TileSet>) -> String {
+        let cell = SVG_CELL_SIZE;
+        let size = BOARD_SIZE * cell;
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {0} {0}" font-family="sans-serif">"#,
+            size
+        );
+
+        for (index, square) in self.0.iter().enumerate() {
+            let x = (index % BOARD_SIZE) * cell;
+            let y = (index / BOARD_SIZE) * cell;
+            let fill = match square {
+                Square::Blank => "#d8d2c0",
+                Square::Tile(_) => "#f3d9a4",
+                Square::LetterBonus(2) => "#a9d5ee",
+                Square::LetterBonus(_) => "#3f8fcc",
+                Square::WordBonus(2) => "#f2b5b0",
+                Square::WordBonus(_) => "#d9534f",
+            };
+
+            svg.push_str(&format!(
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="#ffffff"/>"##,
+                x, y, cell, cell, fill
+            ));
+
+            let label = match square {
+                Square::Tile(tile @ Tile::Char(c)) => {
+                    Some((c, "#222222", Some(tile_value(tile_set, tile))))
+                }
+                Square::Tile(Tile::Blank(Some(c))) => Some((c, "#9a5c00", None)),
+                _ => None,
+            };
+
+            if let Some((letter, color, value)) = label {
+                svg.push_str(&format!(
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="middle" fill="{}">{}</text>"#,
+                    x + cell / 2,
+                    y + cell * 7 / 10,
+                    cell / 2,
+                    color,
+                    letter
+                ));
+
+                if let Some(value) = value {
+                    svg.push_str(&format!(
+                        r#"<text x="{}" y="{}" font-size="{}" text-anchor="end">{}</text>"#,
+                        x + cell - 3,
+                        y + cell - 3,
+                        cell / 4,
+                        value
+                    ));
+                }
+            }
         }
+
+        svg.push_str("</svg>");
+        svg
     }
 
-    fn validate_linear(&self) -> Result<(), Error> {
-        if self
-            .indexes()
-            .map(|i| i % BOARD_SIZE)
-            .collect::<HashSet<usize>>()
-            .len()
-            == 1
-            || self
-                .indexes()
-                .map(|i| i / BOARD_SIZE)
-                .collect::<HashSet<usize>>()
-                .len()
-                == 1
-        {
-            Ok(())
-        } else {
-            Err(Error::TurnNotLinear)
+    // One row per line, every square padded to the same width; `parse` reads it back unchanged.
+    fn as_board_string(&self) -> String {
+        let mut result = String::new();
+        for row in self.0.chunks(BOARD_SIZE) {
+            let squares: Vec<String> = row.iter().map(format_square).collect();
+            result.push_str(&squares.join(" "));
+            result.push('\n');
         }
+
+        result
     }
 
     fn get_tile(&self, index: &usize) -> Option<&Tile> {
-        self.tiles
-            .iter()
-            .filter(|(i, _)| i == index)
-            .map(|(_, tile)| tile)
-            .next()
+        match self.0.get(*index).expect("index out of bounds") {
+            Square::Blank => None,
+            Square::Tile(tile) => Some(tile),
+            Square::LetterBonus(_) => None,
+            Square::WordBonus(_) => None,
+        }
     }
 }
 
-impl TryFrom<serde_json::Value> for Turn {
-    type Error = Error;
+fn format_square(square: &Square) -> String {
+    format!("{:<2}", square_token(square))
+}
 
-    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
-        match value {
-            serde_json::Value::Object(map) => Ok(Turn {
-                tiles: map
-                    .iter()
-                    .map(|(string, char)| {
-                        (
-                            // FIXME: this sometimes panics on join (???) with Invalid Digit
-                            string.parse().unwrap(),
-                            char.as_str().unwrap().parse().unwrap(),
-                        )
-                    })
-                    .collect::<Vec<(usize, Tile)>>(),
-            }),
-            _ => panic!("fixme"),
+fn square_token(square: &Square) -> String {
+    match square {
+        Square::Blank => ".".to_string(),
+        Square::Tile(tile) => match tile {
+            Tile::Char(char) => char.to_string(),
+            Tile::Blank(Some(char)) => format!(":{}", char),
+            Tile::Blank(None) => "::".to_string(),
+        },
+        Square::LetterBonus(m) => format!("{}l", m),
+        Square::WordBonus(m) => format!("{}w", m),
+    }
+}
+
+// The board as saved with the game: its squares' tokens (as in `Board::parse`) separated by
+// spaces, a fraction of the size of the tagged JSON the API sends. Games saved before this
+// stored that JSON array, so either form is read.
+mod stored_board {
+    use super::{square_token, Board, Square};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Compact(String),
+        Legacy(Vec<Square>),
+    }
+
+    pub fn serialize<S: Serializer>(board: &Board, serializer: S) -> Result<S::Ok, S::Error> {
+        let tokens: Vec<String> = board.0.iter().map(square_token).collect();
+        serializer.serialize_str(&tokens.join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        match Stored::deserialize(deserializer)? {
+            Stored::Compact(tokens) => Board::parse(&tokens).map_err(serde::de::Error::custom),
+            Stored::Legacy(squares) => Ok(Board(squares)),
         }
     }
 }
 
-impl FromStr for Tile {
-    type Err = Error;
+// `3w` -> Some(3) for suffix 'w'; any other token -> None
+fn parse_bonus(token: &str, suffix: char) -> Option<isize> {
+    let digits = token.strip_suffix(suffix)?;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.chars().count() {
-            2 => {
-                let mut chars = s.chars();
-                let c1 = chars.next().unwrap();
-                let c2 = chars.next().unwrap();
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
 
-                if c1 != ':' {
-                    return Err(Error::TileParse);
-                }
+fn score_tile(tile: &Tile) -> isize {
+    match tile {
+        Tile::Char(c) => score_char(c),
+        Tile::Blank(_) => 0,
+    }
+}
 
-                Ok(lb!(c2))
-            }
+fn score_char(char: &char) -> isize {
+    match char {
+        'A' => 1,
+        'B' => 3,
+        'C' => 3,
+        'D' => 2,
+        'E' => 1,
+        'F' => 4,
+        'G' => 2,
+        'H' => 4,
+        'I' => 1,
+        'J' => 8,
+        'K' => 5,
+        'L' => 1,
+        'M' => 3,
+        'N' => 1,
+        'O' => 1,
+        'P' => 3,
+        'Q' => 10,
+        'R' => 1,
+        'S' => 1,
+        'T' => 1,
+        'U' => 1,
+        'V' => 4,
+        'W' => 4,
+        'X' => 8,
+        'Y' => 4,
+        'Z' => 10,
+        _ => 0,
+    }
+}
 
-            1 => {
-                let char = s.chars().next().unwrap();
-                Ok(l!(char))
-            }
-            _ => Err(Error::TileParse),
+pub trait GetChar {
+    fn get_char(&self, index: usize) -> Option<char>;
+
+    /// The across and down words (of two letters or more) passing through `index`
+    fn words_through(&self, index: usize) -> (Option<Word>, Option<Word>)
+    where
+        Self: Sized,
+    {
+        (
+            word_through(self, index, Direction::Horizontal),
+            word_through(self, index, Direction::Vertical),
+        )
+    }
+}
+
+// Walks back from `index` to the start of the word, then reads it forwards
+fn word_through<S: GetChar>(source: &S, index: usize, direction: Direction) -> Option<Word> {
+    source.get_char(index)?;
+
+    let step = match direction {
+        Direction::Horizontal => 1,
+        Direction::Vertical => BOARD_SIZE,
+    };
+    // a column never wraps, but a row does
+    let in_line = |i: usize| match direction {
+        Direction::Horizontal => i / BOARD_SIZE == index / BOARD_SIZE,
+        Direction::Vertical => i < INDEX_OVERFLOW,
+    };
+
+    let mut start = index;
+    while let Some(prev) = start.checked_sub(step) {
+        if !in_line(prev) || source.get_char(prev).is_none() {
+            break;
+        }
+        start = prev;
+    }
+
+    let mut word = Word::new();
+    let mut cursor = start;
+    while in_line(cursor) {
+        match source.get_char(cursor) {
+            Some(c) => word.push(cursor, c),
+            None => break,
         }
+        cursor += step;
+    }
+
+    if word.len() > 1 {
+        Some(word)
+    } else {
+        None
     }
 }
 
-// 0  1  2
-// 3  4  5
-// 6  7  8
+struct Overlay<'a> {
+    board: &'a Board,
+    turn: &'a Turn,
+}
 
-// 0 1 2 3 4 5 6 7 8
-// 0 3 6 1 4 7 2 5 8
+impl Overlay<'_> {
+    // A word is new exactly when it includes a placed tile, so the words through the placed
+    // squares are all there is to find; no need to scan (and diff) the full board. Ordered as a
+    // full scan would find them: across words by row, then down words by column.
+    fn new_words(&self) -> Vec<Word> {
+        let mut horizontal = vec![];
+        let mut vertical = vec![];
+
+        for index in self.turn.indexes() {
+            let (across, down) = self.words_through(*index);
+            horizontal.extend(across);
+            vertical.extend(down);
+        }
+
+        horizontal.sort_by_key(|word: &Word| word.indexes[0]);
+        vertical.sort_by_key(|word: &Word| transpose_index(word.indexes[0], &Direction::Vertical));
+        horizontal.dedup();
+        vertical.dedup();
+
+        // A lone tile is not a word. word_through already leaves those out, but scoring and
+        // validation rely on it, so check again here
+        let words: Vec<Word> = horizontal.into_iter().chain(vertical).collect();
+        debug_assert!(
+            words.iter().all(|word| word.len() > 1),
+            "one-letter word in {:?}",
+            words
+        );
+        words.into_iter().filter(|word| word.len() > 1).collect()
+    }
+
+    #[cfg(test)]
+    fn new_words_full_scan(&self) -> Vec<Word> {
+        let original: Vec<Word> = self.board.words().collect();
+        let horizontal = Words::horizontal(self);
+        let vertical = Words::vertical(self);
+        let mut overlay_words: Vec<Word> = horizontal.chain(vertical).collect();
+
+        for word in original {
+            overlay_words.retain(|w| *w != word);
+        }
+
+        overlay_words
+    }
+
+    // FIXME: blank gets 0
+    fn score_tile(&self, tile: &Tile, index: &usize, tile_set: Option<&TileSet>) -> isize {
+        tile_value(tile_set, tile) * self.letter_bonus(index)
+    }
+
+    fn get_tile(&self, index: &usize) -> Option<&Tile> {
+        self.turn
+            .get_tile(index)
+            .or_else(|| self.board.get_tile(index))
+    }
+
+    fn score_word(&self, word: &Word, tile_set: Option<&TileSet>) -> ScoredWord {
+        let word_multiplier = self.word_bonus(&word.indexes);
+
+        let mut base = 0;
+        let mut score = 0;
+        let mut letter_bonuses = vec![];
+
+        for index in &word.indexes {
+            let tile = self
+                .get_tile(index)
+                .expect("tile unexpectedly missing from word");
+            let letter_bonus = self.letter_bonus(index);
+
+            if letter_bonus > 1 {
+                letter_bonuses.push((*index, letter_bonus));
+            }
+
+            base += tile_value(tile_set, tile);
+            score += self.score_tile(tile, index, tile_set);
+        }
+
+        ScoredWord {
+            word: String::from(word),
+            base,
+            letter_bonuses,
+            word_multiplier,
+            total: score * word_multiplier,
+        }
+    }
+
+    fn word_bonus(&self, indexes: &[usize]) -> isize {
+        self.board.word_bonus(indexes)
+    }
+
+    fn letter_bonus(&self, index: &usize) -> isize {
+        match self.board.get_square(index) {
+            Some(Square::LetterBonus(multiplier)) => *multiplier,
+            _ => 1,
+        }
+    }
+
+    pub fn score(&self) -> TurnScore {
+        self.score_with(None)
+    }
+
+    // scored with a custom tile set's letter values, or the standard ones
+    fn score_with(&self, tile_set: Option<&TileSet>) -> TurnScore {
+        let mut scores = vec![];
+        let mut breakdown = vec![];
+        for word in self.new_words() {
+            let scored = self.score_word(&word, tile_set);
+            scores.push((scored.word.clone(), scored.total));
+            breakdown.push(scored);
+        }
+
+        TurnScore { scores, breakdown }
+    }
+
+    // Accents are matched as the tile set has them; see `Dictionary::contains`
+    async fn validate_words(
+        &self,
+        dictionary_enabled: bool,
+        tile_set: Option<&TileSet>,
+    ) -> Result<(), Error> {
+        if !dictionary_enabled {
+            return Ok(());
+        }
+
+        let illegal_words = crate::dictionary::illegal_words(
+            self.new_words().into_iter().map(String::from).collect(),
+            tile_set,
+        )
+        .await;
+
+        if illegal_words.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::IllegalWords(illegal_words))
+        }
+    }
+}
+
+impl GetChar for Board {
+    fn get_char(&self, index: usize) -> Option<char> {
+        self.0.get(index).and_then(|square| square.get_char())
+    }
+}
+
+impl GetChar for Overlay<'_> {
+    fn get_char(&self, index: usize) -> Option<char> {
+        self.board
+            .get_char(index)
+            .or_else(|| self.turn.get_char(index))
+    }
+}
+
+impl GetChar for Turn {
+    fn get_char(&self, index: usize) -> Option<char> {
+        self.tiles
+            .iter()
+            .filter(|(i, _)| *i == index)
+            .map(|(_, tile)| tile.as_char())
+            .next()
+            .unwrap_or(None)
+    }
+}
+
+impl From<Word> for String {
+    fn from(word: Word) -> Self {
+        word.string
+    }
+}
+
+impl From<&Word> for String {
+    fn from(word: &Word) -> Self {
+        word.string.clone()
+    }
+}
+
+#[derive(Debug)]
+enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+#[cfg(test)]
+pub struct Words<'a, S> {
+    cursor: usize,
+    index: usize,
+    direction: Direction,
+    source: &'a S,
+}
+
+#[cfg(test)]
+impl<S: GetChar> Words<'_, S> {
+    fn horizontal(source: &S) -> Words<'_, S> {
+        Words {
+            cursor: 0,
+            index: 0,
+            direction: Direction::Horizontal,
+            source,
+        }
+    }
+
+    fn vertical(source: &S) -> Words<'_, S> {
+        Words {
+            cursor: 0,
+            index: 0,
+            direction: Direction::Vertical,
+            source,
+        }
+    }
+
+    fn advance(&mut self) {
+        self.cursor += 1;
+        self.index = transpose_index(self.cursor, &self.direction);
+    }
+}
+
+#[cfg(test)]
+impl<S: GetChar> Iterator for Words<'_, S> {
+    type Item = Word;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut current = Word::new();
+
+        // advance to next non-empty square
+        loop {
+            // the last word may have run to the very end of the board
+            if self.cursor >= INDEX_OVERFLOW {
+                return None;
+            }
+
+            while self.source.get_char(self.index).is_none() {
+                self.advance();
+
+                if self.cursor >= INDEX_OVERFLOW {
+                    return None;
+                }
+            }
+
+            while let Some(char) = self.source.get_char(self.index) {
+                current.push(self.index, char);
+                self.advance();
+
+                // end of row
+                if self.cursor % BOARD_SIZE == 0 {
+                    break;
+                }
+            }
+
+            if current.len() > 1 {
+                return Some(current.clone());
+            } else {
+                current.clear();
+            }
+        }
+    }
+}
+
+// Word uniqueness is based on the indexes played, not the word itself (allow for duplicates)
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Word {
+    indexes: Vec<usize>,
+    string: String,
+}
+
+impl Word {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, index: usize, char: char) {
+        self.indexes.push(index);
+        self.string.push(char);
+    }
+
+    pub fn clear(&mut self) {
+        self.indexes.clear();
+        self.string.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn char_indicies(&self) -> impl Iterator<Item = (char, &usize)> {
+        self.string.chars().zip(self.indexes.iter())
+    }
+}
+
+fn transpose_index(index: usize, direction: &Direction) -> usize {
+    match direction {
+        Direction::Vertical => (index / BOARD_SIZE) + (index % BOARD_SIZE * BOARD_SIZE),
+        Direction::Horizontal => index,
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Turn {
+    tiles: Vec<(usize, Tile)>,
+    // map of indexes to letters
+    // player
+    // validations:
+    // - player has the letters played
+    // - new words are all valid
+    // - new words all touch the existing words
+    // - single direction of play
+
+    // score!
+}
+
+#[derive(Clone, Default, Deserialize, Serialize, Debug, Eq, PartialEq)]
+pub struct TurnScore {
+    scores: Vec<(String, isize)>,
+    // Per-word detail for the UI; absent on scores saved before it was added.
+    #[serde(default)]
+    breakdown: Vec<ScoredWord>,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, Eq, PartialEq)]
+pub struct ScoredWord {
+    word: String,
+    // face value of the tiles, before any bonuses
+    base: isize,
+    // (index, multiplier) for each letter bonus square covered by a newly placed tile
+    letter_bonuses: Vec<(usize, isize)>,
+    word_multiplier: isize,
+    total: isize,
+}
+
+// label of the end-of-game deduction for tiles left on a rack
+static REMAINING_TILES: &str = "(remaining tiles)";
+// how an unlettered blank is written, e.g. in the remaining tile counts
+static BLANK: &str = "BLANK";
+// label of the bingo bonus
+static BINGO: &str = "*";
+// number of moves sent to a player rejoining a game in progress
+static RESYNC_MOVES: usize = 5;
+
+impl TurnScore {
+    pub fn total(&self) -> isize {
+        self.scores.iter().map(|(_, score)| score).sum()
+    }
+
+    pub fn entries(&self) -> Vec<ScoreEntry> {
+        // the breakdown lists the words in the order they're scored
+        let mut breakdown = self.breakdown.iter();
+
+        self.scores
+            .iter()
+            .map(|(label, score)| match label.as_str() {
+                label if label == BINGO => ScoreEntry::Bingo { score: *score },
+                label if label == REMAINING_TILES => ScoreEntry::RemainingTiles { score: *score },
+                word => ScoreEntry::Word {
+                    word: word.to_string(),
+                    score: *score,
+                    breakdown: breakdown.next().cloned(),
+                },
+            })
+            .collect()
+    }
+
+    // For the live preview as tiles are placed: each word and its score (with how it was scored
+    // in the breakdown), the total, and whether the play earns the bingo bonus
+    pub fn preview(&self) -> serde_json::Value {
+        let words: Vec<serde_json::Value> = self
+            .scores
+            .iter()
+            .filter(|(label, _)| label != BINGO)
+            .map(|(word, score)| json!({ "word": word, "score": score }))
+            .collect();
+
+        json!({
+            "words": words,
+            "breakdown": self.breakdown,
+            "total": self.total(),
+            "bingo": self.scores.iter().any(|(label, _)| label == BINGO),
+        })
+    }
+
+    // end-of-game adjustments aren't scored from the board
+    fn is_adjustment(&self) -> bool {
+        self.scores
+            .iter()
+            .all(|(label, _)| label == REMAINING_TILES)
+    }
+}
+
+impl Turn {
+    fn indexes(&self) -> impl Iterator<Item = &usize> {
+        self.tiles.iter().map(|(i, _)| i)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    fn is_bingo(&self, tile_count: usize) -> bool {
+        self.tiles.len() >= tile_count
+    }
+
+    // FIXME: validate words in dictionary
+    fn validate(&self) -> Result<(), Error> {
+        if self.tiles.is_empty() {
+            return Err(Error::EmptyTurn);
+        }
+
+        self.validate_unique_indexes()?;
+        self.validate_linear()?;
+
+        Ok(())
+    }
+
+    fn validate_unique_indexes(&self) -> Result<(), Error> {
+        // all indexes should be unique
+        if self.indexes().count() == self.indexes().collect::<HashSet<&usize>>().len() {
+            Ok(())
+        } else {
+            Err(Error::TurnIndexesNotUnique)
+        }
+    }
+
+    fn validate_linear(&self) -> Result<(), Error> {
+        if self
+            .indexes()
+            .map(|i| i % BOARD_SIZE)
+            .collect::<HashSet<usize>>()
+            .len()
+            == 1
+            || self
+                .indexes()
+                .map(|i| i / BOARD_SIZE)
+                .collect::<HashSet<usize>>()
+                .len()
+                == 1
+        {
+            Ok(())
+        } else {
+            Err(Error::TurnNotLinear)
+        }
+    }
+
+    fn get_tile(&self, index: &usize) -> Option<&Tile> {
+        self.tiles
+            .iter()
+            .filter(|(i, _)| i == index)
+            .map(|(_, tile)| tile)
+            .next()
+    }
+}
+
+impl TryFrom<serde_json::Value> for Turn {
+    type Error = Error;
+
+    // The client sends a map of board index to tile, e.g. `{"112": "M", "113": ":A"}`
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            // no turn could place more tiles than fit in a row; checked before parsing any of them
+            serde_json::Value::Object(map) if map.len() > BOARD_SIZE => {
+                Err(Error::TurnTooLarge(map.len()))
+            }
+            serde_json::Value::Object(map) => Ok(Turn {
+                tiles: map
+                    .iter()
+                    .map(|(index, tile)| {
+                        Ok((
+                            index.parse().map_err(|_| Error::TurnParse)?,
+                            tile.as_str().ok_or(Error::TurnParse)?.parse()?,
+                        ))
+                    })
+                    .collect::<Result<Vec<(usize, Tile)>, Error>>()?,
+            }),
+            _ => Err(Error::TurnParse),
+        }
+    }
+}
+
+impl FromStr for Tile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.chars().count() {
+            2 => {
+                let mut chars = s.chars();
+                let c1 = chars.next().unwrap();
+                let c2 = chars.next().unwrap();
+
+                if c1 != ':' {
+                    return Err(Error::TileParse);
+                }
+
+                Ok(lb!(parse_letter(c2)?))
+            }
+
+            1 => {
+                let char = s.chars().next().unwrap();
+                Ok(l!(parse_letter(char)?))
+            }
+            _ => Err(Error::TileParse),
+        }
+    }
+}
+
+// A letter for a tile, uppercased; digits, punctuation and symbols are rejected. Whether the
+// game's alphabet has the letter is checked when the turn is validated.
+fn parse_letter(c: char) -> Result<char, Error> {
+    if !c.is_alphabetic() {
+        return Err(Error::TileParse);
+    }
+
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => Ok(upper),
+        _ => Err(Error::TileParse),
+    }
+}
+
+// 0  1  2
+// 3  4  5
+// 6  7  8
+
+// 0 1 2 3 4 5 6 7 8
+// 0 3 6 1 4 7 2 5 8
+
+// 0 * 3 % 16
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+enum Square {
+    Blank,
+    Tile(Tile),
+    LetterBonus(isize),
+    WordBonus(isize),
+}
+
+impl Square {
+    fn blank() -> Self {
+        Square::Blank
+    }
+
+    fn word_bonus(multiplier: isize) -> Self {
+        Square::WordBonus(multiplier)
+    }
+
+    fn letter_bonus(multiplier: isize) -> Self {
+        Square::LetterBonus(multiplier)
+    }
+
+    fn tile(&self) -> Option<&Tile> {
+        if let Square::Tile(tile) = self {
+            return Some(tile);
+        }
+
+        None
+    }
+
+    fn get_char(&self) -> Option<char> {
+        self.tile().and_then(|tile| match tile {
+            Tile::Char(char) => Some(*char),
+            Tile::Blank(None) => None,
+            Tile::Blank(Some(char)) => Some(*char),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_board_a() -> &'static str {
+        "
+            3w .  .  2l .  .  .  3w .  .  .  2l .  H  I 
+            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
+            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
+            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
+            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .  
+            .  3l .  .  .  3l .  .  .  3l .  .  .  3l .   
+            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
+            3w .  .  2l .  .  .  A  M  P  L  E  .  .  3w  
+            .  .  2l .  .  .  2l .  A  A  .  .  2l .  .   
+            .  3l .  .  .  H  A  P  P  Y  .  .  .  3l . 
+            .  .  .  .  2w .  .  .  .  E  2w .  .  .  .   
+            2l .  .  2w .  .  .  2l .  R  .  2w .  .  O  
+            .  .  2w .  .  .  2l .  2l .  .  .  2w .  O   
+            .  2w .  .  .  3l .  .  .  3l .  .  .  2w Z   
+            3w .  .  2l .  .  .  3w .  .  .  2l .  .  E
+        "
+    }
+
+    #[test]
+    fn test_valid_game_id() {
+        assert!(valid_game_id("rudolph"));
+        assert!(valid_game_id("aZ09_x-000000"));
+        assert!(valid_game_id(&"a".repeat(MAX_GAME_ID_LENGTH)));
+
+        assert!(!valid_game_id(""));
+        assert!(!valid_game_id(&"a".repeat(MAX_GAME_ID_LENGTH + 1)));
+        assert!(!valid_game_id("game one"));
+        assert!(!valid_game_id("../admin"));
+        assert!(!valid_game_id("x';--"));
+        assert!(!valid_game_id("caf\u{e9}"));
+    }
+
+    #[test]
+    fn test_standard_premium_squares() {
+        let counts = Board::standard().unwrap().premium_square_counts();
+
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&('w', 3)], 8);
+        assert_eq!(counts[&('w', 2)], 17);
+        assert_eq!(counts[&('l', 3)], 12);
+        assert_eq!(counts[&('l', 2)], 24);
+
+        let mut board = Board::standard().unwrap();
+        board.0[0] = Square::blank();
+        assert!(matches!(
+            board.validate_premium_squares(&STANDARD_PREMIUM_SQUARES),
+            Err(Error::InvalidBoard(_))
+        ));
+    }
+
+    #[test]
+    fn test_board_parse_errors() {
+        let mut tokens: Vec<&str> = test_board_a().split_whitespace().collect();
+        tokens[BOARD_SIZE * 2 + 3] = "xx";
+        let e = Board::parse(&tokens.join(" ")).unwrap_err();
+        assert!(matches!(
+            e,
+            Error::BoardParse { ref token, row: 2, col: 3 } if token == "xx"
+        ));
+        assert_eq!(e.to_string(), "Unknown square \"xx\" at row 2, column 3");
+
+        let tokens: Vec<&str> = test_board_a().split_whitespace().collect();
+        assert!(matches!(
+            Board::parse(&tokens[1..].join(" ")),
+            Err(Error::BoardWrongSize {
+                expected: 225,
+                got: 224
+            })
+        ));
+    }
+
+    #[test]
+    fn test_board_anchors() {
+        assert_eq!(Board::standard().unwrap().anchors(), vec![BOARD_CENTER]);
+
+        // every empty square touching HI, AMPLE, AA, HAPPY, YER or OOZE
+        let board = Board::parse(test_board_a()).unwrap();
+        assert_eq!(
+            board.anchors(),
+            vec![
+                12, 28, 29, 97, 98, 99, 100, 101, 111, 117, 125, 126, 127, 130, 131, 139, 145, 155,
+                156, 157, 158, 160, 164, 173, 175, 178, 189, 193, 208, 223,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_board_grid_json() {
+        let mut board = Board::parse(test_board_a()).unwrap();
+        board.0[111] = Square::Tile(Tile::Blank(Some('S')));
+        let grid = board.to_grid_json(None);
+
+        assert_eq!(grid.as_array().unwrap().len(), BOARD_SIZE);
+        assert_eq!(grid[7].as_array().unwrap().len(), BOARD_SIZE);
+        assert_eq!(
+            grid[7][6],
+            json!({ "type": "tile", "letter": "S", "blank": true, "value": 0 })
+        );
+        assert_eq!(
+            grid[7][7],
+            json!({ "type": "tile", "letter": "A", "blank": false, "value": 1 })
+        );
+        assert_eq!(grid[7][0], json!({ "type": "bonus", "kind": "3w" }));
+        assert_eq!(grid[7][1], json!({ "type": "empty" }));
+    }
+
+    #[test]
+    fn test_board_string_round_trip() {
+        let mut board = Board::parse(test_board_a()).unwrap();
+        board.0[112] = Square::Tile(Tile::Blank(Some('M')));
+        board.0[113] = Square::Tile(Tile::Blank(None));
+
+        let reparsed = Board::parse(&board.as_board_string()).unwrap();
+        assert_eq!(reparsed, board);
+
+        for line in board.as_board_string().lines() {
+            assert_eq!(line.len(), BOARD_SIZE * 3 - 1);
+        }
+
+        let mut tokens: Vec<&str> = test_board_a().split_whitespace().collect();
+        tokens[112] = "(m)";
+        let parsed = Board::parse(&tokens.join(" ")).unwrap();
+        assert_eq!(parsed.0[112], Square::Tile(Tile::Blank(Some('M'))));
+    }
+
+    #[test]
+    fn test_random_board_string_round_trip() {
+        let mut rng = StdRng::seed_from_u64(1094);
+
+        for _ in 0..200 {
+            let squares = (0..BOARD_SIZE * BOARD_SIZE)
+                .map(|_| match rng.gen_range(0..6) {
+                    0 => Square::Blank,
+                    1 => Square::LetterBonus(rng.gen_range(2..5)),
+                    2 => Square::WordBonus(rng.gen_range(2..5)),
+                    3 => Square::Tile(Tile::Char(rng.gen_range('A'..='Z'))),
+                    4 => Square::Tile(Tile::Blank(Some(rng.gen_range('A'..='Z')))),
+                    _ => Square::Tile(Tile::Blank(None)),
+                })
+                .collect();
+            let board = Board(squares);
+
+            assert_eq!(Board::parse(&board.as_board_string()).unwrap(), board);
+        }
+    }
+
+    #[test]
+    fn test_stored_board() {
+        let mut game = test_game();
+        game.board = Board::parse(test_board_a()).unwrap();
+        game.board.0[0] = Square::Tile(Tile::Blank(Some('Q')));
+
+        let json = serde_json::to_value(&game).unwrap();
+        let compact = json["board"].as_str().unwrap();
+        let tagged = serde_json::to_string(&game.board).unwrap();
+        assert!(
+            compact.len() * 3 < tagged.len(),
+            "{} bytes stored, {} as JSON",
+            compact.len(),
+            tagged.len()
+        );
+
+        let reloaded: Game = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(reloaded.board, game.board);
+
+        // saved before the compact form
+        let mut legacy = json;
+        legacy["board"] = serde_json::to_value(&game.board).unwrap();
+        let reloaded: Game = serde_json::from_value(legacy).unwrap();
+        assert_eq!(reloaded.board, game.board);
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 1;
+        game.racks[0] = vec![l!('Q'), l!()].into();
+        game.board.0[BOARD_CENTER] = Square::Tile(l!('M'));
+        game.board.0[BOARD_CENTER + 1] = Square::Tile(lb!('A'));
+
+        let ascii = game.to_ascii(Some(0));
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines[0], "   A  B  C  D  E  F  G  H  I  J  K  L  M  N  O");
+        assert!(lines[1].starts_with(" 1 3w "));
+
+        // H8 is the center square
+        let row = lines[8];
+        assert!(row.starts_with(" 8 "));
+        assert_eq!(&row[3 + 7 * 3..3 + 7 * 3 + 2], "M ");
+        assert_eq!(&row[3 + 8 * 3..3 + 8 * 3 + 2], ":A");
+
+        assert!(ascii.contains("\nRack: Q ?\n"));
+        assert!(ascii.contains("\nFrankie: 0\nAda: 0 (to move)\n"));
+        assert!(!game.to_ascii(None).contains("Rack"));
+    }
+
+    #[test]
+    fn test_parse_move() {
+        let mut game = test_game();
+        game.board.0[BOARD_CENTER] = Square::Tile(l!('M'));
+
+        let across = game.parse_move("8G aMP").unwrap();
+        assert_eq!(across.tiles, vec![(111, lb!('A')), (113, l!('P'))]);
+
+        let down = game.parse_move("h7 AMP").unwrap();
+        assert_eq!(down.tiles, vec![(97, l!('A')), (127, l!('P'))]);
+
+        assert!(matches!(
+            game.parse_move("8H CAT"),
+            Err(Error::SquareOccupied(112))
+        ));
+        assert!(matches!(
+            game.parse_move("8M QUIZ"),
+            Err(Error::IndexOutOfBounds)
+        ));
+        for notation in ["QUIZ", "8 QUIZ", "P8 QUIZ", "16A QUIZ", "8H1 QUIZ"] {
+            assert!(matches!(game.parse_move(notation), Err(Error::TurnParse)));
+        }
+    }
+
+    #[test]
+    fn test_board_svg() {
+        let mut board = Board::parse(test_board_a()).unwrap();
+        board.0[0] = Square::Tile(Tile::Blank(Some('Q')));
+        let svg = board.to_svg(None);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), BOARD_SIZE * BOARD_SIZE);
+
+        for word in ["HI", "AMPLE", "HAPPY", "OOZE"] {
+            for letter in word.chars() {
+                assert!(svg.contains(&format!(">{}</text>", letter)), "{}", letter);
+            }
+        }
+
+        // Z's value; the blank Q has none
+        assert!(svg.contains(">10</text>"));
+        assert!(svg.contains(r##"fill="#9a5c00">Q</text>"##));
+    }
+
+    #[test]
+    fn test_board_words() {
+        let board = Board::parse(test_board_a()).unwrap();
+        let words: Vec<String> = board.words().map(Into::into).collect();
+        let expected: Vec<String> = ["HI", "AMPLE", "AA", "HAPPY", "MAP", "PAYER", "OOZE"]
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        assert_eq!(expected, words);
+    }
+
+    #[test]
+    fn test_board_new_words() {
+        let board = Board::parse(test_board_a()).unwrap();
+        let turn = Turn {
+            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+        };
+
+        assert!(turn.validate_linear().is_ok());
+        // let turn_score = board.score_turn(&turn);
+
+        let new_words: HashSet<String> = board.new_words(&turn).iter().map(Into::into).collect();
+        let expected: HashSet<String> = ["SAMPLE", "SLAT"].into_iter().map(Into::into).collect();
+
+        assert_eq!(new_words, expected);
+    }
+
+    #[test]
+    fn test_overlay_score_turn() {
+        let board = Board::parse(test_board_a()).unwrap();
+        let turn = Turn {
+            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+        };
+
+        let overlay = Overlay {
+            board: &board,
+            turn: &turn,
+        };
+
+        let scores: HashSet<(String, isize)> = overlay.score().scores.into_iter().collect();
+
+        assert_eq!(
+            scores,
+            [("SLAT".to_owned(), 5), ("SAMPLE".to_owned(), 10)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_overlay_single_tile_both_directions() {
+        let board = Board::parse(test_board_a()).unwrap();
+        // joins "AA" to the left (horizontal) and bridges the A above with the P below (vertical)
+        let turn = Turn {
+            tiles: vec![(127, l!('M'))],
+        };
+
+        assert!(turn.validate_linear().is_ok());
+
+        let overlay = Overlay {
+            board: &board,
+            turn: &turn,
+        };
+
+        let scores: HashSet<(String, isize)> = overlay.score().scores.into_iter().collect();
+
+        assert_eq!(
+            scores,
+            [("MAA".to_owned(), 5), ("AMP".to_owned(), 7)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_overlay_score_breakdown() {
+        let board = Board::standard().unwrap();
+        let turn = Turn {
+            tiles: vec![
+                (112, l!('M')),
+                (113, l!('A')),
+                (114, l!('R')),
+                (115, l!('E')),
+                (116, l!('S')),
+            ],
+        };
+
+        let overlay = Overlay {
+            board: &board,
+            turn: &turn,
+        };
+
+        let score = overlay.score();
+
+        assert_eq!(score.scores, vec![("MARES".to_string(), 16)]);
+        assert_eq!(
+            score.breakdown,
+            vec![ScoredWord {
+                word: "MARES".to_string(),
+                base: 7,
+                letter_bonuses: vec![(116, 2)],
+                word_multiplier: 2,
+                total: 16,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_game_options() {
+        let options = GameOptions {
+            bingo_bonus: 35,
+            bingo_tile_count: 6,
+            dictionary_enabled: false,
+            max_players: 2,
+            allow_mulligan: true,
+            assist: true,
+            rack_size: 7,
+            challenge_window: 30,
+        };
+        let mut game =
+            Game::with_options("game:hello".parse().unwrap(), None, options.clone()).unwrap();
+
+        let json = serde_json::to_value(&game).unwrap();
+        assert_eq!(json["bingo_bonus"], 35);
+        let reloaded: Game = serde_json::from_value(json).unwrap();
+        assert_eq!(reloaded.options(), &options);
+
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        assert!(!game.is_joinable());
+        assert!(matches!(
+            game.add_player(Player::from("Grace")),
+            Err(Error::GameFull)
+        ));
+
+        let too_many = GameOptions {
+            max_players: MAX_PLAYERS + 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Game::with_options("game:hello".parse().unwrap(), None, too_many),
+            Err(Error::InvalidOptions(_))
+        ));
+    }
+
+    #[test]
+    fn test_configured_bingo_bonus() {
+        let mut game = test_game();
+        game.options.bingo_bonus = 40;
+
+        let turn = Turn {
+            tiles: vec![
+                (112, l!('L')),
+                (113, l!('E')),
+                (114, l!('T')),
+                (115, l!('T')),
+                (116, l!('E')),
+                (117, l!('R')),
+                (118, l!('S')),
+            ],
+        };
+
+        let score = |turn: &Turn| {
+            game.score_overlay(&Overlay {
+                board: &game.board,
+                turn,
+            })
+        };
+
+        assert_eq!(
+            score(&turn).scores,
+            vec![("LETTERS".to_string(), 16), ("*".to_string(), 40)]
+        );
+
+        let turn = Turn {
+            tiles: turn.tiles[..6].to_vec(),
+        };
+
+        assert_eq!(score(&turn).scores, vec![("LETTER".to_string(), 14)]);
+    }
+
+    #[test]
+    fn test_configured_rack_size() {
+        let options = GameOptions {
+            rack_size: 5,
+            ..Default::default()
+        };
+        let mut game = Game::with_options("game:hello".parse().unwrap(), None, options).unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        assert!(game.racks.iter().all(|rack| rack.len() == 5));
+
+        // the capacity isn't saved with the tiles; it comes back from the options
+        let reloaded = persistence::load(1, json!(game)).unwrap();
+        assert!(reloaded.racks.iter().all(|rack| rack.capacity() == 5));
+
+        // playing out the whole (smaller) rack is a bingo
+        let turn = Turn {
+            tiles: vec![
+                (112, l!('M')),
+                (113, l!('E')),
+                (114, l!('L')),
+                (115, l!('T')),
+                (116, l!('S')),
+            ],
+        };
+        let score = game.score_overlay(&Overlay {
+            board: &game.board,
+            turn: &turn,
+        });
+        assert_eq!(score.scores[1], ("*".to_string(), 50));
+
+        let mut too_many = turn.clone();
+        too_many.tiles.push((117, l!('E')));
+        assert!(matches!(
+            game.validate_turn(&too_many),
+            Err(Error::TooManyTiles(6, 5))
+        ));
+
+        let too_large = GameOptions {
+            rack_size: BOARD_SIZE + 1,
+            ..Default::default()
+        };
+        assert!(too_large.validate().is_err());
+    }
+
+    #[test]
+    fn test_lone_tile_scores_no_word() {
+        let board = Board::standard().unwrap();
+
+        for index in [0, BOARD_CENTER, INDEX_OVERFLOW - 1] {
+            let turn = Turn {
+                tiles: vec![(index, l!('Q'))],
+            };
+            let overlay = Overlay {
+                board: &board,
+                turn: &turn,
+            };
+
+            assert!(overlay.new_words().is_empty());
+            assert_eq!(overlay.score().total(), 0);
+        }
+
+        // nor on a board with words elsewhere
+        let board = Board::parse(test_board_a()).unwrap();
+        let turn = Turn {
+            tiles: vec![(0, l!('Q'))],
+        };
+        assert!(board.new_words(&turn).is_empty());
+    }
+
+    #[test]
+    fn test_board_words_through() {
+        let board = Board::parse(test_board_a()).unwrap();
+        let strings = |(across, down): (Option<Word>, Option<Word>)| {
+            (across.map(|w| w.string), down.map(|w| w.string))
+        };
+
+        assert_eq!(
+            strings(board.words_through(144)),
+            (Some("HAPPY".into()), Some("PAYER".into()))
+        );
+        assert_eq!(
+            strings(board.words_through(113)),
+            (Some("AMPLE".into()), Some("MAP".into()))
+        );
+        assert_eq!(strings(board.words_through(14)), (Some("HI".into()), None));
+        assert_eq!(
+            strings(board.words_through(209)),
+            (None, Some("OOZE".into()))
+        );
+        assert_eq!(strings(board.words_through(BOARD_CENTER - 1)), (None, None));
+
+        // the same words a scan of the whole board finds through each square
+        for index in 0..INDEX_OVERFLOW {
+            let (across, down) = board.words_through(index);
+            let expected: Vec<Word> = board
+                .words()
+                .filter(|word| word.indexes.contains(&index))
+                .collect();
+
+            assert_eq!(across.into_iter().chain(down).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_new_words_matches_full_scan() {
+        // dense board: every third diagonal is empty
+        let board = Board(
+            (0..INDEX_OVERFLOW)
+                .map(|i| {
+                    if (i / BOARD_SIZE + i % BOARD_SIZE) % 3 == 0 {
+                        Square::blank()
+                    } else {
+                        Square::Tile(l!((b'A' + (i % 26) as u8) as char))
+                    }
+                })
+                .collect(),
+        );
+
+        let empty: Vec<usize> = (0..INDEX_OVERFLOW)
+            .filter(|i| board.get_tile(i).is_none())
+            .collect();
+
+        let mut turns: Vec<Turn> = empty
+            .iter()
+            .map(|i| Turn {
+                tiles: vec![(*i, l!('S'))],
+            })
+            .collect();
+
+        for row in 0..BOARD_SIZE {
+            let in_row: Vec<usize> = empty
+                .iter()
+                .copied()
+                .filter(|i| i / BOARD_SIZE == row)
+                .collect();
+
+            turns.push(Turn {
+                tiles: in_row.iter().map(|i| (*i, l!('E'))).collect(),
+            });
+        }
+
+        for turn in turns.iter() {
+            let overlay = Overlay {
+                board: &board,
+                turn,
+            };
+
+            assert_eq!(overlay.new_words(), overlay.new_words_full_scan());
+        }
+
+        let board = Board::parse(test_board_a()).unwrap();
+        let turn = Turn {
+            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+        };
+        let overlay = Overlay {
+            board: &board,
+            turn: &turn,
+        };
+
+        assert_eq!(overlay.new_words(), overlay.new_words_full_scan());
+    }
+
+    #[test]
+    fn test_board_commit_turn() {
+        let mut board = Board::parse(test_board_a()).unwrap();
+        let turn = Turn {
+            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+        };
+
+        board.commit_turn(&turn).unwrap();
+
+        let words: Vec<String> = board.words().map(Into::into).collect();
+
+        let expected: Vec<String> = [
+            "HI", "SAMPLE", "AA", "HAPPY", "SLAT", "MAP", "PAYER", "OOZE",
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_game_init() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        assert_eq!(game.racks.len(), 2);
+        assert_eq!(game.racks[0].len(), 7);
+        assert_eq!(game.racks[1].len(), 7);
+    }
+
+    fn test_bag() -> Bag {
+        let bag = vec![
+            l!('Q'),
+            l!('A'),
+            l!('P'),
+            l!('S'),
+            l!('T'),
+            l!('I'),
+            l!('E'),
+            l!('X'),
+            l!('L'),
+            l!('I'),
+            l!('T'),
+            l!('R'),
+            l!('A'),
+            l!('M'),
+            l!('S'),
+        ];
+
+        Bag(bag)
+    }
+
+    fn test_game() -> Game {
+        let channel_id = "game:hello".parse().unwrap();
+        Game::new(channel_id)
+    }
+
+    #[tokio::test]
+    async fn test_bag_empty() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('M'), l!('A'), l!('R'), l!('E'), l!('E')].into();
+        game.bag = Bag(vec![l!('S'), l!('T')]);
+
+        let state = game.state_for(Some(0));
+        assert_eq!(state["game"]["bag_empty"], false);
+
+        game.play(Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        })
+        .await
+        .unwrap();
+
+        // the last tiles have been drawn, but nobody has gone out yet
+        assert!(!game.is_over());
+        assert_eq!(game.racks[0].len(), 4);
+        let state = game.state_for(Some(1));
+        assert_eq!(state["game"]["bag_empty"], true);
+    }
+
+    #[tokio::test]
+    async fn test_game_play() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+
+        game.start().unwrap();
+        game.player_index = 0;
+
+        assert_eq!(game.racks.len(), 2);
+        assert_eq!(game.racks[0].len(), 7);
+        assert_eq!(game.racks[1].len(), 7);
+
+        // println!("{:#?}", game);
+
+        let turn_a = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn_a).await.unwrap();
+        // println!("{:#?}", game);
+
+        assert_eq!(
+            game.racks[0],
+            vec![l!('S'), l!('T'), l!('I'), l!('L'), l!('Q')]
+        );
+
+        let words: Vec<String> = game.board.words().map(Into::into).collect();
+        assert_eq!(game.player_index, 1);
+        assert_eq!(words, vec!["MAR".to_string()]);
+
+        assert_eq!(
+            game.scores[0],
+            vec![TurnScore {
+                scores: vec![("MAR".to_owned(), 10)],
+                breakdown: vec![ScoredWord {
+                    word: "MAR".to_string(),
+                    base: 5,
+                    letter_bonuses: vec![],
+                    word_multiplier: 2,
+                    total: 10,
+                }],
+            }]
+        );
+
+        // the breakdown reaches the score sheet in the game state
+        let state = game.player_state(None);
+        let word = &state["game"]["scores"]["Frankie"][0][0];
+        assert_eq!(word["word"], "MAR");
+        assert_eq!(word["breakdown"]["word_multiplier"], 2);
+
+        let turn_b = Turn {
+            tiles: vec![(126, l!('T')), (127, l!('A')), (128, l!('X'))],
+        };
+
+        game.play(turn_b).await.unwrap();
+        // println!("{:#?}", game);
+
+        assert_eq!(game.racks[1], vec![l!('E'), l!('I'), l!('S'), l!('P')]);
+
+        assert_eq!(game.scores[1].len(), 1);
+        assert_eq!(
+            game.scores[1][0].scores,
+            vec![
+                ("TAX".to_string(), 19),
+                ("MA".to_string(), 4),
+                ("AX".to_string(), 17),
+            ]
+        );
+        assert_eq!(game.player_index, 0);
+
+        let turn_c_err_1 = Turn {
+            tiles: vec![(140, l!('T')), (127, l!('A')), (128, l!('X'))],
+        };
+
+        assert!(matches!(
+            game.play(turn_c_err_1).await.unwrap_err(),
+            Error::TurnNotLinear
+        ));
+
+        let turn_c_err_2 = Turn {
+            tiles: vec![(140, l!('T')), (141, l!('A')), (142, l!('X'))],
+        };
+
+        assert!(matches!(
+            game.play(turn_c_err_2).await.unwrap_err(),
+            Error::NoTileToSpend(l!('A'))
+        ));
+
+        let turn_c_1 = Turn {
+            tiles: vec![(141, l!('I')), (156, l!('L'))],
+        };
+
+        game.play(turn_c_1).await.unwrap();
+        // println!("{:#?}", game);
+
+        assert_eq!(
+            word_scores(&game.scores[0]),
+            vec![vec![("MAR".to_owned(), 10)], vec![("TIL".to_owned(), 3)]]
+        );
+
+        let turn_d = Turn {
+            tiles: vec![
+                (169, l!('P')),
+                (170, l!('I')),
+                (171, l!('E')),
+                (172, l!('S')),
+            ],
+        };
+
+        game.play(turn_d).await.unwrap();
+        // println!("{:#?}", game);
+
+        assert!(game.is_over());
+
+        assert_eq!(
+            word_scores(&game.scores[0]),
+            vec![
+                vec![("MAR".to_owned(), 10)],
+                vec![("TIL".to_owned(), 3)],
+                vec![("(remaining tiles)".to_string(), -12)]
+            ]
+        )
+    }
+
+    // the word scores of each turn, leaving out the breakdown
+    fn word_scores(turns: &[TurnScore]) -> Vec<Vec<(String, isize)>> {
+        turns.iter().map(|turn| turn.scores.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_parallel_play_scores_every_hook() {
+        let mut game = test_game();
+        game.options.dictionary_enabled = false;
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        let tiles = |word: &str| word.chars().map(|c| l!(c)).collect::<Vec<_>>();
+        game.racks[0] = tiles("HOME").into();
+        game.racks[1] = tiles("ODES").into();
+
+        game.play(Turn {
+            tiles: vec![
+                (112, l!('H')),
+                (113, l!('O')),
+                (114, l!('M')),
+                (115, l!('E')),
+            ],
+        })
+        .await
+        .unwrap();
+
+        // ODES directly beneath HOME, the D on a double letter
+        let parallel = Turn {
+            tiles: vec![
+                (127, l!('O')),
+                (128, l!('D')),
+                (129, l!('E')),
+                (130, l!('S')),
+            ],
+        };
+
+        // every hook is among the words checked against the dictionary
+        let words: Vec<String> = Overlay {
+            board: &game.board,
+            turn: &parallel,
+        }
+        .new_words()
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(words, vec!["ODES", "HO", "OD", "ME", "ES"]);
+
+        game.play(parallel).await.unwrap();
+        assert_eq!(game.scores[1].len(), 1);
+        assert_eq!(
+            game.scores[1][0].scores,
+            vec![
+                ("ODES".to_string(), 7),
+                ("HO".to_string(), 5),
+                ("OD".to_string(), 5),
+                ("ME".to_string(), 4),
+                ("ES".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_propose_validates_like_play() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        assert!(matches!(game.propose(&turn, 0), Err(Error::NotStarted)));
+
+        game.start().unwrap();
+        assert_eq!(game.propose(&turn, 0).unwrap().total(), 10);
+
+        // Ada doesn't have an M or R
+        assert!(matches!(
+            game.propose(&turn, 1),
+            Err(Error::NoTileToSpend(_))
+        ));
+
+        let disconnected = Turn {
+            tiles: vec![(0, l!('M')), (1, l!('A')), (2, l!('R'))],
+        };
+        assert!(matches!(
+            game.propose(&disconnected, 0),
+            Err(Error::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn test_proposed_preview() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.racks[0] = "LETTERS".chars().map(|c| l!(c)).collect::<Vec<_>>().into();
+
+        let turn = Turn {
+            tiles: "LETTERS"
+                .chars()
+                .enumerate()
+                .map(|(i, c)| (112 + i, l!(c)))
+                .collect(),
+        };
+        let before = serde_json::to_value(&game).unwrap();
+
+        assert_eq!(
+            game.propose(&turn, 0).unwrap().preview(),
+            json!({
+                "words": [{ "word": "LETTERS", "score": 16 }],
+                "breakdown": [{
+                    "word": "LETTERS",
+                    "base": 7,
+                    "letter_bonuses": [[116, 2]],
+                    "word_multiplier": 2,
+                    "total": 16,
+                }],
+                "total": 66,
+                "bingo": true,
+            })
+        );
+
+        // only a preview; nothing about the game changes
+        assert_eq!(serde_json::to_value(&game).unwrap(), before);
+    }
+
+    #[test]
+    fn test_score_hypothetical() {
+        let game = test_game();
+
+        // QUIT along the top row, ending on the triple word square at 7
+        let turn = Turn {
+            tiles: "QUIT"
+                .chars()
+                .enumerate()
+                .map(|(i, c)| (4 + i, l!(c)))
+                .collect(),
+        };
+        let score = game.score_hypothetical(&turn).unwrap();
+        assert_eq!(score.scores, vec![("QUIT".to_string(), 39)]);
+        assert_eq!(score.breakdown[0].base, 13);
+        assert_eq!(score.breakdown[0].word_multiplier, 3);
+
+        // the game hasn't started, no one holds these tiles and the word misses the center, yet it
+        // scores; only a placement off the board (or over a tile) is refused
+        assert!(matches!(
+            game.score_hypothetical(&Turn {
+                tiles: vec![(225, l!('A'))]
+            }),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    fn tiny_tile_set() -> TileSet {
+        TileSet {
+            distribution: [('M', 5), ('A', 5), ('R', 5)].into_iter().collect(),
+            blanks: 1,
+            scores: [('M', 10), ('A', 1), ('R', 2)].into_iter().collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_tile_set() {
+        let channel_id = "game:custom".parse().unwrap();
+        let mut game =
+            Game::with_tile_set(channel_id, Some(1), tiny_tile_set(), Default::default()).unwrap();
+        assert_eq!(game.bag.len(), 16);
+
+        // the same tiles, ordered so that Frankie draws M, A and R
+        let mut tiles = vec![l!('M'); 4];
+        tiles.extend(vec![l!('A'); 4]);
+        tiles.extend(vec![l!('R'); 4]);
+        tiles.extend(vec![l!(), l!('R'), l!('A'), l!('M')]);
+        game.bag = Bag(tiles);
+
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        assert!(game.validate_full().is_empty());
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn).await.unwrap();
+
+        // (10 + 1 + 2) on the center double word
+        assert_eq!(game.scores[0][0].total(), 26);
+    }
+
+    #[test]
+    fn test_language_tile_sets() {
+        for language in [
+            Language::English,
+            Language::French,
+            Language::German,
+            Language::Spanish,
+        ] {
+            assert!(language.tile_set().validate().is_ok(), "{:?}", language);
+        }
+
+        let english = Language::English.tile_set();
+        assert_eq!(english.tiles().iter().map(|(_, n)| n).sum::<usize>(), 100);
+        assert_eq!(english.scores[&'Q'], 10);
+        assert_eq!(Language::Spanish.tile_set().scores[&'Ñ'], 8);
+
+        // HAM on the center double word: M is worth 3 in English and 2 in French
+        let turn = Turn {
+            tiles: vec![(112, l!('H')), (113, l!('A')), (114, l!('M'))],
+        };
+        let score = |language: Language| {
+            let game = Game::with_tile_set(
+                "game:hello".parse().unwrap(),
+                None,
+                language.tile_set(),
+                Default::default(),
+            )
+            .unwrap();
+
+            game.score_overlay(&Overlay {
+                board: &game.board,
+                turn: &turn,
+            })
+            .total()
+        };
+        assert_eq!(score(Language::English), 16);
+        assert_eq!(score(Language::French), 14);
+    }
+
+    #[tokio::test]
+    async fn test_has_legal_move() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        game.racks[0] = "VVWWKJX".chars().map(|c| l!(c)).collect();
+        game.racks[1] = "QUIT".chars().map(|c| l!(c)).collect();
+        assert!(!game.has_legal_move(0).await);
+        assert!(game.has_legal_move(1).await);
+        assert!(!game.has_legal_move(2).await);
+
+        // without the word list, anything connected goes
+        game.options.dictionary_enabled = false;
+        assert!(game.has_legal_move(0).await);
+    }
+
+    #[test]
+    fn test_redraw_opening_rack() {
+        let mut game = Game::with_options(
+            "game:hello".parse().unwrap(),
+            Some(7),
+            GameOptions {
+                allow_mulligan: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        assert!(matches!(
+            game.redraw_opening_rack(0),
+            Err(Error::NotStarted)
+        ));
+
+        game.start().unwrap();
+        game.player_index = 0;
+        game.redraw_opening_rack(0).unwrap();
+        assert_eq!(game.racks[0].len(), 7);
+        game.assert_tile_conservation();
+
+        assert!(matches!(
+            game.redraw_opening_rack(0),
+            Err(Error::CannotRedraw)
+        ));
+
+        // only before the player's first move
+        game.set_bag(vec![]);
+        game.pass().unwrap();
+        game.pass().unwrap();
+        assert!(matches!(
+            game.redraw_opening_rack(1),
+            Err(Error::CannotRedraw)
+        ));
+
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.start().unwrap();
+        assert!(matches!(
+            game.redraw_opening_rack(0),
+            Err(Error::CannotRedraw)
+        ));
+    }
+
+    #[test]
+    fn test_turn_has_gaps() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('A'), l!('T'), l!('S'), l!('E')].into();
+        game.board.0[112] = Square::Tile(l!('A'));
+        game.board.0[116] = Square::Tile(l!('O'));
+
+        // two runs on the same row, each touching a tile on the board
+        let two_runs = Turn {
+            tiles: vec![(111, l!('T')), (117, l!('S'))],
+        };
+        assert!(matches!(
+            game.propose(&two_runs, 0),
+            Err(Error::TurnHasGaps)
+        ));
+
+        // the gap between the placed tiles is filled by the board
+        game.board.0[113] = Square::Tile(l!('R'));
+        game.board.0[114] = Square::Tile(l!('T'));
+        let one_run = Turn {
+            tiles: vec![(111, l!('T')), (115, l!('S'))],
+        };
+        assert!(game.propose(&one_run, 0).is_ok());
+
+        let column = Turn {
+            tiles: vec![(112 - BOARD_SIZE, l!('T')), (112 + BOARD_SIZE * 2, l!('E'))],
+        };
+        assert!(matches!(game.propose(&column, 0), Err(Error::TurnHasGaps)));
+    }
+
+    #[tokio::test]
+    async fn test_too_many_tiles() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        // the rack holds every tile played, so only the count stops the turn
+        game.racks[0] = vec![l!('A'); 8].into();
+        let turn = Turn {
+            tiles: (108..116).map(|index| (index, l!('A'))).collect(),
+        };
+        assert!(matches!(
+            game.play(turn).await,
+            Err(Error::TooManyTiles(8, 7))
+        ));
+        assert_eq!(game.racks[0].len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_tile_parse() {
+        assert_eq!(Tile::from_str("A").unwrap(), l!('A'));
+        assert_eq!(Tile::from_str("a").unwrap(), l!('A'));
+        assert_eq!(Tile::from_str(":b").unwrap(), lb!('B'));
+        assert!(matches!(Tile::from_str("5"), Err(Error::TileParse)));
+        assert!(matches!(Tile::from_str("😀"), Err(Error::TileParse)));
+        assert!(matches!(Tile::from_str(":?"), Err(Error::TileParse)));
+        assert!(matches!(
+            Turn::try_from(json!({ "112": "A", "113": "5" })),
+            Err(Error::TileParse)
+        ));
+
+        // rejected by size, before any key or tile is parsed
+        let oversized: serde_json::Map<String, serde_json::Value> = (0..10_000)
+            .map(|i| (format!("not an index {}", i), json!("not a tile")))
+            .collect();
+        assert!(matches!(
+            Turn::try_from(serde_json::Value::Object(oversized)),
+            Err(Error::TurnTooLarge(10_000))
+        ));
+
+        // a letter, but not one in the English tile set
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('Ñ'), l!('A')].into();
+
+        let turn = Turn::try_from(json!({ "112": "ñ", "113": "a" })).unwrap();
+        assert!(matches!(game.play(turn).await, Err(Error::TileParse)));
+    }
+
+    #[test]
+    fn test_tile_set_validation() {
+        assert!(tiny_tile_set().validate().is_ok());
+
+        let mut empty = tiny_tile_set();
+        empty.distribution.clear();
+        assert!(matches!(empty.validate(), Err(Error::InvalidTileSet(_))));
+
+        let mut lowercase = tiny_tile_set();
+        lowercase.distribution.insert('m', 1);
+        lowercase.scores.insert('m', 1);
+        assert!(matches!(
+            lowercase.validate(),
+            Err(Error::InvalidTileSet(_))
+        ));
+
+        let mut unscored = tiny_tile_set();
+        unscored.distribution.insert('Q', 1);
+        assert!(matches!(unscored.validate(), Err(Error::InvalidTileSet(_))));
+
+        let mut too_few = tiny_tile_set();
+        too_few.distribution.insert('M', 1);
+        assert!(matches!(too_few.validate(), Err(Error::InvalidTileSet(_))));
+    }
+
+    #[test]
+    fn test_position_string_round_trip() {
+        let position = [
+            "15", "15", "15", "15", "15", "15", "15", "7MAR5", "8Ax5", "8X6", "15", "15", "15",
+            "15", "15",
+        ]
+        .join("/")
+            + " 1 STIL?Q/EIPS";
+
+        let game = Game::from_position_string("game:puzzle".parse().unwrap(), &position).unwrap();
+        assert_eq!(game.to_position_string(), position);
+
+        assert_eq!(game.board.get_tile(&112), Some(&l!('M')));
+        assert_eq!(game.board.get_tile(&129), Some(&lb!('X')));
+        assert_eq!(game.racks[0].last(), Some(&l!('Q')));
+        assert_eq!(game.racks[0][4], l!());
+        assert_eq!(game.player_index, 1);
+        assert_eq!(game.current_player(), Some("player 2"));
+        assert!(game.validate_full().is_empty());
+
+        let game = test_game();
+        let empty =
+            Game::from_position_string("game:empty".parse().unwrap(), &game.to_position_string());
+        assert!(matches!(empty, Err(Error::PositionParse(_))));
+
+        // runs of empty squares are capped at the row, however many digits they have
+        for run in ["16", "99999999999999999999999"] {
+            let long_row = position.replacen("15", run, 1);
+            assert!(matches!(
+                Game::from_position_string("game:puzzle".parse().unwrap(), &long_row),
+                Err(Error::PositionParse(_))
+            ));
+        }
+
+        let too_many_qs = position.replace("EIPS", "QQ");
+        assert!(matches!(
+            Game::from_position_string("game:puzzle".parse().unwrap(), &too_many_qs),
+            Err(Error::PositionParse(_))
+        ));
+    }
+
+    #[test]
+    fn test_score_entries() {
+        let entries = vec![
+            ScoreEntry::Word {
+                word: "MAR".into(),
+                score: 10,
+                breakdown: None,
+            },
+            ScoreEntry::Word {
+                word: "QI".into(),
+                score: 22,
+                breakdown: Some(ScoredWord {
+                    word: "QI".into(),
+                    base: 11,
+                    letter_bonuses: vec![],
+                    word_multiplier: 2,
+                    total: 22,
+                }),
+            },
+            ScoreEntry::Bingo { score: 50 },
+            ScoreEntry::RemainingTiles { score: -4 },
+            ScoreEntry::Pass,
+            ScoreEntry::Exchange { count: 3 },
+        ];
+
+        for entry in entries {
+            let value = serde_json::to_value(&entry).unwrap();
+            assert!(value["kind"].is_string());
+            assert_eq!(serde_json::from_value::<ScoreEntry>(value).unwrap(), entry);
+        }
+
+        assert_eq!(
+            serde_json::to_value(ScoreEntry::Pass).unwrap(),
+            json!({ "kind": "pass" })
+        );
+
+        let score = TurnScore {
+            scores: vec![
+                ("MAR".into(), 10),
+                (BINGO.into(), 50),
+                (REMAINING_TILES.into(), -4),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(score.entries()).unwrap(),
+            json!([
+                { "kind": "word", "word": "MAR", "score": 10 },
+                { "kind": "bingo", "score": 50 },
+                { "kind": "remaining_tiles", "score": -4 },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_disabled() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('Z'); 5].into();
+
+        let turn = Turn {
+            tiles: (112..117).map(|index| (index, l!('Z'))).collect(),
+        };
+        assert!(matches!(
+            game.play(turn.clone()).await,
+            Err(Error::IllegalWords(_))
+        ));
+
+        game.options.dictionary_enabled = false;
+        game.play(turn).await.unwrap();
+        let score = game.scores[0].last().unwrap();
+        assert_eq!(score.scores[0].0, "ZZZZZ");
+        assert!(score.total() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_set_rack() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        let total = game.bag.len() + game.racks.iter().map(|rack| rack.len()).sum::<usize>();
+        // Ada may have been dealt the Q
+        game.set_rack(1, vec![]).unwrap();
+        game.set_rack(0, vec![l!('Q'), l!('U'), l!('I'), l!('T')])
+            .unwrap();
+        assert_eq!(game.racks[0], vec![l!('Q'), l!('U'), l!('I'), l!('T')]);
+        assert_eq!(
+            game.bag.len() + game.racks.iter().map(|rack| rack.len()).sum::<usize>(),
+            total
+        );
+
+        // there's only one Q
+        assert!(matches!(
+            game.set_rack(1, vec![l!('Q')]),
+            Err(Error::NoTileToSpend(Tile::Char('Q')))
+        ));
+
+        game.set_bag(vec![l!('E'), l!('S')]);
+        let turn = Turn {
+            tiles: vec![
+                (112, l!('Q')),
+                (113, l!('U')),
+                (114, l!('I')),
+                (115, l!('T')),
+            ],
+        };
+        game.play(turn).await.unwrap();
+
+        // drawn in order
+        assert_eq!(game.racks[0], vec![l!('E'), l!('S')]);
+        assert!(game.bag.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resync_state() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn).await.unwrap();
+        // swaps or passes
+        for _ in 0..RESYNC_MOVES {
+            game.record_move(Turn::default(), 0);
+            game.next_player();
+        }
+
+        // rejoining gets the same seat
+        assert_eq!(game.add_player(Player::from("Frankie")).unwrap(), 0);
+
+        let state = game.resync_state(0);
+        assert_eq!(state["game"]["board"], json!(game.board));
+        assert_eq!(state["rack"], json!(game.racks[0]));
+        assert_eq!(
+            state["game"]["current_player"],
+            json!(game.current_player())
+        );
+
+        let moves = state["moves"].as_array().unwrap();
+        assert_eq!(moves.len(), RESYNC_MOVES);
+        assert_eq!(moves[0], json!({ "player": "Ada", "tiles": [] }));
+        assert!(moves.iter().all(|m| m.get("rack").is_none()));
+
+        // the play has scrolled out of the latest moves, but is still on the board
+        assert_eq!(state["game"]["board"][113]["Tile"], json!({ "Char": 'A' }));
+    }
+
+    #[tokio::test]
+    async fn test_is_joinable() {
+        let mut game = test_game();
+        assert!(game.is_joinable());
+
+        game.add_player(Player::from("Frankie")).unwrap();
+        assert!(game.is_joinable());
+
+        for i in 1..MAX_PLAYERS {
+            game.add_player(Player(format!("player {}", i))).unwrap();
+        }
+        assert!(!game.is_joinable());
+        assert!(matches!(
+            game.add_player(Player::from("Ada")),
+            Err(Error::GameFull)
+        ));
+        // but a seated player still gets their seat back
+        assert_eq!(game.add_player(Player::from("Frankie")).unwrap(), 0);
+
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.start().unwrap();
+        assert!(!game.is_joinable());
+    }
+
+    #[test]
+    fn test_error_payload() {
+        let e = Error::IllegalWords(vec!["QX".into(), "ZZQ".into()]);
+        assert_eq!(
+            e.payload(),
+            json!({
+                "code": "illegal_words",
+                "message": "QX and ZZQ are not valid words",
+                "words": ["QX", "ZZQ"],
+            })
+        );
+
+        let e = Error::IllegalWords(vec!["ZZZ".into()]);
+        assert_eq!(e.payload()["message"], "ZZZ is not a valid word");
+        let e = Error::IllegalWords(vec!["A".into(), "B".into(), "C".into()]);
+        assert_eq!(e.payload()["message"], "A, B and C are not valid words");
+
+        assert_eq!(
+            Error::NotYourTurn.payload(),
+            json!({ "code": "not_your_turn", "message": "It's not your turn" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_illegal_words_payload() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('M'), l!('A'), l!('R'), l!('Q'), l!('Z')].into();
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn).await.unwrap();
+
+        // MARQ across, QZ down
+        game.player_index = 0;
+        let turn = Turn {
+            tiles: vec![(115, l!('Q')), (130, l!('Z'))],
+        };
+        let e = game.play(turn).await.unwrap_err();
+        let payload = e.payload();
+
+        assert_eq!(payload["code"], "illegal_words");
+        let mut words: Vec<&str> = payload["words"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|word| word.as_str().unwrap())
+            .collect();
+        words.sort_unstable();
+        assert_eq!(words, vec!["MARQ", "QZ"]);
+        assert!(payload["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("are not valid words"));
+    }
+
+    #[test]
+    fn test_terminate() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+
+        // a game can be terminated before it starts
+        game.terminate().unwrap();
+        assert_eq!(game.state(), &State::Over);
+        assert!(game.final_standings().is_some());
+        assert!(matches!(game.terminate(), Err(Error::GameOver)));
+    }
+
+    #[test]
+    fn test_second_start_changes_nothing() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        let racks = game.racks.clone();
+        let player_index = game.player_index;
+        let bag_len = game.bag.len();
+
+        // e.g. both players pressing start at once
+        assert!(matches!(game.start(), Err(Error::AlreadyStarted)));
+        assert_eq!(game.racks, racks);
+        assert_eq!(game.player_index, player_index);
+        assert_eq!(game.bag.len(), bag_len);
+    }
+
+    #[tokio::test]
+    async fn test_over_game_rejects_every_change() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.options.allow_mulligan = true;
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A'))],
+        };
+        assert!(matches!(game.pass(), Err(Error::NotStarted)));
+        assert!(matches!(game.swap(turn.clone()), Err(Error::NotStarted)));
+
+        game.start().unwrap();
+        assert!(matches!(game.start(), Err(Error::AlreadyStarted)));
+        game.terminate().unwrap();
+        let rack = game.racks[game.player_index].clone();
+
+        assert!(matches!(game.start(), Err(Error::GameOver)));
+        assert!(matches!(
+            game.play(turn.clone()).await,
+            Err(Error::GameOver)
+        ));
+        assert!(matches!(game.swap(turn.clone()), Err(Error::GameOver)));
+        assert!(matches!(game.exchange_all(), Err(Error::GameOver)));
+        assert!(matches!(game.pass(), Err(Error::GameOver)));
+        assert!(matches!(game.propose(&turn, 0), Err(Error::GameOver)));
+        assert!(matches!(game.forfeit(0), Err(Error::GameOver)));
+        assert!(matches!(game.redraw_opening_rack(0), Err(Error::GameOver)));
+        assert!(matches!(game.play_best().await, Err(Error::GameOver)));
+
+        assert_eq!(game.racks[game.player_index], rack);
+        assert!(game.history.is_empty());
+        assert_eq!(game.state(), &State::Over);
+    }
+
+    #[test]
+    fn test_assert_turn() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        assert!(game.assert_turn(0).is_ok());
+        assert!(matches!(game.assert_turn(1), Err(Error::NotYourTurn)));
+        assert!(matches!(game.assert_turn(2), Err(Error::IndexOutOfBounds)));
+
+        // either seated player may act out of turn
+        assert!(game.assert_participant(0).is_ok());
+        assert!(game.assert_participant(1).is_ok());
+        assert!(matches!(
+            game.assert_participant(2),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_forfeit() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        assert!(matches!(game.forfeit(0), Err(Error::NotStarted)));
+
+        game.start().unwrap();
+        game.player_index = 0;
+        assert!(game.final_standings().is_none());
+
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn).await.unwrap();
+
+        // Frankie is ahead, but forfeits
+        game.forfeit(0).unwrap();
+        assert!(game.is_over());
+        assert_eq!(game.current_player(), None);
+
+        let standings = game.final_standings().unwrap();
+        assert_eq!(
+            standings,
+            vec![
+                Standing {
+                    player: Player::from("Ada"),
+                    score: 0,
+                    forfeited: false,
+                },
+                Standing {
+                    player: Player::from("Frankie"),
+                    score: 10,
+                    forfeited: true,
+                },
+            ]
+        );
+
+        assert!(matches!(game.forfeit(1), Err(Error::GameOver)));
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+
+        let score = |scores: &[(&str, isize)]| TurnScore {
+            scores: scores
+                .iter()
+                .map(|(word, points)| (word.to_string(), *points))
+                .collect(),
+            ..Default::default()
+        };
+
+        game.scores[0] = vec![
+            score(&[("MAR", 10)]),
+            score(&[("LETTERS", 16), ("*", 50)]),
+            score(&[("TAX", 19), ("MA", 4), ("AX", 17)]),
+            score(&[("QI", 22), ("*", 50)]),
+            score(&[("(remaining tiles)", -3)]),
+        ];
+
+        assert_eq!(
+            game.player_stats(0),
+            PlayerStats {
+                total: 185,
+                best_word: Some(("QI".to_string(), 22)),
+                bingos: 2,
+                tiles_played: 0,
+                exchanges: 0,
+            }
+        );
+        assert_eq!(game.player_stats(1), PlayerStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_verify_scores() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+
+        let turn_a = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn_a).await.unwrap();
+        let turn_b = Turn {
+            tiles: vec![(126, l!('T')), (127, l!('A')), (128, l!('X'))],
+        };
+        game.play(turn_b).await.unwrap();
+
+        assert!(game.verify_scores().is_empty());
+
+        game.scores[1][0].scores[0].1 = 99;
+        assert_eq!(
+            game.verify_scores(),
+            vec!["Ada has 120 points from plays, expected 40".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_analyze_finds_missed_points() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
 
-// 0 * 3 % 16
+        // SMART was available from the same rack, for 16 across the center
+        let turn = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        };
+        game.play(turn).await.unwrap();
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-enum Square {
-    Blank,
-    Tile(Tile),
-    LetterBonus(isize),
-    WordBonus(isize),
-}
+        let dictionary: Dictionary = ["MAR", "SMART"].into_iter().collect();
+        assert!(matches!(
+            game.analyze_with(&dictionary),
+            Err(Error::GameNotOver)
+        ));
 
-impl Square {
-    fn blank() -> Self {
-        Square::Blank
-    }
+        game.state = State::Over;
+        let analysis = game.analyze_with(&dictionary).unwrap();
 
-    fn word_bonus(multiplier: isize) -> Self {
-        Square::WordBonus(multiplier)
+        assert_eq!(analysis.len(), 1);
+        assert_eq!(analysis[0].player, Player::from("Frankie"));
+        assert_eq!(analysis[0].score.total(), 10);
+        assert_eq!(
+            analysis[0].best_score.as_ref().map(TurnScore::total),
+            Some(16)
+        );
+        assert_eq!(analysis[0].missed, 6);
     }
 
-    fn letter_bonus(multiplier: isize) -> Self {
-        Square::LetterBonus(multiplier)
-    }
+    #[test]
+    fn test_swap_requires_seven_tiles_in_bag() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
 
-    fn tile(&self) -> Option<&Tile> {
-        if let Square::Tile(tile) = self {
-            return Some(tile);
-        }
+        // lettered tiles only; the drawn rack may include a blank
+        let swap = |game: &Game| {
+            let mut letters = game.racks[game.player_index]
+                .iter()
+                .filter(|tile| matches!(tile, Tile::Char(_)));
+            Turn {
+                tiles: vec![(0, *letters.next().unwrap()), (1, *letters.next().unwrap())],
+            }
+        };
 
-        None
-    }
+        game.bag = Bag(vec![l!('E'); 7]);
+        game.swap(swap(&game)).unwrap();
+        assert_eq!(game.bag.len(), 7);
 
-    fn get_char(&self) -> Option<char> {
-        self.tile().and_then(|tile| match tile {
-            Tile::Char(char) => Some(*char),
-            Tile::Blank(None) => None,
-            Tile::Blank(Some(char)) => Some(*char),
-        })
+        game.bag = Bag(vec![l!('E'); 6]);
+        let turn = swap(&game);
+        assert!(matches!(game.swap(turn), Err(Error::CannotSwap(6))));
+        assert_eq!(game.bag.len(), 6);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[tokio::test]
+    async fn test_rematch() {
+        let mut game = Game::with_options(
+            "game:first".parse().unwrap(),
+            Some(7),
+            GameOptions {
+                bingo_bonus: 35,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = vec![l!('M'), l!('A'), l!('R')].into();
+        assert!(matches!(
+            game.rematch("game:second".parse().unwrap()),
+            Err(Error::GameNotOver)
+        ));
 
-    fn test_board_a() -> &'static str {
-        "
-            3w .  .  2l .  .  .  3w .  .  .  2l .  H  I 
-            .  2w .  .  .  3l .  .  .  3l .  .  .  2w .   
-            .  .  2w .  .  .  2l .  2l .  .  .  2w .  .   
-            2l .  .  2w .  .  .  2l .  .  .  2w .  .  2l  
-            .  .  .  .  2w .  .  .  .  .  2w .  .  .  .  
-            .  3l .  .  .  3l .  .  .  3l .  .  .  3l .   
-            .  .  2l .  .  .  2l .  2l .  .  .  2l .  .   
-            3w .  .  2l .  .  .  A  M  P  L  E  .  .  3w  
-            .  .  2l .  .  .  2l .  A  A  .  .  2l .  .   
-            .  3l .  .  .  H  A  P  P  Y  .  .  .  3l . 
-            .  .  .  .  2w .  .  .  .  E  2w .  .  .  .   
-            2l .  .  2w .  .  .  2l .  R  .  2w .  .  O  
-            .  .  2w .  .  .  2l .  2l .  .  .  2w .  O   
-            .  2w .  .  .  3l .  .  .  3l .  .  .  2w Z   
-            3w .  .  2l .  .  .  3w .  .  .  2l .  .  E
-        "
+        game.play(Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
+        })
+        .await
+        .unwrap();
+        game.terminate().unwrap();
+
+        let rematch = game.rematch("game:second".parse().unwrap()).unwrap();
+        assert_eq!(rematch.name(), "second");
+        assert_eq!(rematch.players(), game.players());
+        assert_eq!(rematch.options(), game.options());
+        assert_eq!(rematch.state(), &State::Started);
+        assert!(rematch.board.0.iter().all(|square| square.tile().is_none()));
+        assert!(rematch.scores.iter().all(Vec::is_empty));
+        assert!(rematch.racks.iter().all(|rack| rack.len() == RACK_SIZE));
+        assert!(rematch.validate_full().is_empty());
+
+        // Frankie went first last time
+        assert_eq!(rematch.current_player(), Some("Ada"));
+
+        // seeded, so the same rematch deals the same racks
+        let again = game.rematch("game:second".parse().unwrap()).unwrap();
+        assert_eq!(again.racks, rematch.racks);
     }
 
     #[test]
-    fn test_board_words() {
-        let board = Board::parse(test_board_a()).unwrap();
-        let words: Vec<String> = board.words().map(Into::into).collect();
-        let expected: Vec<String> = ["HI", "AMPLE", "AA", "HAPPY", "MAP", "PAYER", "OOZE"]
-            .into_iter()
-            .map(Into::into)
-            .collect();
+    fn test_pass_regardless_of_bag_size() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        assert!(game.bag.len() >= 7);
 
-        assert_eq!(expected, words);
-    }
+        game.pass().unwrap();
+        assert_eq!(game.player_index, 1);
 
-    #[test]
-    fn test_board_new_words() {
-        let board = Board::parse(test_board_a()).unwrap();
+        game.bag = Bag(vec![l!('E'); 3]);
         let turn = Turn {
-            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+            tiles: vec![(0, game.racks[1][0])],
         };
+        assert!(matches!(game.swap(turn), Err(Error::CannotSwap(3))));
+        game.pass().unwrap();
+        assert_eq!(game.player_index, 0);
+        assert_eq!(game.pass_count, 2);
+        assert_eq!(game.state_for(Some(0))["game"]["pass_allowed"], true);
 
-        assert!(turn.validate_linear().is_ok());
-        // let turn_score = board.score_turn(&turn);
-
-        let new_words: HashSet<String> = board.new_words(&turn).iter().map(Into::into).collect();
-        let expected: HashSet<String> = ["SAMPLE", "SLAT"].into_iter().map(Into::into).collect();
-
-        assert_eq!(new_words, expected);
+        // two rounds of passes end the game
+        game.pass().unwrap();
+        game.pass().unwrap();
+        assert!(game.is_over());
     }
 
-    #[test]
-    fn test_overlay_score_turn() {
-        let board = Board::parse(test_board_a()).unwrap();
-        let turn = Turn {
-            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+    #[tokio::test]
+    async fn test_challenge_window() {
+        let options = GameOptions {
+            challenge_window: 30,
+            ..Default::default()
         };
+        let mut game = Game::with_options("game:hello".parse().unwrap(), None, options).unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        let rack: Rack = "QZXLAEI".chars().map(|c| l!(c)).collect::<Vec<_>>().into();
+        game.racks[0] = rack.clone();
+        game.racks[1] = "QUITNRO".chars().map(|c| l!(c)).collect::<Vec<_>>().into();
+        let bag = game.bag.len();
+
+        // words aren't checked as they're played, so a phony goes down...
+        let phony = Turn {
+            tiles: vec![(112, l!('Q')), (113, l!('Z')), (114, l!('X'))],
+        };
+        game.play(phony).await.unwrap();
+        assert_eq!(game.player_index, 1);
+        assert!(matches!(
+            game.challenge(0, unix_now()).await,
+            Err(Error::NothingToChallenge)
+        ));
 
-        let overlay = Overlay {
-            board: &board,
-            turn: &turn,
+        // ...and comes off again when challenged, as a pass
+        assert_eq!(game.challenge(1, unix_now()).await.unwrap(), vec!["QZX"]);
+        assert_eq!(game.racks[0], rack);
+        assert_eq!(game.bag.len(), bag);
+        assert!(game.board.get_tile(&112).is_none());
+        assert!(game.scores[0].is_empty());
+        assert_eq!(game.score_entries(0), vec![vec![ScoreEntry::Pass]]);
+        assert_eq!(game.pass_count, 1);
+        assert_eq!(game.player_index, 1);
+
+        let quit = Turn {
+            tiles: "QUIT"
+                .chars()
+                .enumerate()
+                .map(|(i, c)| (112 + i, l!(c)))
+                .collect(),
         };
+        game.play(quit).await.unwrap();
+        let deadline = game.pending.as_ref().unwrap().deadline;
+        assert_eq!(game.state_for(Some(0))["game"]["challenge_allowed"], true);
+        assert_eq!(game.state_for(Some(1))["game"]["challenge_allowed"], false);
 
-        let scores: HashSet<(String, isize)> = overlay.score().scores.into_iter().collect();
+        // once the window has closed the play is final
+        assert!(matches!(
+            game.challenge(0, deadline + 1).await,
+            Err(Error::ChallengeTooLate)
+        ));
+        assert!(game.pending.is_none());
+        assert_eq!(game.scores[1][0].total(), 26);
 
-        assert_eq!(
-            scores,
-            [("SLAT".to_owned(), 5), ("SAMPLE".to_owned(), 10)]
-                .into_iter()
-                .collect()
-        );
+        // a challenge in time, of a good word, lets the play stand
+        let tile = Turn {
+            tiles: vec![(130, l!('I')), (145, l!('L')), (160, l!('E'))],
+        };
+        game.play(tile).await.unwrap();
+        assert!(!game.finalize_expired(unix_now()));
+        assert!(game.challenge(1, unix_now()).await.unwrap().is_empty());
+        assert!(game.board.get_tile(&160).is_some());
+        assert_eq!(game.scores[0].len(), 1);
+        assert!(game.pending.is_none());
+
+        // without the dictionary, even a phony stands
+        let options = GameOptions {
+            challenge_window: 30,
+            dictionary_enabled: false,
+            ..Default::default()
+        };
+        let mut game = Game::with_options("game:hello".parse().unwrap(), None, options).unwrap();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.racks[0] = rack.clone();
+        let phony = Turn {
+            tiles: vec![(112, l!('Q')), (113, l!('Z')), (114, l!('X'))],
+        };
+        game.play(phony).await.unwrap();
+        assert!(game.challenge(1, unix_now()).await.unwrap().is_empty());
+        assert!(game.board.get_tile(&112).is_some());
     }
 
-    #[test]
-    fn test_board_commit_turn() {
-        let mut board = Board::parse(test_board_a()).unwrap();
-        let turn = Turn {
-            tiles: vec![(111, l!('S')), (126, l!('L')), (156, l!('T'))],
+    #[tokio::test]
+    async fn test_going_out_within_challenge_window() {
+        // Frankie plays out with QUIT, the bag being empty
+        let out = || async {
+            let options = GameOptions {
+                challenge_window: 30,
+                ..Default::default()
+            };
+            let mut game =
+                Game::with_options("game:hello".parse().unwrap(), None, options).unwrap();
+            game.add_player(Player::from("Frankie")).unwrap();
+            game.add_player(Player::from("Ada")).unwrap();
+            game.start().unwrap();
+            game.player_index = 0;
+            game.bag = Bag(vec![]);
+            game.racks[0] = "QUIT".chars().map(|c| l!(c)).collect::<Vec<_>>().into();
+            game.racks[1] = vec![l!('Q'), l!('Z')].into();
+
+            let turn = Turn {
+                tiles: "QUIT"
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| (112 + i, l!(c)))
+                    .collect(),
+            };
+            game.play(turn).await.unwrap();
+            game
         };
 
-        board.commit_turn(&turn).unwrap();
+        // the play went out, but the game goes on while it can be challenged...
+        let mut game = out().await;
+        assert!(!game.is_over());
+        assert_eq!(game.racks[1].len(), 2);
+        let deadline = game.challenge_deadline().unwrap();
 
-        let words: Vec<String> = board.words().map(Into::into).collect();
+        // ...and ends once the window closes
+        assert!(game.finalize_expired(deadline + 1));
+        assert!(game.is_over());
 
-        let expected: Vec<String> = [
-            "HI", "SAMPLE", "AA", "HAPPY", "SLAT", "MAP", "PAYER", "OOZE",
-        ]
-        .into_iter()
-        .map(Into::into)
-        .collect();
+        // or when a challenge fails
+        let mut game = out().await;
+        assert!(game.challenge(1, unix_now()).await.unwrap().is_empty());
+        assert!(game.is_over());
 
-        assert_eq!(words, expected);
+        // the next move would make the play final, so it's refused as the game is over
+        let mut game = out().await;
+        assert!(matches!(game.pass(), Err(Error::GameOver)));
+        assert!(game.is_over());
+        assert!(game.pending.is_none());
     }
 
     #[test]
-    fn test_game_init() {
+    fn test_stalemate() {
         let mut game = test_game();
         game.add_player(Player::from("Frankie")).unwrap();
         game.add_player(Player::from("Ada")).unwrap();
         game.start().unwrap();
+        game.player_index = 0;
 
-        assert_eq!(game.racks.len(), 2);
-        assert_eq!(game.racks[0].len(), 7);
-        assert_eq!(game.racks[1].len(), 7);
-    }
+        // exchanges count as scoreless turns, the same as passes
+        game.exchange_all().unwrap();
+        game.pass().unwrap();
+        game.exchange_all().unwrap();
+        assert!(!game.is_over());
+        game.pass().unwrap();
+        assert!(game.is_over());
 
-    fn test_bag() -> Bag {
-        let bag = vec![
-            l!('Q'),
-            l!('A'),
-            l!('P'),
-            l!('S'),
-            l!('T'),
-            l!('I'),
-            l!('E'),
-            l!('X'),
-            l!('L'),
-            l!('I'),
-            l!('T'),
-            l!('R'),
-            l!('A'),
-            l!('M'),
-            l!('S'),
-        ];
+        // the bag is empty and neither rack can make a word, so both players pass it out
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game.bag = Bag(vec![]);
+        game.racks[0] = vec![l!('Q'), l!('Z'), l!()].into();
+        game.racks[1] = vec![l!('X'), l!('J'), l!('K')].into();
 
-        Bag(bag)
-    }
+        for _ in 0..3 {
+            game.pass().unwrap();
+        }
+        assert!(!game.is_over());
+        game.pass().unwrap();
+        assert!(game.is_over());
+        assert!(matches!(game.pass(), Err(Error::GameOver)));
 
-    fn test_game() -> Game {
-        let channel_id = "game:hello".parse().unwrap();
-        Game::new(channel_id)
+        // everyone loses the value of their own rack, and no one gains anyone else's
+        let totals: Vec<isize> = game
+            .scores
+            .iter()
+            .map(|scores| scores.iter().map(TurnScore::total).sum())
+            .collect();
+        assert_eq!(totals, vec![-20, -21]);
+        assert_eq!(
+            game.scores[0].last().unwrap().scores,
+            vec![(REMAINING_TILES.to_string(), -20)]
+        );
     }
 
-    #[tokio::test]
-    async fn test_game_play() {
+    #[test]
+    fn test_exchange_all() {
         let mut game = test_game();
-        game.bag = test_bag();
         game.add_player(Player::from("Frankie")).unwrap();
         game.add_player(Player::from("Ada")).unwrap();
-
         game.start().unwrap();
         game.player_index = 0;
 
-        assert_eq!(game.racks.len(), 2);
-        assert_eq!(game.racks[0].len(), 7);
-        assert_eq!(game.racks[1].len(), 7);
-
-        // println!("{:#?}", game);
-
-        let turn_a = Turn {
-            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
-        };
-        game.play(turn_a).await.unwrap();
-        // println!("{:#?}", game);
-
-        assert_eq!(
-            game.racks[0],
-            vec![l!('S'), l!('T'), l!('I'), l!('L'), l!('Q')]
-        );
+        assert!(matches!(game.swap(Turn::default()), Err(Error::EmptyTurn)));
 
-        let words: Vec<String> = game.board.words().map(Into::into).collect();
+        // a blank can be exchanged, and goes back into the bag unlettered
+        game.racks[0][0] = l!();
+        game.exchange_all().unwrap();
+        assert_eq!(game.racks[0].len(), RACK_SIZE);
+        assert!(!game
+            .bag
+            .0
+            .iter()
+            .any(|tile| matches!(tile, Tile::Blank(Some(_)))));
+        // the exchange is the player's whole turn; nothing more can be played from it
         assert_eq!(game.player_index, 1);
-        assert_eq!(words, vec!["MAR".to_string()]);
 
         assert_eq!(
-            game.scores[0],
-            vec![TurnScore {
-                scores: vec![("MAR".to_owned(), 10)]
-            }]
+            game.score_entries(0),
+            vec![vec![ScoreEntry::Exchange { count: RACK_SIZE }]]
         );
+        assert_eq!(game.player_stats(0).exchanges, 1);
+        assert_eq!(game.player_stats(0).total, 0);
+        assert_eq!(game.player_stats(1).exchanges, 0);
 
-        let turn_b = Turn {
-            tiles: vec![(126, l!('T')), (127, l!('A')), (128, l!('X'))],
+        // an unlettered blank can be exchanged, but not played
+        let blank = Turn {
+            tiles: vec![(BOARD_CENTER, l!())],
         };
+        assert!(matches!(
+            game.validate_turn(&blank),
+            Err(Error::BlankTileInTurn)
+        ));
+    }
 
-        game.play(turn_b).await.unwrap();
-        // println!("{:#?}", game);
+    #[test]
+    fn test_rack_draw_from() {
+        let mut bag = Bag(vec![l!('E'); 10]);
+        let mut rack: Rack = vec![l!('Q'), l!('A')].into();
+
+        rack.draw_from(&mut bag);
+        assert_eq!(rack.len(), rack.capacity());
+        assert_eq!(rack.iter().filter(|tile| **tile == l!('E')).count(), 5);
+        assert_eq!(bag.len(), 5);
+
+        // a full rack takes nothing more
+        rack.draw_from(&mut bag);
+        assert_eq!(bag.len(), 5);
+
+        // nor does an empty bag have anything to give
+        let mut rack = Rack::default();
+        rack.draw_from(&mut Bag(vec![l!('Z')]));
+        assert_eq!(rack, vec![l!('Z')]);
+
+        // still serialized as a plain list
+        assert_eq!(serde_json::to_value(&rack).unwrap(), json!([{"Char": "Z"}]));
+    }
 
-        assert_eq!(game.racks[1], vec![l!('E'), l!('I'), l!('S'), l!('P')]);
+    #[test]
+    fn test_rack_remove_tile() {
+        let mut rack: Rack = vec![l!('Q'), l!(), l!('A'), l!('Q')].into();
 
-        assert_eq!(
-            game.scores[1],
-            vec![TurnScore {
-                scores: vec![
-                    ("TAX".to_string(), 19),
-                    ("MA".to_string(), 4),
-                    ("AX".to_string(), 17),
-                ]
-            }]
-        );
-        assert_eq!(game.player_index, 0);
+        rack.remove_tile(&l!('Q')).unwrap();
+        assert_eq!(rack, vec![l!(), l!('A'), l!('Q')]);
 
-        let turn_c_err_1 = Turn {
-            tiles: vec![(140, l!('T')), (127, l!('A')), (128, l!('X'))],
-        };
+        // a lettered blank on the board comes off the rack as an unlettered one
+        rack.remove_tile(&lb!('S')).unwrap();
+        assert_eq!(rack, vec![l!('A'), l!('Q')]);
 
         assert!(matches!(
-            game.play(turn_c_err_1).await.unwrap_err(),
-            Error::TurnNotLinear
+            rack.remove_tile(&l!('Z')),
+            Err(Error::NoTileToSpend(Tile::Char('Z')))
         ));
+        assert!(matches!(
+            rack.remove_tile(&lb!('E')),
+            Err(Error::NoTileToSpend(_))
+        ));
+        assert_eq!(rack.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_game_play_rejects_empty_and_overlapping_turns() {
+        let mut game = test_game();
+        game.bag = test_bag();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
 
-        let turn_c_err_2 = Turn {
-            tiles: vec![(140, l!('T')), (141, l!('A')), (142, l!('X'))],
-        };
+        game.start().unwrap();
+        game.player_index = 0;
 
         assert!(matches!(
-            game.play(turn_c_err_2).await.unwrap_err(),
-            Error::NoTileToSpend(l!('A'))
+            game.play(Turn::default()).await.unwrap_err(),
+            Error::EmptyTurn
         ));
+        assert_eq!(game.player_index, 0);
 
-        let turn_c_1 = Turn {
-            tiles: vec![(141, l!('I')), (156, l!('L'))],
+        let turn_a = Turn {
+            tiles: vec![(112, l!('M')), (113, l!('A')), (114, l!('R'))],
         };
+        game.play(turn_a).await.unwrap();
 
-        game.play(turn_c_1).await.unwrap();
-        // println!("{:#?}", game);
-
-        assert_eq!(
-            game.scores[0],
-            vec![
-                TurnScore {
-                    scores: vec![("MAR".to_owned(), 10)]
-                },
-                TurnScore {
-                    scores: vec![("TIL".to_owned(), 3)]
-                }
-            ]
-        );
-
-        let turn_d = Turn {
-            tiles: vec![
-                (169, l!('P')),
-                (170, l!('I')),
-                (171, l!('E')),
-                (172, l!('S')),
-            ],
+        let overlapping = Turn {
+            tiles: vec![(112, l!('E')), (113, l!('I'))],
         };
 
-        game.play(turn_d).await.unwrap();
-        // println!("{:#?}", game);
-
-        assert!(game.is_over());
-
-        assert_eq!(
-            game.scores[0],
-            vec![
-                TurnScore {
-                    scores: vec![("MAR".to_owned(), 10)]
-                },
-                TurnScore {
-                    scores: vec![("TIL".to_owned(), 3)]
-                },
-                TurnScore {
-                    scores: vec![("(remaining tiles)".to_string(), -12)]
-                }
-            ]
-        )
+        assert!(matches!(
+            game.play(overlapping).await.unwrap_err(),
+            Error::SquareOccupied(112)
+        ));
+        assert_eq!(game.player_index, 1);
+        assert!(game.scores[1].is_empty());
     }
 
     #[tokio::test]
@@ -1624,7 +6326,14 @@ mod test {
         assert_eq!(
             game.scores[0],
             vec![TurnScore {
-                scores: vec![("SMART".to_string(), 8)]
+                scores: vec![("SMART".to_string(), 8)],
+                breakdown: vec![ScoredWord {
+                    word: "SMART".to_string(),
+                    base: 4,
+                    letter_bonuses: vec![],
+                    word_multiplier: 2,
+                    total: 8,
+                }],
             }]
         );
 
@@ -1636,15 +6345,14 @@ mod test {
         };
 
         game.play(turn_b).await.unwrap();
+        assert_eq!(game.scores[1].len(), 1);
         assert_eq!(
-            game.scores[1],
-            vec![TurnScore {
-                scores: vec![
-                    ("AX".to_string(), 17),
-                    ("MA".to_string(), 1), // blank M doesn't count
-                    ("AX".to_string(), 17)
-                ]
-            }],
+            game.scores[1][0].scores,
+            vec![
+                ("AX".to_string(), 17),
+                ("MA".to_string(), 1), // blank M doesn't count
+                ("AX".to_string(), 17)
+            ],
         );
     }
 
@@ -1662,6 +6370,547 @@ mod test {
         assert_eq!(sum, 100);
     }
 
+    #[test]
+    fn test_state_for_spectator() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        let player = game.state_for(Some(1));
+        assert_eq!(player["rack"].as_array().unwrap().len(), 7);
+        assert_eq!(player["game"]["spectating"], false);
+
+        let spectator = game.state_for(None);
+        assert!(spectator.get("rack").is_none());
+        assert_eq!(spectator["game"]["spectating"], true);
+    }
+
+    #[test]
+    fn test_spectator_follows_revealed_rack() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        // hidden by default
+        let following = game.spectator_state(Some(1));
+        assert!(following.get("rack").is_none());
+        assert!(following.get("following").is_none());
+
+        game.set_reveal_rack(1, true).unwrap();
+        let following = game.spectator_state(Some(1));
+        assert_eq!(following["rack"], json!(game.racks[1]));
+        assert_eq!(following["following"], 1);
+        assert_eq!(following["game"]["spectating"], true);
+        assert_eq!(following["game"]["revealed"], json!([1]));
+
+        // only the seat that opted in
+        assert!(game.spectator_state(Some(0)).get("rack").is_none());
+        assert!(game.spectator_state(None).get("rack").is_none());
+
+        game.set_reveal_rack(1, false).unwrap();
+        assert!(game.spectator_state(Some(1)).get("rack").is_none());
+        assert!(matches!(
+            game.set_reveal_rack(2, true),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn test_seeded_games_are_reproducible() {
+        let seeded = |seed| {
+            let mut game = Game::with_seed("game:seeded".parse().unwrap(), Some(seed));
+            game.add_player(Player::from("Frankie")).unwrap();
+            game.add_player(Player::from("Ada")).unwrap();
+            game.add_player(Player::from("Grace")).unwrap();
+            game.start().unwrap();
+            game
+        };
+
+        let a = seeded(42);
+        let b = seeded(42);
+
+        assert_eq!(a.bag.0, b.bag.0);
+        assert_eq!(a.racks, b.racks);
+        assert_eq!(a.player_index, b.player_index);
+
+        // survives a round trip through persistence
+        let c: Game = serde_json::from_value(json!(a)).unwrap();
+        assert_eq!(c.seed, Some(42));
+        assert_eq!(c.rng().gen::<u64>(), a.rng().gen::<u64>());
+
+        assert_ne!(a.bag.0, seeded(43).bag.0);
+    }
+
+    #[test]
+    fn test_validate_full() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        assert!(game.validate_full().is_empty());
+
+        game.players.push(Player::from("Ada"));
+        game.player_index = 5;
+        game.racks[0].push(l!('Z'));
+        game.bag.push(lb!('Q'));
+
+        assert_eq!(
+            game.validate_full(),
+            vec![
+                "2 racks for 3 players",
+                "2 score lists for 3 players",
+                "player_index 5 is out of range for 3 players",
+                "duplicate player Ada",
+                "assigned blank [(Q)] found off the board",
+                "expected 2 of tile BLANK, found 3",
+                "expected 1 of tile Z, found 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_truncated_data() {
+        let data = include_str!("../../bad_data.json");
+        let mut value: serde_json::Value = serde_json::from_str(data).unwrap();
+        value.as_object_mut().unwrap().remove("racks");
+
+        assert!(matches!(
+            persistence::load(10, value).unwrap_err(),
+            Error::Deserialize(..)
+        ));
+
+        let game = persistence::load(10, serde_json::from_str(data).unwrap()).unwrap();
+        assert_eq!(game.pkid, Some(10));
+    }
+
+    #[test]
+    fn test_load_migrates_older_games() {
+        // saved before options, the seed, history, tile sets, forfeits and redraws were added,
+        // and missing the second player's scores
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../../bad_data.json")).unwrap();
+        value["scores"].as_array_mut().unwrap().pop();
+
+        let game = persistence::load(10, value).unwrap();
+        assert_eq!(game.scores.len(), game.players.len());
+        assert!(game.scores[1].is_empty());
+        assert_eq!(game.options, GameOptions::default());
+        assert_eq!(game.seed, None);
+        assert!(game.history.is_empty());
+        assert!(game.tile_set.is_none());
+        assert_eq!(game.forfeited, None);
+        assert!(game.redrawn.is_empty());
+        assert_eq!(game.move_count, 0);
+
+        // the current format is left as it is
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        let current = serde_json::to_value(&game).unwrap();
+        assert_eq!(persistence::migrate_blob(current.clone()), current);
+        assert!(persistence::load(11, current).is_ok());
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_games() {
+        let data: serde_json::Value =
+            serde_json::from_str(include_str!("../../bad_data.json")).unwrap();
+
+        let mut extra_rack = data.clone();
+        extra_rack["racks"].as_array_mut().unwrap().push(json!([]));
+        match persistence::load(10, extra_rack) {
+            Err(Error::CorruptGame(message)) => assert_eq!(message, "3 racks for 2 players"),
+            other => panic!("expected a corrupt game, got {:?}", other.map(|g| g.name)),
+        }
+
+        // a missing rack isn't padded like a missing score list
+        let mut missing_rack = data.clone();
+        missing_rack["racks"].as_array_mut().unwrap().pop();
+        match persistence::load(10, missing_rack) {
+            Err(Error::CorruptGame(message)) => assert_eq!(message, "1 racks for 2 players"),
+            other => panic!("expected a corrupt game, got {:?}", other.map(|g| g.name)),
+        }
+
+        let mut misaligned = data;
+        misaligned["scores"].as_array_mut().unwrap().push(json!([]));
+        misaligned["player_index"] = json!(2);
+        match persistence::load(10, misaligned) {
+            Err(e @ Error::CorruptGame(_)) => assert_eq!(
+                e.to_string(),
+                "This game can't be loaded: 3 score lists for 2 players; \
+                 player_index 2 is out of range for 2 players"
+            ),
+            other => panic!("expected a corrupt game, got {:?}", other.map(|g| g.name)),
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_tiles() {
+        let data: serde_json::Value =
+            serde_json::from_str(include_str!("../../bad_data.json")).unwrap();
+
+        let mut unknown = data.clone();
+        unknown["racks"][1][2] = json!({ "Char": "Ä" });
+        unknown["board"][0] = json!({ "Tile": { "Blank": "1" } });
+        match persistence::load(10, unknown) {
+            Err(Error::CorruptGame(message)) => assert_eq!(
+                message,
+                "unknown tile 'Ä' in rack 1; unknown tile '1' on square 0"
+            ),
+            other => panic!("expected a corrupt game, got {:?}", other.map(|g| g.name)),
+        }
+
+        let mut unlettered = data;
+        unlettered["board"][0] = json!({ "Tile": { "Blank": null } });
+        assert!(matches!(
+            persistence::load(10, unlettered),
+            Err(Error::CorruptGame(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replaced_seat_is_played_by_the_bot() {
+        let board = ["15"; 15].join("/");
+        let mut game = Game::from_position_string(
+            "game:test".parse().unwrap(),
+            &format!("{} 0 QUITAEL/SMARTXE", board),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            game.replace_with_bot(2),
+            Err(Error::IndexOutOfBounds)
+        ));
+        game.replace_with_bot(1).unwrap();
+        game.replace_with_bot(1).unwrap();
+        assert!(game.is_bot(1));
+        assert!(matches!(
+            game.replace_with_bot(0),
+            Err(Error::CannotReplace(_))
+        ));
+        assert_eq!(game.player_state(None)["game"]["bots"], json!([1]));
+
+        // nothing for the bot to do on someone else's turn
+        assert!(game.play_bots().await.unwrap().is_empty());
+
+        game.play(Turn {
+            tiles: vec![
+                (112, l!('Q')),
+                (113, l!('U')),
+                (114, l!('I')),
+                (115, l!('T')),
+            ],
+        })
+        .await
+        .unwrap();
+        let score_before = game.scores[1].len();
+        let moves = game.play_bots().await.unwrap();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, 1);
+        assert!(moves[0].1.is_some());
+        assert_eq!(game.player_index, 0);
+        assert_eq!(game.scores[1].len(), score_before + 1);
+        assert_eq!(game.history.last().unwrap().player_index, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_game_with_ordered_bag() {
+        let mut game = scripted_game();
+
+        // dealt in order, leaving the bag empty
+        assert_eq!(
+            game.racks[0],
+            "QUITSAX".chars().map(|c| l!(c)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            game.racks[1],
+            "MELTRED".chars().map(|c| l!(c)).collect::<Vec<_>>()
+        );
+        assert!(game.bag_empty());
+
+        play_script(&mut game).await;
+
+        // QUIT (26) + MAX (12) + QUITS (15); MELT (12) less TRED left on the rack (5)
+        assert!(game.is_over());
+        let standings = game.final_standings().unwrap();
+        let standings: Vec<(&str, isize)> = standings
+            .iter()
+            .map(|standing| (standing.player.as_str(), standing.score))
+            .collect();
+        assert_eq!(standings, vec![("Frankie", 53), ("Ada", 7)]);
+    }
+
+    // Frankie and Ada, dealt QUITSAX and MELTRED from a bag holding nothing else; Frankie moves
+    // first
+    fn scripted_game() -> Game {
+        let tiles = "QUITSAX".chars().chain("MELTRED".chars()).map(|c| l!(c));
+        let mut game = Game::with_bag(
+            "game:scripted".parse().unwrap(),
+            None,
+            Bag::from_ordered(tiles.collect()),
+        );
+        game.options.dictionary_enabled = false;
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.player_index = 0;
+        game
+    }
+
+    // QUIT, MELT, MAX, a pass, then QUITS to go out
+    async fn play_script(game: &mut Game) {
+        let script = [
+            vec![(112, 'Q'), (113, 'U'), (114, 'I'), (115, 'T')], // QUIT
+            vec![(70, 'M'), (85, 'E'), (100, 'L')],               // MELT
+            vec![(71, 'A'), (72, 'X')],                           // MAX
+        ];
+        for tiles in script {
+            let tiles = tiles.into_iter().map(|(i, c)| (i, l!(c))).collect();
+            game.play(Turn { tiles }).await.unwrap();
+        }
+        game.pass().unwrap();
+        game.play(Turn {
+            tiles: vec![(116, l!('S'))], // QUITS, going out
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_move_count() {
+        let mut game = scripted_game();
+        assert_eq!(game.move_count(), 0);
+
+        // rejected moves don't count
+        assert!(game
+            .play(Turn {
+                tiles: vec![(0, l!('Q'))],
+            })
+            .await
+            .is_err());
+        assert_eq!(game.move_count(), 0);
+
+        play_script(&mut game).await;
+        assert_eq!(game.move_count(), 5);
+        assert_eq!(game.player_state(None)["game"]["move_count"], 5);
+
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        assert!(game.check_move_count(Some(0)).is_ok());
+        game.pass().unwrap();
+        game.exchange_all().unwrap();
+        assert_eq!(game.move_count(), 2);
+
+        // the count a move is sent with is checked against the game's
+        assert!(game.check_move_count(Some(2)).is_ok());
+        for stale in [Some(0), Some(1), Some(3), None] {
+            assert!(matches!(
+                game.check_move_count(stale),
+                Err(Error::OutOfOrderTurn)
+            ));
+        }
+
+        // counted from the history for games saved before
+        let mut saved = serde_json::to_value(&game).unwrap();
+        saved.as_object_mut().unwrap().remove("move_count");
+        let reloaded = persistence::load(12, saved).unwrap();
+        assert_eq!(reloaded.move_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_scoresheet_csv() {
+        let mut game = scripted_game();
+        game.players[1] = Player::from("Ada, \"the Countess\"");
+        play_script(&mut game).await;
+
+        let csv = game.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "turn,player,words,score",
+                "1,Frankie,QUIT,26",
+                "2,\"Ada, \"\"the Countess\"\"\",MELT,12",
+                "3,Frankie,MAX,12",
+                "4,\"Ada, \"\"the Countess\"\"\",(pass),0",
+                "5,Frankie,QUITS,15",
+                ",\"Ada, \"\"the Countess\"\"\",(remaining tiles),-5",
+                ",Frankie,(total),53",
+                ",\"Ada, \"\"the Countess\"\"\",(total),7",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scoresheet_csv_in_move_order() {
+        // the second seat goes first
+        let mut game = scripted_game();
+        game.player_index = 1;
+        game.pass().unwrap();
+        for tiles in [
+            vec![(112, 'Q'), (113, 'U'), (114, 'I'), (115, 'T')], // QUIT
+            vec![(70, 'M'), (85, 'E'), (100, 'L')],               // MELT
+        ] {
+            let tiles = tiles.into_iter().map(|(i, c)| (i, l!(c))).collect();
+            game.play(Turn { tiles }).await.unwrap();
+        }
+
+        let csv = game.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "turn,player,words,score",
+                "1,Ada,(pass),0",
+                "2,Frankie,QUIT,26",
+                "3,Ada,MELT,12",
+                ",Frankie,(total),26",
+                ",Ada,(total),12",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remaining_blanks() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.racks[0] = vec![l!('Q')].into();
+        game.racks[1] = vec![l!('E')].into();
+        // a stray lettered blank counts as a blank, not as an S
+        game.bag = Bag(vec![l!('S'), l!(), lb!('S'), l!('A')]);
+
+        assert_eq!(
+            game.remaining_tiles(Some(&PlayerIndex(0))),
+            vec![
+                ("A".to_string(), 1),
+                ("E".to_string(), 1),
+                ("S".to_string(), 1),
+                ("BLANK".to_string(), 2),
+            ]
+        );
+
+        let state = game.state_for(Some(0));
+        assert_eq!(state["blanks_remaining"], 2);
+        game.bag = Bag(vec![l!('S')]);
+        assert_eq!(game.state_for(None)["blanks_remaining"], 0);
+    }
+
+    fn corrupted_game() -> Game {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+
+        let rack = game.racks.pop().unwrap();
+        game.bag.0.extend(rack);
+        game.scores.pop();
+        game.player_index = 2;
+
+        let blank = game.bag.0.iter_mut().find(|tile| **tile == l!()).unwrap();
+        *blank = lb!('Q');
+
+        game
+    }
+
+    #[test]
+    fn test_tile_conservation() {
+        let mut game = test_game();
+        game.add_player(Player::from("Frankie")).unwrap();
+        game.add_player(Player::from("Ada")).unwrap();
+        game.start().unwrap();
+        game.assert_tile_conservation();
+
+        // a tile duplicated into a rack
+        let tile = game.racks[0][0];
+        game.racks[1].push(tile);
+        let result = std::panic::catch_unwind(|| game.assert_tile_conservation());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_repair() {
+        let mut game = corrupted_game();
+        assert_eq!(game.validate_full().len(), 4);
+
+        assert_eq!(
+            game.repair(),
+            vec![
+                "reset assigned blank (Q) off the board",
+                "added missing score list 1",
+                "added missing rack 1",
+                "reset out-of-range player_index 2",
+            ]
+        );
+
+        assert!(game.validate_full().is_empty());
+        assert_eq!(game.racks[1].len(), 7);
+        assert_eq!(game.player_index, 0);
+        assert!(game.repair().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repair_persisted_game() {
+        let pool = test_pool().await;
+        let mut tx = pool.begin().await.unwrap();
+
+        let mut game = corrupted_game();
+        game.name = "test_repair_persisted_game".to_string();
+        game.persist(&mut tx).await.unwrap();
+
+        let mut fetched = persistence::fetch(&game.name, &mut tx).await.unwrap();
+        assert!(!fetched.validate_full().is_empty());
+
+        fetched.repair();
+        fetched.persist(&mut tx).await.unwrap();
+
+        let fetched = persistence::fetch(&game.name, &mut tx).await.unwrap();
+        assert!(fetched.validate_full().is_empty());
+
+        tx.rollback().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_recent_prefers_finished_games() {
+        // nothing is listening here, so any database round trip would fail
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost:1/scrabble_rs_unreachable")
+            .unwrap();
+
+        let mut game = Game::new("game:retained-test".parse().unwrap());
+        game.add_player(Player::from("Frankie")).unwrap();
+
+        persistence::retain_finished(&game);
+        assert!(persistence::fetch_recent("retained-test", &pool)
+            .await
+            .is_err());
+
+        game.state = State::Over;
+        persistence::retain_finished(&game);
+
+        let fetched = persistence::fetch_recent("retained-test", &pool)
+            .await
+            .unwrap();
+        assert!(fetched.is_over());
+        assert_eq!(fetched.players, vec![Player::from("Frankie")]);
+    }
+
+    async fn test_pool() -> PgPool {
+        sqlx::postgres::PgPoolOptions::new()
+            .max_connections(5)
+            .connect("postgres://localhost/scrabble_rs_test")
+            .await
+            .unwrap()
+    }
+
     #[test]
     fn test_bad_data() {
         let data = include_str!("../../bad_data.json");
@@ -1671,12 +6920,19 @@ mod test {
             tiles: vec![(105, l!('Q')), (106, l!('U')), (108, l!('D'))],
         };
 
-        let proposed = game.propose(&turn);
+        let proposed = game.propose(&turn, 1).unwrap();
 
         assert_eq!(
             proposed,
             TurnScore {
-                scores: vec![("QUAD".to_string(), 48)]
+                scores: vec![("QUAD".to_string(), 48)],
+                breakdown: vec![ScoredWord {
+                    word: "QUAD".to_string(),
+                    base: 14,
+                    letter_bonuses: vec![(108, 2)],
+                    word_multiplier: 3,
+                    total: 48,
+                }],
             }
         );
     }
@@ -0,0 +1,265 @@
+// Brute-force move generation: every bounded segment of every row and column is matched against
+// the word list, then the remaining cross-words are checked. Far slower than a proper move
+// generator (GADDAG etc.), so it's only run off the async runtime: for post-game analysis, the
+// bot's moves and the assist option's hints.
+use super::{
+    Board, Error, Game, GetChar, Overlay, State, Tile, TileSet, Turn, TurnScore, BOARD_SIZE,
+};
+use crate::dictionary::Dictionary;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    ops::ControlFlow,
+};
+
+impl Game {
+    // highest-scoring legal play of tiles from `rack` on `board`, under this game's scoring rules
+    pub(crate) fn best_turn(
+        &self,
+        board: &Board,
+        rack: &[Tile],
+        dictionary: &Dictionary,
+    ) -> Option<(Turn, TurnScore)> {
+        legal_turns(board, rack, dictionary, self.tile_set.as_ref())
+            .into_iter()
+            .map(|turn| {
+                let score = self.score_overlay(&Overlay { board, turn: &turn });
+                (turn, score)
+            })
+            .max_by_key(|(_, score)| score.total())
+    }
+
+    // Whether any word can be played from the player's rack, as the game is now; worked out off
+    // the async runtime, as move generation is slow. The future doesn't borrow the game, so it can
+    // be left to run on its own task. Without the word list, any tile could be played.
+    pub(crate) fn has_legal_move(
+        &self,
+        player_index: usize,
+    ) -> impl Future<Output = bool> + Send + 'static {
+        let rack = self.racks.get(player_index).cloned();
+        let dictionary_enabled = self.options.dictionary_enabled;
+        let board = self.board.clone();
+        let tile_set = self.tile_set.clone();
+
+        async move {
+            let rack = match rack {
+                Some(rack) if !rack.is_empty() => rack,
+                _ => return false,
+            };
+            if !dictionary_enabled {
+                return true;
+            }
+
+            let dictionary = crate::dictionary::dictionary().await;
+            tokio::task::spawn_blocking(move || {
+                any_legal_turn(&board, &rack, dictionary, tile_set.as_ref())
+            })
+            .await
+            .unwrap_or(true)
+        }
+    }
+
+    // Makes the best play for whoever is to move. With nothing to play, the whole rack is
+    // swapped, or the turn passed once the bag is too low to swap. Returns the turn played.
+    pub(crate) async fn play_best(&mut self) -> Result<Option<Turn>, Error> {
+        self.ensure_playable()?;
+
+        let dictionary = crate::dictionary::dictionary().await;
+        let game = self.clone();
+        let best = tokio::task::spawn_blocking(move || {
+            let rack = &game.racks[game.player_index];
+            game.best_turn(&game.board, rack, dictionary)
+        })
+        .await
+        .map_err(|_| Error::Unknown)?;
+
+        if let Some((turn, _)) = best {
+            self.play(turn.clone()).await?;
+            return Ok(Some(turn));
+        }
+
+        if self.swap_allowed() {
+            self.exchange_all()?;
+        } else {
+            self.pass()?;
+        }
+
+        Ok(None)
+    }
+
+    // Moves for the seats handed over to the bot, until it's a person's turn or the game is over.
+    // After a play that went out the bot waits, as the play can still be challenged. Returns each
+    // bot move's seat and the turn played (`None` for an exchange or pass).
+    pub(crate) async fn play_bots(&mut self) -> Result<Vec<(usize, Option<Turn>)>, Error> {
+        let mut moves = vec![];
+
+        while self.state == State::Started && self.is_bot(self.player_index) && !self.pending_out()
+        {
+            let player_index = self.player_index;
+            moves.push((player_index, self.play_best().await?));
+        }
+
+        Ok(moves)
+    }
+}
+
+// Every legal placement of tiles from `rack` on `board`. A single tile forming words in both
+// directions may be listed twice.
+pub(crate) fn legal_turns(
+    board: &Board,
+    rack: &[Tile],
+    dictionary: &Dictionary,
+    tile_set: Option<&TileSet>,
+) -> Vec<Turn> {
+    let mut turns = vec![];
+    let _ = find_turns(board, rack, dictionary, tile_set, |turn| {
+        turns.push(turn);
+        ControlFlow::Continue(())
+    });
+
+    turns
+}
+
+// Stops at the first legal placement found
+fn any_legal_turn(
+    board: &Board,
+    rack: &[Tile],
+    dictionary: &Dictionary,
+    tile_set: Option<&TileSet>,
+) -> bool {
+    find_turns(
+        board,
+        rack,
+        dictionary,
+        tile_set,
+        |_| ControlFlow::Break(()),
+    )
+    .is_break()
+}
+
+// Hands each legal placement to `found`, until it says to stop
+fn find_turns(
+    board: &Board,
+    rack: &[Tile],
+    dictionary: &Dictionary,
+    tile_set: Option<&TileSet>,
+    mut found: impl FnMut(Turn) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut by_length: HashMap<usize, Vec<&str>> = HashMap::new();
+    for word in dictionary.iter() {
+        by_length
+            .entry(word.chars().count())
+            .or_default()
+            .push(word.as_str());
+    }
+
+    let anchors: HashSet<usize> = board.anchors().into_iter().collect();
+
+    for vertical in [false, true] {
+        for line in 0..BOARD_SIZE {
+            let index_at = |pos: usize| {
+                if vertical {
+                    pos * BOARD_SIZE + line
+                } else {
+                    line * BOARD_SIZE + pos
+                }
+            };
+
+            for start in 0..BOARD_SIZE {
+                // the word would extend backwards into an existing tile
+                if start > 0 && board.get_tile(&index_at(start - 1)).is_some() {
+                    continue;
+                }
+
+                for end in (start + 2)..=BOARD_SIZE {
+                    if end < BOARD_SIZE && board.get_tile(&index_at(end)).is_some() {
+                        continue;
+                    }
+
+                    let indexes: Vec<usize> = (start..end).map(index_at).collect();
+                    let pattern: Vec<Option<char>> =
+                        indexes.iter().map(|index| board.get_char(*index)).collect();
+                    let open = pattern.iter().filter(|c| c.is_none()).count();
+
+                    if open == 0 || open > rack.len() {
+                        continue;
+                    }
+
+                    if !is_connected(&indexes, &pattern, &anchors) {
+                        continue;
+                    }
+
+                    for word in by_length.get(&indexes.len()).into_iter().flatten() {
+                        if let Some(tiles) = fill(word, &indexes, &pattern, rack) {
+                            let turn = Turn { tiles };
+
+                            if cross_words_legal(board, &turn, dictionary, tile_set)
+                                && found(turn).is_break()
+                            {
+                                return ControlFlow::Break(());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ControlFlow::Continue(())
+}
+
+// Either plays through an existing tile or covers an anchor (the center, on an empty board)
+fn is_connected(indexes: &[usize], pattern: &[Option<char>], anchors: &HashSet<usize>) -> bool {
+    pattern.iter().any(Option::is_some) || indexes.iter().any(|index| anchors.contains(index))
+}
+
+// Tiles from the rack that spell `word` across the open squares of the segment, preferring
+// lettered tiles over blanks.
+fn fill(
+    word: &str,
+    indexes: &[usize],
+    pattern: &[Option<char>],
+    rack: &[Tile],
+) -> Option<Vec<(usize, Tile)>> {
+    if !word
+        .chars()
+        .zip(pattern)
+        .all(|(c, fixed)| fixed.is_none() || *fixed == Some(c))
+    {
+        return None;
+    }
+
+    let mut available = rack.to_vec();
+    let mut tiles = vec![];
+
+    for ((c, index), fixed) in word.chars().zip(indexes).zip(pattern) {
+        if fixed.is_some() {
+            continue;
+        }
+
+        let tile = if let Some(pos) = available.iter().position(|t| *t == Tile::Char(c)) {
+            available.swap_remove(pos)
+        } else if let Some(pos) = available.iter().position(|t| *t == Tile::Blank(None)) {
+            available.swap_remove(pos);
+            Tile::Blank(Some(c))
+        } else {
+            return None;
+        };
+
+        tiles.push((*index, tile));
+    }
+
+    Some(tiles)
+}
+
+fn cross_words_legal(
+    board: &Board,
+    turn: &Turn,
+    dictionary: &Dictionary,
+    tile_set: Option<&TileSet>,
+) -> bool {
+    Overlay { board, turn }
+        .new_words()
+        .iter()
+        .all(|word| dictionary.contains(&word.string, tile_set))
+}
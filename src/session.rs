@@ -3,12 +3,12 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::async_trait;
 use axum::extract::{FromRequest, RequestParts};
 use axum::http::{Request, StatusCode, Uri};
-use axum::response::{Redirect, Response};
+use axum::response::{IntoResponse, Redirect, Response};
 use cookie::{Cookie, CookieJar, Key};
 use parking_lot::Mutex;
 use pin_project::pin_project;
@@ -31,13 +31,18 @@ pub struct Session {
     csrf_token: String,
     #[serde(default)]
     login_redirect: Option<String>,
+    // Unix millis; slides forward on each authenticated request. Sessions from before this was
+    // added have none until their next request.
+    #[serde(default)]
+    expires_at: Option<u64>,
+    // the user's token version when this was minted; stale once they log out anywhere
+    #[serde(default)]
+    token_version: i32,
 }
 
 impl From<User> for Session {
     fn from(user: User) -> Self {
-        let mut session = Session::new();
-        session.user_id = Some(user.id);
-        session
+        Session::from(&user)
     }
 }
 
@@ -45,6 +50,8 @@ impl From<&User> for Session {
     fn from(user: &User) -> Self {
         let mut session = Session::new();
         session.user_id = Some(user.id);
+        session.token_version = user.token_version;
+        session.renew(now_millis());
         session
     }
 }
@@ -55,12 +62,34 @@ impl Session {
             user_id: None,
             csrf_token: new_csrf_token(),
             login_redirect: None,
+            expires_at: None,
+            token_version: 0,
         }
     }
 
     pub fn as_json(&self) -> serde_json::Value {
         json!(self)
     }
+
+    fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+
+    fn renew(&mut self, now: u64) {
+        self.expires_at = Some(now + SESSION_TTL.as_millis() as u64);
+    }
+
+    // Whether this (read from a token) still speaks for `user`
+    pub fn is_current_for(&self, user: &User) -> bool {
+        self.user_id == Some(user.id) && self.token_version == user.token_version
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
 }
 
 fn new_csrf_token() -> String {
@@ -92,7 +121,9 @@ impl Session {
             .value()
             .to_string();
 
-        serde_json::from_str(&value).ok()?
+        let session: Session = serde_json::from_str(&value).ok()?;
+
+        Some(session).filter(|session| !session.is_expired(now_millis()))
     }
 }
 
@@ -106,6 +137,13 @@ lazy_static::lazy_static! {
     pub static ref SECRET: String = std::env::var("SECRET_KEY_BASE").unwrap_or_else(|_|
                 "FIXME-the-is-the-default-development-key-and-should-not-be-used!".to_string());
     pub static ref KEY: Key = Key::from(secret_key_base());
+    // how long a session lasts without a request (SESSION_TTL_SECS, default two weeks)
+    static ref SESSION_TTL: Duration = Duration::from_secs(
+        std::env::var("SESSION_TTL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(14 * 24 * 60 * 60)
+    );
 }
 
 fn secret_key_base() -> &'static [u8] {
@@ -146,13 +184,60 @@ where
             return Err(redirect_to_login(req, &session));
         }
 
-        User::find(user_id.unwrap(), pool)
+        let user = User::find(user_id.unwrap(), pool)
+            .await
+            .map_err(|_| redirect_to_login(req, &session))?;
+
+        // the cookie was revoked since it was set (the user logged out in another browser)
+        if !session.is_current_for(&user) {
+            session.set_user_id(None);
+            return Err(redirect_to_login(req, &session));
+        }
+
+        Ok(CurrentUser(user))
+    }
+}
+
+// Like `CurrentUser`, but responds with 403 Forbidden unless the user is an admin
+pub(crate) struct RequireAdmin(pub User);
+
+#[async_trait]
+impl<B> FromRequest<B> for RequireAdmin
+where
+    B: Send,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let CurrentUser(user) = CurrentUser::from_request(req)
             .await
-            .map(CurrentUser)
-            .map_err(|_| redirect_to_login(req, &session))
+            .map_err(IntoResponse::into_response)?;
+
+        require_admin(user).map_err(IntoResponse::into_response)
     }
 }
 
+fn require_admin(user: User) -> Result<RequireAdmin, StatusCode> {
+    if user.is_admin() {
+        Ok(RequireAdmin(user))
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Accepts only a local path (a single leading `/`), so a stored redirect can't send the user
+/// off-site: `//host` and `/\host` are protocol-relative to browsers, and anything else has a
+/// scheme or is relative.
+pub(crate) fn sanitize_redirect(path: &str) -> Option<String> {
+    let rest = path.strip_prefix('/')?;
+
+    if rest.starts_with('/') || rest.starts_with('\\') || path.chars().any(char::is_control) {
+        return None;
+    }
+
+    Some(path.to_string())
+}
+
 fn redirect_to_login<B>(req: &RequestParts<B>, session: &SessionManager) -> Redirect {
     session.set_login_redirect(Some(req.uri().to_string()));
 
@@ -176,7 +261,8 @@ pub(crate) struct SessionManagerMiddleware<S> {
 }
 
 // FIXME: make generic for Serialize
-// FIXME: track changes
+// Changes are tracked by hashing the session; the cookie is only written when the hash differs
+// from the one the request started with.
 #[derive(Clone, Debug)]
 pub struct SessionManager {
     inner: Arc<Mutex<Session>>,
@@ -209,10 +295,9 @@ where
                 SESSION_COOKIE_NAME,
                 serde_json::to_string(&this.session.as_json()).unwrap(),
             )
-            .max_age(Duration::from_secs(31536000).try_into().unwrap())
+            .max_age((*SESSION_TTL).try_into().unwrap())
             .path("/")
             .finish();
-            // FIXME: only if changed
             let jar = this.cookies.private(key());
 
             jar.add(cookie);
@@ -242,19 +327,12 @@ where
 
         let key = Key::from(SECRET.as_bytes());
 
-        let mut session_was_new = false;
-        let session: Session = match cookies.private(&key).get(SESSION_COOKIE_NAME) {
-            Some(cookie) => serde_json::from_str(cookie.value()).unwrap(),
-            None => {
-                session_was_new = true;
-                Session::new()
-            }
-        };
+        let session: Option<Session> = cookies
+            .private(&key)
+            .get(SESSION_COOKIE_NAME)
+            .map(|cookie| serde_json::from_str(cookie.value()).unwrap());
 
-        let mut session_manager = SessionManager::new(session);
-        if session_was_new {
-            session_manager.hash = 0; // force cookie to be set
-        }
+        let session_manager = resume_session(session, now_millis());
 
         head.extensions.insert(session_manager.clone());
 
@@ -266,12 +344,138 @@ where
     }
 }
 
+// A fresh (logged out) session if there's none or it has expired. Otherwise an authenticated
+// session's expiry slides forward, which marks it changed so the cookie is rewritten. A new
+// session only gets a cookie if something is set on it during the request.
+fn resume_session(session: Option<Session>, now: u64) -> SessionManager {
+    match session {
+        Some(session) if session.is_expired(now) => {
+            let mut session_manager = SessionManager::new(Session::new());
+            session_manager.hash = 0; // force the stale cookie to be replaced
+            session_manager
+        }
+        Some(session) => {
+            let session_manager = SessionManager::new(session);
+            if session_manager.user_id().is_some() {
+                session_manager.inner.lock().renew(now);
+            }
+
+            session_manager
+        }
+        None => SessionManager::new(Session::new()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use cookie::{Cookie, CookieJar};
 
     use super::*;
 
+    #[test]
+    fn test_require_admin() {
+        let rejected = require_admin(User::for_test("frankie", false));
+        assert!(matches!(rejected, Err(StatusCode::FORBIDDEN)));
+
+        let RequireAdmin(admin) = require_admin(User::for_test("ada", true)).unwrap();
+        assert_eq!(admin.username, "ada");
+    }
+
+    #[test]
+    fn test_expired_session_is_not_authenticated() {
+        let now = now_millis();
+        let mut session = Session::from(&User::for_test("ada", false));
+        assert!(!session.is_expired(now));
+        assert!(Session::read_token(session.token()).is_some());
+
+        session.expires_at = Some(now - 1);
+        assert!(Session::read_token(session.token()).is_none());
+
+        let session_manager = resume_session(Some(session), now);
+        assert_eq!(session_manager.user_id(), None);
+        assert!(session_manager.has_changed());
+    }
+
+    #[test]
+    fn test_token_revoked_by_logout() {
+        let mut user = User::for_test("ada", false);
+        let token = Session::from(&user).token();
+
+        let session = Session::read_token(token.clone()).unwrap();
+        assert!(session.is_current_for(&user));
+
+        // as bumped by `User::revoke_tokens` on logout
+        user.token_version += 1;
+        let session = Session::read_token(token).unwrap();
+        assert!(!session.is_current_for(&user));
+        assert!(Session::from(&user).is_current_for(&user));
+    }
+
+    #[test]
+    fn test_cookie_revoked_by_logout_elsewhere() {
+        let mut user = User::for_test("ada", false);
+        let session_manager = SessionManager::new(Session::new());
+        session_manager.set_user(&user);
+        assert!(session_manager.is_current_for(&user));
+
+        // the token minted for the game page carries the cookie's version
+        let token = Session::read_token(session_manager.token()).unwrap();
+        assert!(token.is_current_for(&user));
+
+        // logging out in another browser bumps the version
+        user.token_version += 1;
+        assert!(!session_manager.is_current_for(&user));
+        assert!(!token.is_current_for(&user));
+    }
+
+    #[test]
+    fn test_session_expiry_slides() {
+        let now = now_millis();
+        let mut session = Session::new();
+        session.user_id = Some(1);
+        session.renew(now);
+
+        let session_manager = resume_session(Some(session), now + 1000);
+        assert_eq!(session_manager.user_id(), Some(1));
+        assert!(session_manager.has_changed());
+        assert_eq!(
+            session_manager.inner.lock().expires_at,
+            Some(now + 1000 + SESSION_TTL.as_millis() as u64)
+        );
+
+        // anonymous sessions aren't renewed
+        let session_manager = resume_session(Some(Session::new()), now);
+        assert!(!session_manager.has_changed());
+    }
+
+    #[test]
+    fn test_new_session_is_only_written_when_changed() {
+        let session_manager = resume_session(None, now_millis());
+        assert!(!session_manager.has_changed());
+        session_manager.csrf_token();
+        assert!(!session_manager.has_changed());
+
+        session_manager.set_login_redirect(Some("/play/abc".into()));
+        assert!(session_manager.has_changed());
+
+        let session_manager = resume_session(None, now_millis());
+        session_manager.set_user_id(Some(1));
+        assert!(session_manager.has_changed());
+    }
+
+    #[test]
+    fn test_sanitize_redirect() {
+        assert_eq!(sanitize_redirect("/play/abc"), Some("/play/abc".into()));
+        assert_eq!(sanitize_redirect("/"), Some("/".into()));
+
+        assert_eq!(sanitize_redirect("//evil.com"), None);
+        assert_eq!(sanitize_redirect("http://evil.com"), None);
+        assert_eq!(sanitize_redirect("/\\evil.com"), None);
+        assert_eq!(sanitize_redirect("/play\n/abc"), None);
+        assert_eq!(sanitize_redirect("play/abc"), None);
+        assert_eq!(sanitize_redirect(""), None);
+    }
+
     #[test]
     fn test_private_jar() {
         let value = "secret-thing-here";
@@ -289,8 +493,8 @@ mod tests {
 
         let decrypted = new_jar.private(&key).get(SESSION_COOKIE_NAME).unwrap();
 
-        dbg!(encrypted.to_string());
-        dbg!(decrypted.to_string());
+        assert_ne!(encrypted.value(), value);
+        assert_eq!(decrypted.value(), value);
     }
 }
 impl SessionManager {
@@ -317,12 +521,33 @@ impl SessionManager {
     pub(crate) fn set_user_id(&self, id: Option<i64>) {
         let mut inner = self.inner.lock();
         inner.user_id = id;
+
+        if id.is_some() {
+            inner.renew(now_millis());
+        }
+    }
+
+    // Logs `user` in, as of their current token version
+    pub(crate) fn set_user(&self, user: &User) {
+        let mut inner = self.inner.lock();
+        inner.user_id = Some(user.id);
+        inner.token_version = user.token_version;
+        inner.renew(now_millis());
     }
 
     pub(crate) fn user_id(&self) -> Option<i64> {
         self.inner.lock().user_id
     }
 
+    pub(crate) fn is_current_for(&self, user: &User) -> bool {
+        self.inner.lock().is_current_for(user)
+    }
+
+    // The session as a token for the game socket, with the same user and token version
+    pub(crate) fn token(&self) -> String {
+        self.inner.lock().token()
+    }
+
     pub(crate) fn current_hash(&self) -> u64 {
         let locked = self.inner.lock();
         Self::hash_session(&locked)
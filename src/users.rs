@@ -5,6 +5,9 @@ pub struct User {
     pub id: i64,
     pub username: String,
     hashed_password: String,
+    is_admin: bool,
+    // bumped on logout, revoking the session cookies and websocket tokens minted before
+    pub token_version: i32,
 }
 
 #[derive(Debug)]
@@ -21,16 +24,32 @@ impl std::fmt::Display for Error {
 }
 
 impl User {
+    pub fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+
+    #[cfg(test)]
+    pub(crate) fn for_test(username: &str, is_admin: bool) -> Self {
+        User {
+            id: 0,
+            username: username.to_string(),
+            hashed_password: String::new(),
+            is_admin,
+            token_version: 0,
+        }
+    }
+
     pub async fn find<'a, E>(id: i64, db: E) -> Result<User, Error>
     where
         E: PgExecutor<'a>,
     {
-        let user: User =
-            sqlx::query_as("SELECT id, username, hashed_password from users WHERE id = $1;")
-                .bind(id)
-                .fetch_one(db)
-                .await
-                .map_err(Error::Sqlx)?;
+        let user: User = sqlx::query_as(
+            "SELECT id, username, hashed_password, is_admin, token_version from users WHERE id = $1;",
+        )
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(Error::Sqlx)?;
 
         Ok(user)
     }
@@ -42,12 +61,13 @@ impl User {
     where
         E: PgExecutor<'a>,
     {
-        let user: User =
-            sqlx::query_as("SELECT id, username, hashed_password from users WHERE username = $1;")
-                .bind(username)
-                .fetch_one(db)
-                .await
-                .map_err(Error::Sqlx)?;
+        let user: User = sqlx::query_as(
+            "SELECT id, username, hashed_password, is_admin, token_version from users WHERE username = $1;",
+        )
+        .bind(username)
+        .fetch_one(db)
+        .await
+        .map_err(Error::Sqlx)?;
 
         Ok(user)
     }
@@ -85,6 +105,20 @@ impl User {
 
         Ok(result.id)
     }
+
+    // Invalidates every token minted for the user so far; returns the new token version
+    pub async fn revoke_tokens<'a, E>(id: i64, db: E) -> Result<i32, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        sqlx::query_scalar(
+            "UPDATE users SET token_version = token_version + 1 WHERE id = $1 RETURNING token_version;",
+        )
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .map_err(Error::Sqlx)
+    }
 }
 
 #[cfg(not(test))]
@@ -125,6 +159,7 @@ mod tests {
             .unwrap();
 
         assert_ne!(user.hashed_password, "password");
+        assert!(!user.is_admin());
 
         assert!(bcrypt::verify("password", &user.hashed_password).unwrap());
 
@@ -1,15 +1,17 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use askama::Template;
-use axum::extract::{ws::WebSocketUpgrade, Extension, Form, Path};
-use axum::http::StatusCode;
-use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::extract::{ws::WebSocketUpgrade, Extension, Form, Path, Query};
+use axum::http::{header, StatusCode};
+use axum::response::{Headers, Html, IntoResponse, Redirect, Response};
 use axum::routing::{get, post};
 use axum::Json;
 use axum::{AddExtensionLayer, Router};
 use axum_channels::registry::{RegistryMessage, RegistrySender};
 use axum_channels::ConnFormat;
 use cookie::{Cookie, Key};
+use parking_lot::Mutex;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde::Deserialize;
@@ -19,10 +21,17 @@ use tokio::sync::oneshot;
 use tower_cookies::{CookieManagerLayer, Cookies};
 use tracing::debug;
 
-use crate::session::{self, CurrentUser, SessionManager, SessionManagerLayer};
+use crate::scrabble::{self, persistence, Game, GameOptions, Language, TileSet};
+use crate::session::{self, CurrentUser, RequireAdmin, SessionManager, SessionManagerLayer};
 use crate::users;
 use crate::users::User;
 
+// the most games listed in the lobby
+static LOBBY_SIZE: i64 = 50;
+
+// the largest websocket message accepted; far more than any legitimate event needs
+static MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
 #[derive(Deserialize, Debug)]
 struct Registration {
     username: String,
@@ -31,6 +40,50 @@ struct Registration {
     _csrf_token: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct GameParams {
+    // seeds the shuffles of a game that doesn't exist yet (for reproducible games); the channel
+    // only takes it with the debug hooks
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NewCustomGame {
+    game_id: String,
+    seed: Option<u64>,
+    // either a custom tile set or a language's standard one
+    tile_set: Option<TileSet>,
+    language: Option<Language>,
+    #[serde(default)]
+    options: GameOptions,
+}
+
+#[derive(Deserialize, Debug)]
+struct NewGame {
+    // a random id is used when left blank
+    #[serde(default)]
+    game_id: String,
+    // house rules; the defaults apply to any left out
+    max_players: Option<usize>,
+    bingo_bonus: Option<isize>,
+    // a checkbox, so only sent when checked
+    #[serde(default)]
+    assist: bool,
+}
+
+impl NewGame {
+    fn options(&self) -> GameOptions {
+        let defaults = GameOptions::default();
+
+        GameOptions {
+            max_players: self.max_players.unwrap_or(defaults.max_players),
+            bingo_bonus: self.bingo_bonus.unwrap_or(defaults.bingo_bonus),
+            assist: self.assist,
+            ..defaults
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct Login {
     username: String,
@@ -38,16 +91,32 @@ struct Login {
 }
 
 pub fn app(registry: RegistrySender, pool: PgPool) -> Router {
-    Router::new()
+    let router = Router::new();
+
+    #[cfg(feature = "debug-hooks")]
+    let router = router.route("/admin/game/:name/rack", post(set_rack));
+
+    router
         .route("/", get(index))
         .route("/sign_up", get(new_registration))
         .route("/register", post(create_registration))
         .route("/login", get(new_login))
         .route("/login", post(create_login))
+        .route("/logout", post(logout))
         .route("/simple/websocket", get(ws_handler))
         .route("/play/:game_id", get(show_game))
+        .route("/play/:game_id/analysis", get(game_analysis))
+        .route("/play/:game_id/board.svg", get(board_svg))
+        .route("/play/:game_id/scoresheet.csv", get(scoresheet_csv))
         .route("/rand_game", get(rand_game))
+        .route("/lobby", get(lobby))
+        .route("/games", post(create_game))
         .route("/debug/registry", get(debug_registry))
+        .route("/admin/games", post(create_custom_game))
+        .route("/admin/games/:game_id", get(inspect_game))
+        .route("/admin/games/:game_id/ascii", get(inspect_game_ascii))
+        .route("/admin/games/:game_id/terminate", post(terminate_game))
+        .route("/admin/games/:game_id/repair", post(repair_game))
         .layer(
             tower::ServiceBuilder::new()
                 .layer(CookieManagerLayer::new())
@@ -77,11 +146,34 @@ async fn create_login(
         .await
         .map_err(Error::User)?;
 
-    session.set_user_id(Some(user.id));
+    session.set_user(&user);
+
+    Ok(Redirect::to(login_destination(&session).parse().unwrap()))
+}
 
-    let location = session.take_login_redirect().unwrap_or_else(|| "/".into());
+// Also revokes the user's cookies and game tokens, so other browsers and pages left open can't
+// keep playing
+async fn logout(
+    Extension(pool): Extension<PgPool>,
+    Extension(session): Extension<SessionManager>,
+) -> Result<Redirect, Error> {
+    if let Some(user_id) = session.user_id() {
+        User::revoke_tokens(user_id, &pool)
+            .await
+            .map_err(Error::User)?;
+    }
 
-    Ok(Redirect::to(location.parse().unwrap()))
+    session.set_user_id(None);
+
+    Ok(Redirect::to("/".parse().unwrap()))
+}
+
+// The page that sent the user to log in (stored by `CurrentUser`), if it's local; otherwise "/"
+fn login_destination(session: &SessionManager) -> String {
+    session
+        .take_login_redirect()
+        .and_then(|location| session::sanitize_redirect(&location))
+        .unwrap_or_else(|| "/".into())
 }
 
 async fn create_registration(
@@ -103,11 +195,175 @@ async fn debug_registry(_: CurrentUser, Extension(registry): Extension<RegistryS
     rx.await.unwrap()
 }
 
+// Applies the automatic fixes from `Game::repair` to the stored game. A live channel for the
+// game holds its own copy, and would save over the repair, so it's refused until that closes.
+async fn repair_game(
+    Path(game_id): Path<String>,
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let name = game_name(&game_id);
+    require_not_live(&name)?;
+
+    let mut game = persistence::fetch(&name, &pool)
+        .await
+        .map_err(Error::Game)?;
+    let changes = game.repair();
+
+    if !changes.is_empty() {
+        game.persist(&pool).await.map_err(Error::Game)?;
+    }
+
+    Ok(Json(json!({
+        "changes": changes,
+        "remaining_issues": game.validate_full(),
+    })))
+}
+
+#[cfg(feature = "debug-hooks")]
+#[derive(Deserialize, Debug)]
+struct SetRack {
+    player_index: usize,
+    rack: Vec<scrabble::Tile>,
+}
+
+// Forces a player's rack (to reproduce a bug report). Like repair, a live channel for this game
+// will overwrite it on its next save.
+#[cfg(feature = "debug-hooks")]
+async fn set_rack(
+    Path(name): Path<String>,
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+    Json(params): Json<SetRack>,
+) -> Result<Json<serde_json::Value>, Error> {
+    let mut game = persistence::fetch(&name, &pool)
+        .await
+        .map_err(Error::Game)?;
+    game.set_rack(params.player_index, params.rack)
+        .map_err(Error::Game)?;
+    game.persist(&pool).await.map_err(Error::Game)?;
+
+    Ok(Json(json!({ "issues": game.validate_full() })))
+}
+
+// Creates a game with a custom tile set before anyone has joined it; players then join it at
+// /play/:game_id as usual.
+async fn create_custom_game(
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+    Json(params): Json<NewCustomGame>,
+) -> Result<Json<serde_json::Value>, Error> {
+    require_valid_game_id(&params.game_id)?;
+
+    let tile_set = match (params.tile_set, params.language) {
+        (Some(tile_set), None) => tile_set,
+        (None, Some(language)) => language.tile_set(),
+        _ => {
+            return Err(Error::BadRequest(
+                "expected either a tile_set or a language".into(),
+            ))
+        }
+    };
+
+    let name = game_name(&params.game_id);
+    match persistence::fetch(&name, &pool).await {
+        Err(scrabble::Error::Sqlx(sqlx::Error::RowNotFound)) => (),
+        Ok(_) => return Err(Error::BadRequest("game already exists".into())),
+        Err(e) => return Err(Error::Game(e)),
+    }
+
+    let channel_id = format!("game:{}", name)
+        .parse()
+        .map_err(|_| Error::BadRequest(format!("invalid game id {:?}", params.game_id)))?;
+    let mut game = Game::with_tile_set(channel_id, params.seed, tile_set, params.options)
+        .map_err(Error::Game)?;
+    game.persist(&pool).await.map_err(Error::Game)?;
+
+    Ok(Json(json!({
+        "game_id": params.game_id,
+        "url": format!("/play/{}", params.game_id),
+    })))
+}
+
+async fn inspect_game(
+    Path(game_id): Path<String>,
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let name = game_name(&game_id);
+    let game = persistence::fetch_recent(&name, &pool)
+        .await
+        .map_err(Error::Game)?;
+
+    Ok(Json(json!({
+        "name": name,
+        "state": game.state(),
+        "players": game.players(),
+        "current_player": game.current_player(),
+        "options": game.options(),
+        "live": crate::channel_is_live(&name),
+        "issues": game.validate_full(),
+    })))
+}
+
+// The board as `to_ascii` draws it (no racks), for reproducing a reported position
+async fn inspect_game_ascii(
+    Path(game_id): Path<String>,
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Response, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let game = persistence::fetch_recent(&game_name(&game_id), &pool)
+        .await
+        .map_err(Error::Game)?;
+
+    Ok(ascii_board(&game).into_response())
+}
+
+fn ascii_board(game: &Game) -> (Headers<Vec<(header::HeaderName, &'static str)>>, String) {
+    (
+        Headers(vec![(header::CONTENT_TYPE, "text/plain; charset=utf-8")]),
+        game.to_ascii(None),
+    )
+}
+
+// Ends a stuck game. A live channel for it is woken to pick this up, and closes; until then,
+// its saves are refused (see `Game::update`).
+async fn terminate_game(
+    Path(game_id): Path<String>,
+    RequireAdmin(_): RequireAdmin,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let name = game_name(&game_id);
+    let mut game = persistence::fetch_recent(&name, &pool)
+        .await
+        .map_err(Error::Game)?;
+    game.terminate().map_err(Error::Game)?;
+    game.persist(&pool).await.map_err(Error::Game)?;
+    persistence::retain_finished(&game);
+
+    Ok(Json(json!({
+        "name": name,
+        "state": game.state(),
+        "live": crate::request_termination(&name),
+    })))
+}
+
 enum Error {
     PasswordConfirmation,
     #[allow(dead_code)]
     Csrf,
     User(users::Error),
+    BadRequest(String),
+    Conflict(String),
+    Game(scrabble::Error),
 }
 
 impl IntoResponse for Error {
@@ -123,6 +379,12 @@ impl IntoResponse for Error {
                 "Invalid CSRF token".to_string(),
             ),
             Error::User(e) => (StatusCode::UNPROCESSABLE_ENTITY, format!("{:?}", e)),
+            Error::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Error::Conflict(message) => (StatusCode::CONFLICT, message),
+            Error::Game(scrabble::Error::Sqlx(sqlx::Error::RowNotFound)) => {
+                (StatusCode::NOT_FOUND, "Game not found".to_string())
+            }
+            Error::Game(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
         };
 
         let body = Json(json!({
@@ -170,27 +432,256 @@ async fn new_registration() -> Html<String> {
 }
 
 // FIXME: move boilerplate into lib
+// Only browsers with a logged in session cookie may open a socket. Channels still identify the
+// player by the token in the join payload: `handle_connect` has no way to hand the handshake's
+// session to the channel.
 async fn ws_handler(
     ws: WebSocketUpgrade,
     Extension(registry): Extension<RegistrySender>,
-    Extension(_pg_pool): Extension<PgPool>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| {
-        axum_channels::handle_connect(socket, ConnFormat::Phoenix, registry)
-    })
+    Extension(session): Extension<SessionManager>,
+) -> Response {
+    if let Err(status) = socket_user_id(&session) {
+        return status.into_response();
+    }
+
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .max_frame_size(MAX_MESSAGE_SIZE)
+        .on_upgrade(move |socket| {
+            axum_channels::handle_connect(socket, ConnFormat::Phoenix, registry)
+        })
+        .into_response()
+}
+
+fn socket_user_id(session: &SessionManager) -> Result<i64, StatusCode> {
+    session.user_id().ok_or(StatusCode::UNAUTHORIZED)
 }
 
-async fn show_game(Path(game_id): Path<String>, CurrentUser(user): CurrentUser) -> Html<String> {
-    let session = session::Session::from(&user);
+async fn show_game(
+    Path(game_id): Path<String>,
+    Query(params): Query<GameParams>,
+    CurrentUser(user): CurrentUser,
+    Extension(session): Extension<SessionManager>,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Html<String>, Error> {
+    require_valid_game_id(&game_id)?;
+
+    // minted from the cookie, which `CurrentUser` has checked is still current for the user
     let token = session.token();
+    let seed = params.seed.map(|seed| seed.to_string()).unwrap_or_default();
+    let initial_state = initial_state(&game_id, &user.username, &pool).await?;
 
     let template = GameTemplate {
         game_id: game_id.as_str(),
         token: token.as_str(),
         player: user.username.as_str(),
+        seed: seed.as_str(),
+        initial_state: initial_state.as_str(),
     };
 
-    Html(template.render().unwrap())
+    Ok(Html(template.render().unwrap()))
+}
+
+// The user's view of the game (a spectator's if they aren't seated), so the board renders before
+// the socket connects; an empty board for a game that hasn't been created yet.
+async fn initial_state(game_id: &str, username: &str, pool: &PgPool) -> Result<String, Error> {
+    let name = game_name(game_id);
+    let game = match persistence::fetch_recent(&name, pool).await {
+        Ok(game) => game,
+        Err(scrabble::Error::Sqlx(sqlx::Error::RowNotFound)) => {
+            let channel_id = format!("game:{}", name)
+                .parse()
+                .map_err(|_| Error::BadRequest(format!("invalid game id {:?}", game_id)))?;
+            Game::new(channel_id)
+        }
+        Err(e) => return Err(Error::Game(e)),
+    };
+
+    let index = game
+        .players()
+        .iter()
+        .position(|player| player.as_str() == username);
+
+    Ok(script_json(&game.state_for(index)))
+}
+
+// JSON to embed in a <script> element, which mustn't contain "</"
+fn script_json(value: &serde_json::Value) -> String {
+    value.to_string().replace("</", "<\\/")
+}
+
+// Lists games waiting for players
+async fn lobby(_: CurrentUser, Extension(pool): Extension<PgPool>) -> Result<Html<String>, Error> {
+    let games: Vec<LobbyGame> = persistence::list_joinable(LOBBY_SIZE, &pool)
+        .await
+        .map_err(Error::Game)?
+        .iter()
+        .filter_map(LobbyGame::from_game)
+        .collect();
+
+    let template = LobbyTemplate { games };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Creates the game if it doesn't exist yet, then sends the user to it either way
+async fn create_game(
+    _: CurrentUser,
+    Extension(pool): Extension<PgPool>,
+    Form(params): Form<NewGame>,
+) -> Result<Redirect, Error> {
+    let game_id = match params.game_id.trim() {
+        "" => random_game_id(),
+        game_id => game_id.to_string(),
+    };
+    require_valid_game_id(&game_id)?;
+
+    let name = game_name(&game_id);
+    match persistence::fetch(&name, &pool).await {
+        Ok(_) => (),
+        Err(scrabble::Error::Sqlx(sqlx::Error::RowNotFound)) => {
+            let channel_id = format!("game:{}", name)
+                .parse()
+                .map_err(|_| Error::BadRequest(format!("invalid game id {:?}", game_id)))?;
+            Game::with_options(channel_id, None, params.options())
+                .map_err(Error::Game)?
+                .persist(&pool)
+                .await
+                .map_err(Error::Game)?;
+        }
+        Err(e) => return Err(Error::Game(e)),
+    }
+
+    Ok(Redirect::to(format!("/play/{}", game_id).parse().unwrap()))
+}
+
+// the name a game is stored under; matches the channel id joined by the client (see js/scrabble.js)
+pub(crate) fn game_name(game_id: &str) -> String {
+    format!("{}-000000", game_id)
+}
+
+fn game_id_from_name(name: &str) -> Option<&str> {
+    name.strip_suffix("-000000")
+}
+
+// The channel validates the name the game is stored under, so check that here too; an id that
+// only fits without the suffix couldn't be joined.
+fn require_not_live(name: &str) -> Result<(), Error> {
+    if crate::channel_is_live(name) {
+        return Err(Error::Conflict(format!(
+            "{} is open in a channel; try again once it's closed",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+fn require_valid_game_id(game_id: &str) -> Result<(), Error> {
+    (!game_id.is_empty() && scrabble::valid_game_id(&game_name(game_id)))
+        .then(|| ())
+        .ok_or_else(|| Error::BadRequest(format!("invalid game id {:?}", game_id)))
+}
+
+// analyses kept in memory; the one viewed longest ago makes room for a new one
+static MAX_ANALYSES: usize = 100;
+
+lazy_static::lazy_static! {
+    // finished games can't change, so an analysis is computed once and kept while it's viewed
+    static ref ANALYSES: Mutex<Analyses> = Mutex::new(Analyses::default());
+}
+
+#[derive(Default)]
+struct Analyses {
+    // by game name, with the view count as of the last time it was viewed
+    games: HashMap<String, (u64, serde_json::Value)>,
+    views: u64,
+}
+
+impl Analyses {
+    fn get(&mut self, name: &str) -> Option<serde_json::Value> {
+        let (viewed, analysis) = self.games.get_mut(name)?;
+        self.views += 1;
+        *viewed = self.views;
+        Some(analysis.clone())
+    }
+
+    fn insert(&mut self, name: String, analysis: serde_json::Value) {
+        if self.games.len() >= MAX_ANALYSES && !self.games.contains_key(&name) {
+            let oldest = self
+                .games
+                .iter()
+                .min_by_key(|(_, (viewed, _))| *viewed)
+                .map(|(name, _)| name.clone());
+
+            if let Some(oldest) = oldest {
+                self.games.remove(&oldest);
+            }
+        }
+
+        self.views += 1;
+        self.games.insert(name, (self.views, analysis));
+    }
+}
+
+async fn game_analysis(
+    Path(game_id): Path<String>,
+    _: CurrentUser,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let name = game_name(&game_id);
+
+    if let Some(analysis) = ANALYSES.lock().get(&name) {
+        return Ok(Json(analysis));
+    }
+
+    let game = persistence::fetch_recent(&name, &pool)
+        .await
+        .map_err(Error::Game)?;
+    let analysis = json!(game.analyze().await.map_err(Error::Game)?);
+    ANALYSES.lock().insert(name, analysis.clone());
+
+    Ok(Json(analysis))
+}
+
+// The current board as an image, for sharing; it shows nothing a spectator couldn't see, so no
+// login is needed
+async fn board_svg(
+    Path(game_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Response, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let game = persistence::fetch_recent(&game_name(&game_id), &pool)
+        .await
+        .map_err(Error::Game)?;
+
+    Ok((
+        Headers(vec![(header::CONTENT_TYPE, "image/svg+xml")]),
+        game.board_svg(),
+    )
+        .into_response())
+}
+
+// The scores so far (or final scores) for record-keeping; like the board, nothing secret
+async fn scoresheet_csv(
+    Path(game_id): Path<String>,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Response, Error> {
+    require_valid_game_id(&game_id)?;
+
+    let game = persistence::fetch_recent(&game_name(&game_id), &pool)
+        .await
+        .map_err(Error::Game)?;
+
+    Ok((
+        Headers(vec![
+            (header::CONTENT_TYPE, "text/csv"),
+            (header::CONTENT_DISPOSITION, "attachment"),
+        ]),
+        game.to_csv(),
+    )
+        .into_response())
 }
 
 #[derive(Template)]
@@ -199,12 +690,42 @@ struct GameTemplate<'a> {
     game_id: &'a str,
     token: &'a str,
     player: &'a str,
+    seed: &'a str,
+    initial_state: &'a str,
+}
+
+struct LobbyGame {
+    game_id: String,
+    players: String,
+}
+
+impl LobbyGame {
+    fn from_game(game: &Game) -> Option<Self> {
+        Some(LobbyGame {
+            game_id: game_id_from_name(game.name())?.to_string(),
+            players: game
+                .players()
+                .iter()
+                .map(|player| player.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "lobby.html")]
+struct LobbyTemplate {
+    games: Vec<LobbyGame>,
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate<'a> {
-    info: &'a str,
+    // `None` when logged out
+    username: Option<&'a str>,
+    // the user's unfinished games, to resume
+    games: Vec<LobbyGame>,
 }
 
 #[derive(Template)]
@@ -219,22 +740,46 @@ struct NewLoginTemplate {
     csrf_token: String,
 }
 
-async fn index(Extension(session): Extension<SessionManager>) -> Html<String> {
-    let info = format!("{:#?}\n{}", session, session.current_hash());
+// Logged out visitors are welcome here, so the user is looked up without `CurrentUser`, whose
+// rejection would store this page as the place to return to after logging in
+async fn index(
+    Extension(session): Extension<SessionManager>,
+    Extension(pool): Extension<PgPool>,
+) -> Result<Html<String>, Error> {
+    let user = match session.user_id() {
+        Some(user_id) => User::find(user_id, &pool)
+            .await
+            .ok()
+            .filter(|user| session.is_current_for(user)),
+        None => None,
+    };
+    let games = match &user {
+        Some(user) => persistence::list_for_player(&user.username, LOBBY_SIZE, &pool)
+            .await
+            .map_err(Error::Game)?
+            .iter()
+            .filter_map(LobbyGame::from_game)
+            .collect(),
+        None => vec![],
+    };
+
     let template = IndexTemplate {
-        info: info.as_str(),
+        username: user.as_ref().map(|user| user.username.as_str()),
+        games,
     };
-    Html(template.render().unwrap())
+    Ok(Html(template.render().unwrap()))
 }
 
 async fn rand_game(_: CurrentUser) -> Redirect {
-    let rand_string: String = thread_rng()
+    Redirect::to(format!("/play/{}", random_game_id()).parse().unwrap())
+}
+
+pub(crate) fn random_game_id() -> String {
+    thread_rng()
         .sample_iter(&Alphanumeric)
         .take(30)
         .map(char::from)
-        .collect();
-
-    Redirect::to(format!("/play/{}", rand_string).parse().unwrap())
+        .collect()
 }
 
 mod assets {
@@ -250,3 +795,150 @@ mod assets {
         include_str!("../assets/index.css")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_page_includes_board() {
+        let mut game = Game::new("game:abc-000000".parse().unwrap());
+        game.add_player(scrabble::Player::from("ada")).unwrap();
+        let state = game.state_for(Some(0));
+        let initial_state = script_json(&state);
+
+        let template = GameTemplate {
+            game_id: "abc",
+            token: "token",
+            player: "ada",
+            seed: "",
+            initial_state: initial_state.as_str(),
+        };
+        let html = template.render().unwrap();
+
+        assert!(html.contains(&state["game"]["board"].to_string()));
+        assert!(html.contains(r#"<script id="initial-state" type="application/json">"#));
+    }
+
+    #[test]
+    fn test_index_lists_games_to_resume() {
+        let mut game = Game::new("game:abc-000000".parse().unwrap());
+        game.add_player(scrabble::Player::from("ada")).unwrap();
+        game.add_player(scrabble::Player::from("frankie")).unwrap();
+
+        let template = IndexTemplate {
+            username: Some("ada"),
+            games: LobbyGame::from_game(&game).into_iter().collect(),
+        };
+        let html = template.render().unwrap();
+        assert!(html.contains(r#"<a href="/play/abc">abc</a>"#));
+        assert!(html.contains("ada, frankie"));
+        assert!(!html.contains(r#"href="/login""#));
+
+        let template = IndexTemplate {
+            username: None,
+            games: vec![],
+        };
+        let html = template.render().unwrap();
+        assert!(html.contains(r#"href="/login""#));
+        assert!(html.contains(r#"href="/sign_up""#));
+        assert!(!html.contains("/play/"));
+    }
+
+    #[test]
+    fn test_no_repair_while_live() {
+        assert!(require_not_live("repaired-000000").is_ok());
+
+        let refused = Error::Conflict("open in a channel".into()).into_response();
+        assert_eq!(refused.status(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_script_json() {
+        let value = json!({ "player": "</script><script>alert(1)</script>" });
+        let embedded = script_json(&value);
+
+        assert!(!embedded.contains("</"));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&embedded).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_socket_requires_session() {
+        let session = SessionManager::new(session::Session::new());
+        assert_eq!(socket_user_id(&session), Err(StatusCode::UNAUTHORIZED));
+
+        session.set_user_id(Some(7));
+        assert_eq!(socket_user_id(&session), Ok(7));
+    }
+
+    #[test]
+    fn test_ascii_board() {
+        let mut rows = vec!["15"; 15];
+        rows[7] = "7Q7";
+        let position = format!("{} 0 AEILNRT/VVWWKJX", rows.join("/"));
+        let game = Game::from_position_string("game:abc".parse().unwrap(), &position).unwrap();
+
+        let (Headers(headers), body) = ascii_board(&game);
+        assert_eq!(
+            headers,
+            vec![(header::CONTENT_TYPE, "text/plain; charset=utf-8")]
+        );
+
+        // the Q is on H8
+        let row = body.lines().nth(8).unwrap();
+        assert!(row.starts_with(" 8 "));
+        assert_eq!(&row[3 + 7 * 3..3 + 7 * 3 + 2], "Q ");
+        assert!(!body.contains("Rack"));
+    }
+
+    #[test]
+    fn test_analyses_are_bounded() {
+        let mut analyses = Analyses::default();
+        for i in 0..MAX_ANALYSES {
+            analyses.insert(format!("game-{}", i), json!(i));
+        }
+
+        // viewing the oldest keeps it; the next oldest makes room instead
+        assert_eq!(analyses.get("game-0"), Some(json!(0)));
+        analyses.insert("game-new".into(), json!("new"));
+
+        assert_eq!(analyses.games.len(), MAX_ANALYSES);
+        assert!(analyses.games.contains_key("game-0"));
+        assert!(!analyses.games.contains_key("game-1"));
+        assert!(analyses.games.contains_key("game-new"));
+    }
+
+    #[test]
+    fn test_game_id_from_name() {
+        assert_eq!(game_id_from_name(&game_name("abc")), Some("abc"));
+        assert_eq!(game_id_from_name("abc"), None);
+    }
+
+    #[test]
+    fn test_game_id_leaves_room_for_the_suffix() {
+        let longest = scrabble::MAX_GAME_ID_LENGTH - "-000000".len();
+        assert!(require_valid_game_id(&"a".repeat(longest)).is_ok());
+        assert!(require_valid_game_id(&"a".repeat(longest + 1)).is_err());
+        assert!(require_valid_game_id("").is_err());
+    }
+
+    #[test]
+    fn test_login_destination() {
+        let session = SessionManager::new(session::Session::new());
+        assert_eq!(login_destination(&session), "/");
+
+        // as set when a logged out user requests a protected page
+        session.set_login_redirect(Some("/play/abc?seed=1".into()));
+        assert_eq!(login_destination(&session), "/play/abc?seed=1");
+        // only used once
+        assert_eq!(login_destination(&session), "/");
+
+        session.set_login_redirect(Some("https://example.com/".into()));
+        assert_eq!(login_destination(&session), "/");
+        session.set_login_redirect(Some("//example.com/".into()));
+        assert_eq!(login_destination(&session), "/");
+    }
+}